@@ -0,0 +1,64 @@
+use std::{
+  env,
+  path::Path,
+  process::Command,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+// Embeds build metadata reported by the `/api/v1/health/info` endpoint.
+fn main() {
+  // The commit can be passed explicitly when building without the git directory, e.g. in Docker.
+  let git_commit = env::var("GIT_COMMIT")
+    .ok()
+    .filter(|commit| !commit.is_empty())
+    .or_else(|| command_output("git", &["rev-parse", "--short", "HEAD"]))
+    .unwrap_or_else(|| "unknown".to_string());
+
+  // Honour SOURCE_DATE_EPOCH for reproducible builds.
+  let build_timestamp = env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs())
+      .unwrap_or_default()
+      .to_string()
+  });
+
+  let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+  let rustc_version =
+    command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+
+  let mut features: Vec<String> = env::vars()
+    .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+    .map(|feature| feature.to_lowercase().replace('_', "-"))
+    .collect();
+  features.sort();
+
+  println!("cargo:rustc-env=GIT_COMMIT={git_commit}");
+  println!("cargo:rustc-env=BUILD_TIMESTAMP={build_timestamp}");
+  println!("cargo:rustc-env=RUSTC_VERSION={rustc_version}");
+  println!("cargo:rustc-env=CARGO_FEATURES={}", features.join(","));
+
+  // Rebuild the metadata when the inputs change rather than on every source change.
+  for key in ["GIT_COMMIT", "SOURCE_DATE_EPOCH", "RUSTC"] {
+    println!("cargo:rerun-if-env-changed={key}");
+  }
+  // A checkout or a commit moves HEAD or the ref it points to, which may be packed. Only existing
+  // paths are watched, since cargo reruns the script on every build for missing ones.
+  if let Some(git_dir) = command_output("git", &["rev-parse", "--git-dir"]) {
+    for path in ["HEAD", "refs/heads", "packed-refs"] {
+      let path = Path::new(&git_dir).join(path);
+      if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+      }
+    }
+  }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+  let output = Command::new(program).args(args).output().ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  let stdout = String::from_utf8(output.stdout).ok()?;
+  Some(stdout.trim().to_string())
+}
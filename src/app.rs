@@ -1,29 +1,125 @@
-use async_graphql::{dynamic, http::GraphiQLSource};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use async_graphql::{
+  dynamic,
+  futures_util::{SinkExt, StreamExt},
+  http::GraphiQLSource,
+  parser::types::OperationType,
+};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
-  extract::State,
-  response::Html,
+  extract::{
+    ws::{close_code, CloseFrame, Message, WebSocketUpgrade},
+    State,
+  },
+  http::{header, HeaderMap},
+  response::{Html, IntoResponse, Response},
   routing::{get, post},
-  Router,
+  Extension, Router,
 };
-use utoipa::OpenApi;
-use utoipa_swagger_ui::{BasicAuth, Config as SwaggerConfig, SwaggerUi};
+use tokio::sync::oneshot;
+use utoipa_swagger_ui::{Config as SwaggerConfig, SwaggerUi};
 
-use crate::common::utils;
-use crate::common::{cfg::Config, middleware, telemetry};
+use crate::common::{
+  api_error::{self, ApiError},
+  utils::auth::{basic_auth_layer, BasicAuth},
+};
+use crate::common::{
+  cfg::Config,
+  events::EventBus,
+  graphql::{
+    limits::Limits,
+    persisted::PersistedQueries,
+    telemetry::{self as graphql_telemetry, RequestId, Telemetry},
+  },
+  metrics, middleware,
+  rate_limit::{self, RateLimiter},
+  read_your_writes,
+  security_headers::{self, CspNonce, SecurityHeaders},
+  settings::Settings,
+  shutdown::Shutdown,
+  telemetry::{self, LogFilter},
+  transaction::Tx,
+};
 use crate::database::Db;
 use crate::doc;
-use crate::modules::{self, auth::guards::auth_guard};
+use crate::modules::{
+  self,
+  auth::guards::{admin_guard, auth_guard, optional_auth_guard},
+  health::checks::HealthRegistry,
+  repositories::{Repositories, SeaOrmRepositories},
+  users::{dto::UserDto, events::UserEvent},
+};
 use crate::query_root;
 
 #[derive(Clone)]
 pub struct AppState {
   pub db: Db,
   pub cfg: Config,
+  pub health: HealthRegistry,
+  pub started_at: Instant,
+  pub shutdown: Shutdown,
+  pub settings: Settings,
+  pub rate_limiter: RateLimiter,
+  pub repositories: Arc<dyn Repositories>,
+  pub user_events: EventBus<UserEvent>,
+  /// The basic auth users allowed to see the configuration on the info endpoint.
+  pub info_basic_auth: Arc<BasicAuth>,
+}
+
+impl AppState {
+  pub fn new(cfg: Config, db: Db) -> Self {
+    let health = modules::health_checks(&cfg, &db);
+    let settings = Settings::new(cfg.runtime.clone());
+    let info_basic_auth = BasicAuth::new(
+      "Info",
+      cfg.info_basic_auth.clone(),
+      cfg.trusted_proxies.clone(),
+    );
+    Self {
+      db,
+      cfg,
+      health,
+      started_at: Instant::now(),
+      shutdown: Shutdown::new(),
+      settings,
+      rate_limiter: RateLimiter::new(),
+      repositories: Arc::new(SeaOrmRepositories),
+      user_events: EventBus::new(),
+      info_basic_auth,
+    }
+  }
+
+  /// Replaces the repositories, e.g. with mocks in tests.
+  pub fn with_repositories(mut self, repositories: Arc<dyn Repositories>) -> Self {
+    self.repositories = repositories;
+    self
+  }
+
+  /// Applies log filter changes from runtime settings reloads to the tracing subscriber.
+  pub fn with_log_filter(mut self, log_filter: LogFilter) -> Self {
+    self.settings = self.settings.with_log_filter(log_filter);
+    self
+  }
 }
 
-pub fn router(cfg: Config, db: Db) -> Router {
-  let app_state = AppState { db, cfg };
+/// State of the GraphQL endpoint.
+#[derive(Clone)]
+struct GraphQLState {
+  schema: dynamic::Schema,
+  persisted: PersistedQueries,
+  operation_labels: Arc<metrics::OperationLabels>,
+  app: AppState,
+}
+
+pub fn router(app_state: AppState) -> Router {
+  // Install the Prometheus recorder before any request is served.
+  if app_state.cfg.metrics_enabled {
+    metrics::handle();
+  }
 
   // Middleware that adds high level tracing to a Service.
   // Trace comes with good defaults but also supports customizing many aspects of the output:
@@ -37,7 +133,8 @@ pub fn router(cfg: Config, db: Db) -> Router {
   let propagate_request_id_layer = middleware::propagate_request_id_layer();
 
   // Layer that applies the Cors middleware which adds headers for CORS.
-  let cors_layer = middleware::cors_layer();
+  // Allowed origins follow the runtime settings.
+  let cors_layer = middleware::cors_layer(app_state.settings.clone());
 
   // Layer that applies the Timeout middleware, which sets a timeout for requests.
   // The default value is 15 seconds.
@@ -47,88 +144,351 @@ pub fn router(cfg: Config, db: Db) -> Router {
   // will be changed to `/foo` before reaching the internal service.
   let normalize_path_layer = middleware::normalize_path_layer();
 
+  // Sets HSTS, the content security policies and the other security headers on every response.
+  let security_headers_layer = axum::middleware::from_fn_with_state(
+    SecurityHeaders::from_config(&app_state.cfg),
+    security_headers::security_headers,
+  );
+
+  // Rejects clients exceeding the rate limit from the runtime settings.
+  let rate_limit_layer =
+    axum::middleware::from_fn_with_state(app_state.clone(), rate_limit::rate_limit);
+
+  // Only reports the underlying errors to clients where the environment allows it.
+  let error_details_layer = axum::middleware::from_fn_with_state(
+    app_state.cfg.profile.error_details,
+    api_error::error_details,
+  );
+
+  // Records request count, latency and in-flight requests for every matched route.
+  let metrics_layer = axum::middleware::from_fn(metrics::track_metrics);
+
   // Create the router with the routes.
-  let router = modules::router(axum::extract::State(app_state.clone()));
+  // Clients that wrote read from the primary for a while, see `read_your_writes`.
+  let router = modules::router(axum::extract::State(app_state.clone())).layer(
+    axum::middleware::from_fn_with_state(app_state.clone(), read_your_writes::read_your_writes),
+  );
 
   // Create the API documentation using OpenAPI and Swagger UI.
   let api_doc = SwaggerUi::new(app_state.cfg.swagger_endpoint.clone())
     .url(
       app_state.cfg.swagger_endpoint.clone() + "/api-doc/openapi.json",
-      doc::ApiDoc::openapi(),
+      doc::openapi(&app_state.cfg.session_cookie),
     )
-    .config({
-      let mut config = SwaggerConfig::default().persist_authorization(true);
-      if !app_state.cfg.swagger_basic_auth.is_empty() {
-        let parts: Vec<&str> = app_state.cfg.swagger_basic_auth.split(':').collect();
-        if parts.len() == 2 {
-          config = config.basic_auth(BasicAuth {
-            username: parts[0].to_string(),
-            password: parts[1].to_string(),
-          });
-        } else {
-          panic!("Invalid format for swagger_basic_auth. Expected 'username:password'.");
-        }
-      }
-      config
-    });
+    .config(SwaggerConfig::default().persist_authorization(true));
+
+  let persisted = PersistedQueries::from_config(&app_state.cfg, app_state.db.writer().clone());
 
   // Create the GraphQL schema using the query root.
-  let schema = query_root::schema(app_state.db.conn.clone(), None, None).unwrap();
+  // Mutations use the primary, queries are given a reader per request.
+  let schema = query_root::schema(
+    app_state.db.writer().clone(),
+    query_root::Services {
+      repositories: app_state.repositories.clone(),
+      user_events: app_state.user_events.clone(),
+      jwt_secret: app_state.cfg.jwt_secret.clone(),
+    },
+    Limits::from_config(&app_state.cfg),
+    persisted.clone(),
+    Telemetry {
+      error_details: app_state.cfg.profile.error_details,
+    },
+  )
+  .unwrap();
+  let operation_labels = Arc::new(metrics::OperationLabels::new(
+    persisted
+      .allowlist
+      .iter()
+      .flat_map(|allowlist| allowlist.operation_names())
+      .collect::<Vec<_>>(),
+  ));
+  let graphql_state = GraphQLState {
+    schema,
+    persisted,
+    operation_labels,
+    app: app_state.clone(),
+  };
   let graphql_router = Router::new().nest(
     &app_state.cfg.graphql_endpoint,
     Router::new()
       .merge({
-        let mut router = Router::new().route("/", get(graphql_playground));
-        if !app_state.cfg.graphql_basic_auth.is_empty() {
-          let parts: Vec<&str> = app_state.cfg.graphql_basic_auth.split(':').collect();
-          if parts.len() == 2 {
-            router = router.layer(axum::middleware::from_fn({
-              let auth_config = app_state.clone();
-              move |req, next| {
-                let auth_config = auth_config.clone();
-                async move { utils::auth::basic_auth_layer(State(auth_config), req, next).await }
-              }
-            }));
-          } else {
-            panic!("Invalid format for graphql_basic_auth. Expected 'username:password'.");
-          }
+        // GraphiQL is only mounted in environments that expose the API documentation, and the
+        // schema where it can be introspected anyway.
+        let mut router = Router::new();
+        if app_state.cfg.profile.mount_docs {
+          router = router.route("/", get(graphql_playground));
+        }
+        if app_state.cfg.graphql_introspection {
+          router = router.route(
+            "/schema.graphql",
+            get(graphql_sdl).with_state(graphql_state.clone()),
+          );
+        }
+        if app_state.cfg.graphql_basic_auth.is_enabled() {
+          router = router.layer(axum::middleware::from_fn_with_state(
+            BasicAuth::new(
+              "GraphQL",
+              app_state.cfg.graphql_basic_auth.clone(),
+              app_state.cfg.trusted_proxies.clone(),
+            ),
+            basic_auth_layer,
+          ));
         }
         router
       })
       .merge(
         Router::new()
           .route("/", post(graphql_handler))
-          .with_state(schema)
+          .with_state(graphql_state.clone())
+          // Anonymous requests can log in, the guards of the schema protect the rest.
           .layer(axum::middleware::from_fn_with_state(
             app_state.clone(),
-            auth_guard,
+            optional_auth_guard,
           )),
+      )
+      // Subscriptions authenticate with the `connection_init` payload instead of a header.
+      .merge(
+        Router::new()
+          .route("/ws", get(graphql_ws_handler))
+          .with_state(graphql_state),
       ),
   );
 
+  // Expose metrics on the main listener, restricted to admins, unless a dedicated
+  // metrics listener is configured.
+  let metrics_router =
+    if app_state.cfg.metrics_enabled && app_state.cfg.metrics_listen_address.is_none() {
+      Router::new()
+        .route(
+          &app_state.cfg.metrics_endpoint,
+          get(metrics::metrics_handler),
+        )
+        .layer(axum::middleware::from_fn(admin_guard))
+        .layer(axum::middleware::from_fn_with_state(
+          app_state.clone(),
+          auth_guard,
+        ))
+    } else {
+      Router::new()
+    };
+
+  // The Swagger UI is only mounted in environments that expose the API documentation.
+  let api_doc = if app_state.cfg.profile.mount_docs {
+    let mut router = Router::from(api_doc).route(
+      &(app_state.cfg.swagger_endpoint.clone() + "/api-doc/openapi.yaml"),
+      get(openapi_yaml).with_state(app_state.clone()),
+    );
+    if app_state.cfg.swagger_basic_auth.is_enabled() {
+      router = router.layer(axum::middleware::from_fn_with_state(
+        BasicAuth::new(
+          "Swagger",
+          app_state.cfg.swagger_basic_auth.clone(),
+          app_state.cfg.trusted_proxies.clone(),
+        ),
+        basic_auth_layer,
+      ));
+    }
+    router
+  } else {
+    Router::new()
+  };
+
   // Combine all the routes and apply the middleware layers.
   // The order of the layers is important. The first layer is the outermost layer.
   Router::new()
     .merge(router)
     .merge(api_doc)
     .merge(graphql_router)
+    .merge(metrics_router)
+    .route_layer(metrics_layer)
+    .layer(rate_limit_layer)
+    .layer(error_details_layer)
     .layer(normalize_path_layer)
     .layer(cors_layer)
     .layer(timeout_layer)
+    .layer(security_headers_layer)
     .layer(propagate_request_id_layer)
     .layer(trace_layer)
     .layer(request_id_layer)
     .with_state(app_state)
 }
 
+/// Creates the router served by the dedicated metrics listener.
+pub fn metrics_router(app_state: AppState) -> Router {
+  Router::new()
+    .route(
+      &app_state.cfg.metrics_endpoint,
+      get(metrics::metrics_handler),
+    )
+    .with_state(app_state)
+}
+
 async fn graphql_handler(
-  schema: axum::extract::State<dynamic::Schema>,
+  State(graphql): State<GraphQLState>,
+  headers: HeaderMap,
+  user: Option<Extension<UserDto>>,
   req: GraphQLRequest,
-) -> GraphQLResponse {
-  schema.execute(req.into_inner()).await.into()
+) -> Response {
+  let mut req = req.into_inner();
+  if let Some(Extension(user)) = user {
+    req = req.data(user);
+  }
+  if let Some(request_id) = request_id(&headers) {
+    req = req.data(request_id);
+  }
+  let operation = graphql
+    .operation_labels
+    .label(req.operation_name.as_deref());
+
+  // Queries read from a replica, unless the client recently wrote. Mutations run in a
+  // transaction, committed if every one of them succeeds.
+  let query = graphql.persisted.query(&req).await.unwrap_or_default();
+  let operation_type = operation_type(&query, req.operation_name.as_deref());
+  let is_query = operation_type == Some(OperationType::Query);
+  if is_query && !read_your_writes::requested(&headers) {
+    req = req.data(graphql.app.db.reader().clone());
+  }
+  let tx = match operation_type {
+    Some(OperationType::Mutation) => match Tx::begin(&graphql.app.db).await {
+      Ok(tx) => Some(tx),
+      Err(e) => return e.into_response(),
+    },
+    _ => None,
+  };
+  if let Some(tx) = &tx {
+    req = req.data(tx.clone());
+  }
+
+  let start = Instant::now();
+  let resp = graphql.schema.execute(req).await;
+  let is_ok = resp.is_ok();
+  if let Some(tx) = tx {
+    if let Err(e) = tx.finish(is_ok).await {
+      return e.into_response();
+    }
+  }
+  metrics::record_graphql_operation(&operation, start.elapsed(), is_ok);
+
+  let mut response = GraphQLResponse::from(resp).into_response();
+  if !is_query && is_ok {
+    read_your_writes::mark(response.headers_mut(), &graphql.app);
+  }
+  response
+}
+
+/// Serves subscriptions over the `graphql-transport-ws` and legacy `graphql-ws` protocols.
+///
+/// The connection is closed when the token it was initialized with expires, or when the server
+/// shuts down.
+async fn graphql_ws_handler(
+  State(graphql): State<GraphQLState>,
+  headers: HeaderMap,
+  protocol: GraphQLProtocol,
+  upgrade: WebSocketUpgrade,
+) -> Response {
+  let app = graphql.app.clone();
+  let shutdown = graphql.app.shutdown.clone();
+  let request_id = request_id(&headers);
+  upgrade
+    .protocols(async_graphql::http::ALL_WEBSOCKET_PROTOCOLS)
+    .on_upgrade(move |socket| {
+      // Axum spawns the upgraded connection itself, out of reach of the connection tasks.
+      let tracker = shutdown.clone();
+      tracker.track(async move {
+        let (mut sink, stream) = socket.split();
+        let (expiry_tx, expiry_rx) = oneshot::channel();
+        let serve = GraphQLWebSocket::new_with_pair(&mut sink, stream, graphql.schema, protocol)
+          .on_connection_init(move |payload| async move {
+            let claims = auth_guard::authenticate_connection(&payload, app.cfg.jwt_secret.expose())
+              .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+            let user = auth_guard::current_user(&app, &claims)
+              .await
+              .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+            let _ = expiry_tx.send(claims.exp);
+            let mut data = async_graphql::Data::default();
+            data.insert(user);
+            if let Some(request_id) = request_id {
+              data.insert(request_id);
+            }
+            Ok(data)
+          })
+          .serve();
+
+        let close = tokio::select! {
+          () = serve => None,
+          () = token_expiry(expiry_rx) => Some(CloseFrame {
+            code: TOKEN_EXPIRED_CLOSE_CODE,
+            reason: "Token has expired".into(),
+          }),
+          () = shutdown.cancelled() => Some(CloseFrame {
+            code: close_code::AWAY,
+            reason: "Server is shutting down".into(),
+          }),
+        };
+        if let Some(close) = close {
+          tokio::select! {
+            _ = sink.send(Message::Close(Some(close))) => {}
+            () = shutdown.aborted() => {}
+          }
+        }
+      })
+    })
+}
+
+/// The close code of WebSocket connections whose token expired, the `Unauthorized` code of the
+/// `graphql-transport-ws` protocol.
+const TOKEN_EXPIRED_CLOSE_CODE: u16 = 4401;
+
+/// Completes when the token of a WebSocket connection expires, given the `exp` claim of the token
+/// once the connection is initialized.
+async fn token_expiry(exp: oneshot::Receiver<usize>) {
+  let Ok(exp) = exp.await else {
+    // The connection was never initialized, the protocol handles that itself.
+    return std::future::pending().await;
+  };
+  let now = chrono::Utc::now().timestamp() as usize;
+  tokio::time::sleep(Duration::from_secs(exp.saturating_sub(now) as u64)).await;
+}
+
+/// The type of the operation to execute, if the query is valid.
+fn operation_type(query: &str, operation_name: Option<&str>) -> Option<OperationType> {
+  let document = async_graphql::parser::parse_query(query).ok()?;
+  graphql_telemetry::operation_type(&document, operation_name)
+}
+
+/// The `x-request-id` set by the request id layer.
+fn request_id(headers: &HeaderMap) -> Option<RequestId> {
+  let request_id = headers.get("x-request-id")?.to_str().ok()?;
+  Some(RequestId(request_id.to_string()))
+}
+
+/// Serves the OpenAPI specification as YAML, next to the JSON one of the Swagger UI.
+async fn openapi_yaml(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+  let yaml = doc::to_yaml(&doc::openapi(&state.cfg.session_cookie))
+    .map_err(|e| ApiError::InternalError(e.into()))?;
+  Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml))
+}
+
+/// Serves the GraphQL schema in SDL.
+async fn graphql_sdl(State(graphql): State<GraphQLState>) -> impl IntoResponse {
+  (
+    [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+    graphql.schema.sdl(),
+  )
 }
 
-async fn graphql_playground(State(state): State<AppState>) -> Html<String> {
+/// Serves GraphiQL, its scripts carrying the nonce of the content security policy.
+async fn graphql_playground(
+  State(state): State<AppState>,
+  nonce: Option<Extension<CspNonce>>,
+) -> Html<String> {
   let endpoint = &state.cfg.graphql_endpoint;
-  Html(GraphiQLSource::build().endpoint(endpoint).finish())
+  let html = GraphiQLSource::build()
+    .endpoint(endpoint)
+    .subscription_endpoint(&format!("{endpoint}/ws"))
+    .finish();
+  Html(match nonce {
+    Some(Extension(nonce)) => nonce.apply(&html),
+    None => html,
+  })
 }
@@ -0,0 +1,33 @@
+use anyhow::anyhow;
+use clap::Args;
+
+use crate::common::cfg::Config;
+use crate::database::Db;
+use crate::modules::users::{repository::SeaOrmUserRepository, service};
+
+#[derive(Args, Debug)]
+pub struct CreateAdminArgs {
+  /// The email of the admin
+  #[arg(long)]
+  pub email: String,
+
+  /// The name of the admin, only used when the user is created
+  #[arg(long, default_value = "Admin")]
+  pub name: String,
+
+  /// The password of the admin, required when the user is created
+  #[arg(long, env = "ADMIN_PASSWORD", hide_env_values = true)]
+  pub password: Option<String>,
+}
+
+pub async fn create_admin(cfg: &Config, args: CreateAdminArgs) -> anyhow::Result<()> {
+  let db = Db::new(cfg).await?;
+  let users = SeaOrmUserRepository::new(db.writer());
+  let user = service::upsert_admin(&users, args.email, args.name, args.password)
+    .await
+    .map_err(|e| anyhow!("Failed to create the admin: {}", e))?;
+  db.close().await?;
+
+  tracing::info!(id = %user.id, email = %user.email, "Admin ready");
+  Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::bail;
+use clap::Subcommand;
+use sea_orm_migration::{MigrationStatus, MigratorTrait};
+
+use crate::common::cfg::Config;
+use crate::database::{migrations::Migrator, Db};
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+  /// Applies pending migrations
+  Up {
+    /// Number of migrations to apply, all pending migrations if not given
+    #[arg(long)]
+    steps: Option<u32>,
+  },
+
+  /// Rolls back applied migrations
+  Down {
+    /// Number of migrations to roll back
+    #[arg(long, default_value_t = 1)]
+    steps: u32,
+
+    /// Allows rolling back in environments that protect their data
+    #[arg(long)]
+    force: bool,
+  },
+
+  /// Lists applied and pending migrations
+  Status,
+
+  /// Drops all tables, then applies all migrations
+  Fresh {
+    /// Allows dropping the tables in environments that protect their data
+    #[arg(long)]
+    force: bool,
+  },
+
+  /// Rolls back all applied migrations
+  Reset {
+    /// Allows rolling back in environments that protect their data
+    #[arg(long)]
+    force: bool,
+  },
+}
+
+impl MigrateCommand {
+  /// Whether the command can lose data.
+  fn is_destructive(&self) -> bool {
+    !matches!(self, MigrateCommand::Up { .. } | MigrateCommand::Status)
+  }
+
+  /// Whether `--force` was passed to a destructive command.
+  fn is_forced(&self) -> bool {
+    match self {
+      MigrateCommand::Up { .. } | MigrateCommand::Status => false,
+      MigrateCommand::Down { force, .. }
+      | MigrateCommand::Fresh { force }
+      | MigrateCommand::Reset { force } => *force,
+    }
+  }
+}
+
+pub async fn run(cfg: &Config, command: MigrateCommand) -> anyhow::Result<()> {
+  if command.is_destructive() && !command.is_forced() && !cfg.profile.allow_destructive_commands {
+    bail!(
+      "Refusing to run a destructive migration command in {}, pass --force to run it anyway",
+      cfg.env
+    );
+  }
+
+  let db = Db::for_migrations(cfg).await?;
+  match command {
+    MigrateCommand::Up { steps } => db.run_migrations(steps).await?,
+    MigrateCommand::Down { steps, .. } => {
+      let txn = db.migration_transaction().await?;
+      Migrator::down(&txn, Some(steps)).await?;
+      txn.commit().await?;
+    }
+    MigrateCommand::Status => {
+      for migration in Migrator::get_migration_with_status(db.writer()).await? {
+        let status = match migration.status() {
+          MigrationStatus::Applied => "applied",
+          MigrationStatus::Pending => "pending",
+        };
+        println!("{:<8} {}", status, migration.name());
+      }
+    }
+    MigrateCommand::Fresh { .. } => {
+      let txn = db.migration_transaction().await?;
+      Migrator::fresh(&txn).await?;
+      txn.commit().await?;
+    }
+    MigrateCommand::Reset { .. } => {
+      let txn = db.migration_transaction().await?;
+      Migrator::reset(&txn).await?;
+      txn.commit().await?;
+    }
+  }
+  db.close().await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_destructive_commands() {
+    assert!(!MigrateCommand::Status.is_destructive());
+    assert!(!MigrateCommand::Up { steps: None }.is_destructive());
+
+    let fresh = MigrateCommand::Fresh { force: false };
+    assert!(fresh.is_destructive());
+    assert!(!fresh.is_forced());
+
+    let down = MigrateCommand::Down {
+      steps: 1,
+      force: true,
+    };
+    assert!(down.is_destructive());
+    assert!(down.is_forced());
+  }
+}
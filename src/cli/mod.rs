@@ -0,0 +1,161 @@
+pub mod admin;
+pub mod migrate;
+pub mod schema;
+pub mod seed;
+pub mod serve;
+
+use std::{process::ExitCode, sync::Arc};
+
+use clap::{Parser, Subcommand};
+
+use crate::common::{
+  cfg::{Config, ConfigError, Configuration},
+  telemetry::{self, TelemetryGuard},
+};
+
+/// The command line interface of the server binary.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+  /// The command to run, `serve` if not given.
+  #[command(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  /// Starts the HTTP server
+  Serve,
+
+  /// Manages the database migrations
+  Migrate {
+    #[command(subcommand)]
+    command: migrate::MigrateCommand,
+  },
+
+  /// Creates an admin user, or promotes an existing user to admin
+  CreateAdmin(admin::CreateAdminArgs),
+
+  /// Inserts the seed data of the modules and the fixture files
+  Seed(seed::SeedArgs),
+
+  /// Validates the configuration and prints it with secrets redacted
+  CheckConfig,
+
+  /// Prints the OpenAPI specification
+  Openapi(schema::OpenapiArgs),
+
+  /// Prints the GraphQL schema in SDL, or checks it against a snapshot
+  GraphqlSchema(schema::GraphqlSchemaArgs),
+}
+
+/// Runs the command, reporting errors on stderr.
+pub async fn run(cli: Cli) -> ExitCode {
+  match execute(cli.command.unwrap_or(Command::Serve)).await {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      // Commands writing to stdout have no log subscriber to report the error.
+      if tracing::dispatcher::has_been_set() {
+        tracing::error!("{:#}", err);
+      } else {
+        eprintln!("Error: {:#}", err);
+      }
+      ExitCode::FAILURE
+    }
+  }
+}
+
+async fn execute(command: Command) -> anyhow::Result<()> {
+  match command {
+    Command::Serve => {
+      let cfg = load()?;
+      let telemetry_guard = init(&cfg);
+      serve::run(cfg, telemetry_guard.log_filter()).await
+    }
+    Command::Migrate { command } => {
+      let cfg = load()?;
+      let _telemetry_guard = init(&cfg);
+      migrate::run(&cfg, command).await
+    }
+    Command::CreateAdmin(args) => {
+      let cfg = load()?;
+      let _telemetry_guard = init(&cfg);
+      admin::create_admin(&cfg, args).await
+    }
+    Command::Seed(args) => {
+      let cfg = load()?;
+      let _telemetry_guard = init(&cfg);
+      seed::run(&cfg, args).await
+    }
+    // The remaining commands write to stdout, so they do not install the log subscriber.
+    Command::CheckConfig => check_config(),
+    Command::Openapi(args) => schema::openapi(args),
+    Command::GraphqlSchema(args) => schema::graphql_schema(args).await,
+  }
+}
+
+/// Loads the configuration, failing with a report of every invalid value.
+fn load() -> anyhow::Result<Config> {
+  let cfg = Configuration::new().map_err(ConfigError)?;
+  Ok(Arc::new(cfg))
+}
+
+/// Installs the log subscriber and applies the environment profile.
+fn init(cfg: &Config) -> TelemetryGuard {
+  // Uses the configured log filter (RUST_LOG) or "debug".
+  // Spans are exported to the OTLP collector if one is configured.
+  let telemetry_guard = telemetry::setup_tracing(cfg);
+
+  // Log the current configuration
+  tracing::info!(?cfg, "Application configuration loaded");
+
+  telemetry_guard
+}
+
+fn check_config() -> anyhow::Result<()> {
+  let cfg = Configuration::load()?;
+  println!("{}", serde_json::to_string_pretty(&cfg)?);
+  eprintln!("Configuration is valid");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cli_is_valid() {
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+  }
+
+  #[test]
+  fn test_serve_is_the_default() {
+    let cli = Cli::try_parse_from(["server"]).unwrap();
+    assert!(cli.command.is_none());
+  }
+
+  #[test]
+  fn test_parse_migrate_down() {
+    let cli = Cli::try_parse_from(["server", "migrate", "down", "--steps", "2"]).unwrap();
+    assert!(matches!(
+      cli.command,
+      Some(Command::Migrate {
+        command: migrate::MigrateCommand::Down {
+          steps: 2,
+          force: false
+        }
+      })
+    ));
+  }
+
+  #[test]
+  fn test_parse_seed() {
+    let cli = Cli::try_parse_from(["server", "seed", "--only", "admin,users"]).unwrap();
+    let Some(Command::Seed(args)) = cli.command else {
+      panic!("expected the seed command");
+    };
+    assert_eq!(args.only, vec!["admin", "users"]);
+    assert_eq!(args.fake_users, 0);
+  }
+}
@@ -0,0 +1,129 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use anyhow::{bail, Context};
+use clap::{Args, ValueEnum};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+
+use crate::common::{
+  cfg::{Environment, Secret, SessionCookieConfig, Values},
+  events::EventBus,
+  graphql::{breaking, limits::Limits, persisted::PersistedQueries, telemetry::Telemetry},
+};
+use crate::modules::repositories::SeaOrmRepositories;
+use crate::{doc, query_root};
+
+#[derive(Args, Debug)]
+pub struct OpenapiArgs {
+  /// The format of the specification
+  #[arg(long, value_enum, default_value_t = OpenapiFormat::Json)]
+  pub format: OpenapiFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OpenapiFormat {
+  Json,
+  Yaml,
+}
+
+/// Prints the OpenAPI specification.
+pub fn openapi(args: OpenapiArgs) -> anyhow::Result<()> {
+  let openapi = doc::openapi(&session_cookie()?);
+  match args.format {
+    OpenapiFormat::Json => println!("{}", openapi.to_pretty_json()?),
+    OpenapiFormat::Yaml => print!("{}", doc::to_yaml(&openapi)?),
+  }
+  Ok(())
+}
+
+/// Reads the session cookie configuration alone, which names a security scheme of the
+/// specification, so that the specification can be printed without the rest of the configuration.
+fn session_cookie() -> anyhow::Result<SessionCookieConfig> {
+  let mut values = Values::load();
+  let secure_cookies = values
+    .optional::<Environment>("APP_ENV")
+    .is_some_and(|env| env.profile().secure_cookies);
+  let cookies = SessionCookieConfig::from_values(&mut values, secure_cookies);
+  values.finish()?;
+  Ok(cookies)
+}
+
+#[derive(Args, Debug)]
+pub struct GraphqlSchemaArgs {
+  /// Compares the schema with a snapshot instead of printing it, failing on breaking changes
+  #[arg(long, value_name = "SNAPSHOT")]
+  pub check: Option<PathBuf>,
+}
+
+/// Prints the GraphQL schema in SDL, or checks it against a snapshot.
+pub async fn graphql_schema(args: GraphqlSchemaArgs) -> anyhow::Result<()> {
+  let sdl = graphql_sdl().await?;
+  let Some(snapshot) = args.check else {
+    println!("{sdl}");
+    return Ok(());
+  };
+
+  let snapshot_sdl = fs::read_to_string(&snapshot)
+    .with_context(|| format!("Failed to read {}", snapshot.display()))?;
+  let changes = breaking::breaking_changes(&snapshot_sdl, &sdl)
+    .with_context(|| format!("Failed to parse {}", snapshot.display()))?;
+  if !changes.is_empty() {
+    for change in &changes {
+      eprintln!("- {change}");
+    }
+    bail!(
+      "{} breaking change(s) from {}",
+      changes.len(),
+      snapshot.display()
+    );
+  }
+  if snapshot_sdl.trim() != sdl.trim() {
+    eprintln!(
+      "The schema has non-breaking changes, update {} with `server graphql-schema`",
+      snapshot.display()
+    );
+  } else {
+    eprintln!("The schema matches {}", snapshot.display());
+  }
+  Ok(())
+}
+
+/// Returns the GraphQL schema in SDL.
+pub async fn graphql_sdl() -> anyhow::Result<String> {
+  let schema = query_root::schema(
+    offline_connection().await?,
+    query_root::Services {
+      repositories: Arc::new(SeaOrmRepositories),
+      user_events: EventBus::new(),
+      jwt_secret: Secret::default(),
+    },
+    Limits::none(),
+    PersistedQueries::default(),
+    Telemetry::default(),
+  )?;
+  Ok(schema.sdl())
+}
+
+/// Returns a Postgres connection that is never opened.
+///
+/// Building the schema only needs to know the database backend, so the schema can be generated
+/// without a database, e.g. in CI.
+async fn offline_connection() -> Result<DatabaseConnection, DbErr> {
+  let mut opt = ConnectOptions::new("postgres://localhost/offline");
+  opt.connect_lazy(true).sqlx_logging(false);
+  Database::connect(opt).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_graphql_schema_has_no_breaking_changes() {
+    let snapshot = include_str!("../../schema.graphql");
+    let changes = breaking::breaking_changes(snapshot, &graphql_sdl().await.unwrap()).unwrap();
+    assert!(
+      changes.is_empty(),
+      "Breaking changes from schema.graphql: {changes:#?}"
+    );
+  }
+}
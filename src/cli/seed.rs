@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use anyhow::bail;
+use clap::Args;
+
+use crate::common::cfg::Config;
+use crate::database::{seeders::Fixtures, Db};
+use crate::modules;
+
+#[derive(Args, Debug)]
+pub struct SeedArgs {
+  /// Only runs the given seeders, e.g. `--only admin,users`
+  #[arg(long, value_delimiter = ',')]
+  pub only: Vec<String>,
+
+  /// Number of fake users to create for load tests
+  #[arg(long, default_value_t = 0)]
+  pub fake_users: u32,
+
+  /// Allows creating fake users in environments that protect their data
+  #[arg(long)]
+  pub force: bool,
+}
+
+pub async fn run(cfg: &Config, args: SeedArgs) -> anyhow::Result<()> {
+  // Fake users share a well-known password.
+  if args.fake_users > 0 && !args.force && !cfg.profile.allow_destructive_commands {
+    bail!(
+      "Refusing to create fake users in {}, pass --force to create them anyway",
+      cfg.env
+    );
+  }
+
+  let fixtures = Fixtures::load(Path::new(&cfg.seed_fixtures_dir))?;
+  let db = Db::new(cfg).await?;
+  modules::seeders(cfg, args.fake_users)
+    .run(db.writer(), &fixtures, &args.only)
+    .await?;
+  db.close().await?;
+  Ok(())
+}
@@ -0,0 +1,167 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{bail, Context};
+use tokio::net::TcpListener;
+
+use crate::app::{self, AppState};
+use crate::common::cfg::{Config, MigrationMode};
+use crate::common::telemetry::LogFilter;
+use crate::common::utils::shutdown_signal::shutdown_signal;
+use crate::common::{settings, shutdown};
+use crate::database::{seeders::Fixtures, Db};
+use crate::modules;
+
+/// Runs the HTTP server until a shutdown signal is received, then drains it gracefully.
+pub async fn run(cfg: Config, log_filter: LogFilter) -> anyhow::Result<()> {
+  // Initialize db connection.
+  tracing::debug!("Initializing db connection");
+  let db = Db::new(&cfg).await.context("Failed to initialize db")?;
+
+  match cfg.db_migrations {
+    MigrationMode::Apply => {
+      tracing::debug!("Running migrations");
+      let migration_db = Db::for_migrations(&cfg)
+        .await
+        .context("Failed to connect for migrations")?;
+      migration_db
+        .run_migrations(None)
+        .await
+        .context("Failed to run migrations")?;
+      migration_db
+        .close()
+        .await
+        .context("Failed to close the migration connection")?;
+    }
+    MigrationMode::Verify => {
+      tracing::debug!("Verifying migrations");
+      let pending = db
+        .pending_migrations()
+        .await
+        .context("Failed to verify migrations")?;
+      if !pending.is_empty() {
+        bail!(
+          "Refusing to start with {} pending migration(s): {}",
+          pending.len(),
+          pending.join(", ")
+        );
+      }
+    }
+    MigrationMode::Skip => {
+      tracing::debug!("Skipping migrations as DATABASE_RUN_MIGRATIONS is disabled")
+    }
+  }
+
+  if cfg.seed_on_startup {
+    tracing::debug!("Running seeders");
+    let fixtures = Fixtures::load(Path::new(&cfg.seed_fixtures_dir))?;
+    modules::seeders(&cfg, 0)
+      .run(db.writer(), &fixtures, &[])
+      .await?;
+  }
+
+  // Spin up our server.
+  tracing::info!("Starting server on {}", cfg.listen_address);
+  let listener = TcpListener::bind(&cfg.listen_address)
+    .await
+    .with_context(|| format!("Failed to bind {}", cfg.listen_address))?;
+
+  let state = AppState::new(cfg.clone(), db.clone()).with_log_filter(log_filter);
+  let shutdown = state.shutdown.clone();
+
+  // Reload the runtime settings on SIGHUP or when the configuration file changes.
+  shutdown.spawn(settings::watch(
+    state.settings.clone(),
+    Duration::from_secs(cfg.config_reload_interval),
+    shutdown.clone(),
+  ));
+
+  // Stop reading from replicas while they are unhealthy.
+  if db.has_replicas() {
+    shutdown.spawn(db.clone().monitor_replicas(
+      Duration::from_secs(cfg.db_replica_check_interval),
+      shutdown.clone(),
+    ));
+  }
+
+  // Serve metrics on a dedicated listener when configured.
+  if let Some(metrics_address) = cfg.metrics_listen_address.filter(|_| cfg.metrics_enabled) {
+    tracing::info!("Starting metrics server on {}", metrics_address);
+    let metrics_listener = TcpListener::bind(metrics_address)
+      .await
+      .with_context(|| format!("Failed to bind the metrics listener to {metrics_address}"))?;
+    let metrics_router = app::metrics_router(state.clone());
+    shutdown.spawn(shutdown::serve(
+      metrics_listener,
+      metrics_router,
+      shutdown.clone(),
+    ));
+  }
+
+  let health = state.health.clone();
+  let router = app::router(state);
+
+  if cfg.profile.mount_docs {
+    tracing::info!(
+      "Swagger at http://{}{}",
+      cfg.listen_address,
+      cfg.swagger_endpoint
+    );
+  }
+  tracing::info!(
+    "GraphQL at http://{}{}",
+    cfg.listen_address,
+    cfg.graphql_endpoint
+  );
+
+  // The client address is used by the rate limiter.
+  let mut server = tokio::spawn(shutdown::serve(listener, router, shutdown.clone()));
+
+  tokio::select! {
+    result = &mut server => {
+      // The server only stops on its own if it panicked.
+      result.context("Server task failed")?;
+      return Ok(());
+    }
+    _ = shutdown_signal() => {}
+  }
+
+  // Fail the readiness probe and give load balancers time to stop routing new requests here.
+  health.mark_shutting_down();
+  tracing::info!(
+    "Readiness set to unhealthy, waiting {}s before draining",
+    cfg.shutdown_pre_stop_delay
+  );
+  tokio::time::sleep(Duration::from_secs(cfg.shutdown_pre_stop_delay)).await;
+
+  // Stop accepting connections, then drain the in-flight requests and the background workers,
+  // which were signalled by the same token, and close the database within a single deadline.
+  tracing::info!(
+    "Draining in-flight requests for at most {}s",
+    cfg.shutdown_drain_timeout
+  );
+  shutdown.trigger();
+  let drain = async {
+    let result = (&mut server).await;
+    shutdown.wait_for_tasks().await;
+    tracing::info!("All in-flight requests completed, closing database connections");
+    if let Err(err) = db.close().await {
+      tracing::error!("Failed to close database connections: {}", err);
+    }
+    result
+  };
+  match tokio::time::timeout(Duration::from_secs(cfg.shutdown_drain_timeout), drain).await {
+    Ok(result) => result.context("Server task failed")?,
+    Err(_) => {
+      tracing::warn!(
+        "Drain deadline exceeded, closing {} remaining connection(s) and task(s)",
+        shutdown.running_tasks()
+      );
+      shutdown.abort();
+      server.abort();
+    }
+  }
+
+  tracing::info!("Shutdown complete");
+
+  Ok(())
+}
@@ -1,13 +1,19 @@
 use axum::{
-  extract::rejection::JsonRejection,
+  body::Body,
+  extract::{rejection::JsonRejection, Request, State},
+  http::header,
+  middleware::Next,
   response::{IntoResponse, Response},
   Json,
 };
 use hyper::StatusCode;
-use sea_orm::DbErr;
+use sea_orm::{DbErr, SqlErr};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::error;
+use utoipa::ToSchema;
+
+use crate::common::telemetry;
 
 /// Custom error type for the API.
 /// The `#[from]` attribute allows for easy conversion from other error types.
@@ -33,6 +39,14 @@ pub enum ApiError {
   #[error("Unauthorized: {0}")]
   Unauthorized(String),
 
+  /// For requests that conflict with the current state of a resource, e.g. a taken email.
+  #[error("Conflict: {0}")]
+  Conflict(String),
+
+  /// For requests rejected by the rate limiter.
+  #[error("Too many requests.")]
+  TooManyRequests,
+
   /// Converts from `sea_orm::DbErr`.
   #[error("A database error has occurred.")]
   DatabaseError(#[from] DbErr),
@@ -42,10 +56,57 @@ pub enum ApiError {
   InternalError(#[from] anyhow::Error),
 }
 
-#[derive(Serialize, Deserialize)]
+/// The body of the error responses.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct ApiErrorResp {
   pub status: u16,
   pub message: String,
+  /// The id of the trace the failed request belongs to.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub trace_id: Option<String>,
+  /// The underlying error, only reported in environments with error details enabled.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub details: Option<String>,
+}
+
+/// The response of an error whose message hides the underlying one, kept in the extensions of the
+/// response for [`error_details`].
+#[derive(Clone)]
+struct MaskedError {
+  resp: ApiErrorResp,
+  details: String,
+}
+
+impl ApiError {
+  /// The status of the response to the error.
+  pub fn status(&self) -> StatusCode {
+    match self {
+      ApiError::InvalidJsonBody(_) | ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+      ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+      ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+      ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+      ApiError::Conflict(_) => StatusCode::CONFLICT,
+      ApiError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+      ApiError::DatabaseError(_) | ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+
+  /// Reports the violation of a unique constraint as a conflict with `message`, and any other
+  /// database error as is.
+  pub fn unique_violation(err: DbErr, message: &str) -> Self {
+    match err.sql_err() {
+      Some(SqlErr::UniqueConstraintViolation(_)) => ApiError::Conflict(message.to_string()),
+      _ => ApiError::DatabaseError(err),
+    }
+  }
+
+  /// Whether the message hides the underlying error.
+  pub fn is_masked(&self) -> bool {
+    matches!(
+      self,
+      ApiError::InvalidJsonBody(_) | ApiError::DatabaseError(_) | ApiError::InternalError(_)
+    )
+  }
 }
 
 // The IntoResponse implementation for ApiError logs the error message.
@@ -69,28 +130,51 @@ impl IntoResponse for ApiError {
       ApiError::NotFound(_) => format!("{}", self),
       ApiError::Forbidden(_) => format!("{}", self),
       ApiError::Unauthorized(_) => format!("{}", self),
+      ApiError::Conflict(_) => format!("{}", self),
+      ApiError::TooManyRequests => format!("{}", self),
       ApiError::DatabaseError(ref err) => format!("{}", err),
       ApiError::InternalError(ref err) => format!("{}", err),
     };
     error!("{}", error_to_log);
 
     // Determine the appropriate status code.
-    let status = match self {
-      ApiError::InvalidJsonBody(_) | ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
-      ApiError::NotFound(_) => StatusCode::NOT_FOUND,
-      ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
-      ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
-      ApiError::DatabaseError(_) | ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-    };
+    let status = self.status();
 
     // Create a generic response to hide specific implementation details.
     let resp = ApiErrorResp {
       status: status.as_u16(),
       message: self.to_string(),
+      trace_id: telemetry::current_trace_id(),
+      details: None,
     };
 
-    (status, Json(resp)).into_response()
+    let mut response = (status, Json(resp.clone())).into_response();
+    if self.is_masked() {
+      response.extensions_mut().insert(MaskedError {
+        resp,
+        details: error_to_log,
+      });
+    }
+    response
+  }
+}
+
+/// Adds the underlying error to the responses of the errors whose message hides it, if `enabled`
+/// by the environment profile.
+pub async fn error_details(State(enabled): State<bool>, req: Request, next: Next) -> Response {
+  let mut response = next.run(req).await;
+  let Some(MaskedError { mut resp, details }) = response.extensions_mut().remove() else {
+    return response;
+  };
+  if !enabled {
+    return response;
   }
+
+  resp.details = Some(details);
+  let (mut parts, _) = response.into_parts();
+  parts.headers.remove(header::CONTENT_LENGTH);
+  let body = serde_json::to_vec(&resp).expect("ApiErrorResp is serializable");
+  Response::from_parts(parts, Body::from(body))
 }
 
 #[cfg(test)]
@@ -138,6 +222,10 @@ mod tests {
     let unauthorized = ApiError::Unauthorized("Test".to_string());
     let response = unauthorized.into_response();
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let conflict = ApiError::Conflict("Test".to_string());
+    let response = conflict.into_response();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
   }
 
   #[test]
@@ -145,11 +233,27 @@ mod tests {
     let error_resp = ApiErrorResp {
       status: 400,
       message: "Bad Request".to_string(),
+      trace_id: None,
+      details: None,
     };
 
     let json = serde_json::to_string(&error_resp).unwrap();
     assert!(json.contains("\"status\":400"));
     assert!(json.contains("\"message\":\"Bad Request\""));
+    assert!(!json.contains("trace_id"));
+  }
+
+  #[test]
+  fn test_api_error_resp_serialization_with_trace_id() {
+    let error_resp = ApiErrorResp {
+      status: 500,
+      message: "An internal server error has occurred.".to_string(),
+      trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+      details: None,
+    };
+
+    let json = serde_json::to_string(&error_resp).unwrap();
+    assert!(json.contains("\"trace_id\":\"4bf92f3577b34da6a3ce929d0e0e4736\""));
   }
 
   #[test]
@@ -158,5 +262,35 @@ mod tests {
     let error_resp: ApiErrorResp = serde_json::from_str(json).unwrap();
     assert_eq!(error_resp.status, 404);
     assert_eq!(error_resp.message, "Not Found");
+    assert!(error_resp.trace_id.is_none());
+  }
+
+  async fn error_response(error: fn() -> ApiError, enabled: bool) -> ApiErrorResp {
+    use tower::ServiceExt;
+
+    let router = axum::Router::new()
+      .route("/", axum::routing::get(move || async move { error() }))
+      .layer(axum::middleware::from_fn_with_state(enabled, error_details));
+    let response = router
+      .oneshot(Request::get("/").body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+      .await
+      .unwrap();
+    serde_json::from_slice(&body).unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_api_error_details() {
+    let internal = || ApiError::InternalError(anyhow::anyhow!("connection reset"));
+    let resp = error_response(internal, true).await;
+    assert_eq!(resp.message, "An internal server error has occurred.");
+    assert_eq!(resp.details.as_deref(), Some("connection reset"));
+    assert_eq!(error_response(internal, false).await.details, None);
+
+    // The message of other variants already describes the error.
+    let not_found = || ApiError::NotFound("User not found".to_string());
+    assert_eq!(error_response(not_found, true).await.details, None);
   }
 }
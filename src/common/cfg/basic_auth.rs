@@ -0,0 +1,201 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::{Serialize, Serializer};
+
+use super::{Secret, Values};
+
+/// The users allowed by a realm protected with basic authentication, and the networks it can be
+/// reached from.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BasicAuthConfig {
+  /// The users of the realm, which is not protected if there are none.
+  pub users: Vec<BasicAuthUser>,
+
+  /// The networks allowed to reach the realm, any if empty.
+  #[serde(serialize_with = "serialize_networks")]
+  pub allowed_ips: Vec<IpNet>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BasicAuthUser {
+  pub username: String,
+  pub password: Password,
+}
+
+/// The password of a basic auth user, either given as is or hashed.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Password {
+  Plain(Secret),
+  /// A bcrypt hash, e.g. `$2b$12$...`.
+  Bcrypt(Secret),
+  /// An argon2 hash in the PHC format, e.g. `$argon2id$v=19$...`.
+  Argon2(Secret),
+}
+
+impl BasicAuthConfig {
+  /// Reads the users of `key`, separated by whitespace, each in the format "username:password"
+  /// where the password may be a bcrypt or argon2 hash, and the networks of
+  /// `{key}_ALLOWED_IPS` if `allow_ips` is set.
+  pub fn from_values(values: &mut Values, key: &str, allow_ips: bool) -> Self {
+    let raw = values.string(key, "");
+    let mut users = Vec::new();
+    for entry in raw.split_whitespace() {
+      match parse_user(entry) {
+        Ok(user) => users.push(user),
+        Err(message) => values.invalid(key, message),
+      }
+    }
+
+    let mut allowed_ips = Vec::new();
+    if allow_ips {
+      let ips_key = format!("{key}_ALLOWED_IPS");
+      for network in values.string(&ips_key, "").split(',').map(str::trim) {
+        match parse_network(network) {
+          Ok(Some(network)) => allowed_ips.push(network),
+          Ok(None) => {}
+          Err(message) => values.invalid(&ips_key, message),
+        }
+      }
+      if !allowed_ips.is_empty() && users.is_empty() {
+        values.invalid(&ips_key, format!("requires {key}"));
+      }
+    }
+
+    Self { users, allowed_ips }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    !self.users.is_empty()
+  }
+
+  /// Whether a client may reach the realm, unknown clients only being allowed without an
+  /// allow-list.
+  pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+    self.allowed_ips.is_empty()
+      || ip.is_some_and(|ip| {
+        let ip = ip.to_canonical();
+        self.allowed_ips.iter().any(|network| network.contains(&ip))
+      })
+  }
+}
+
+fn parse_user(entry: &str) -> Result<BasicAuthUser, String> {
+  let Some((username, password)) = entry.split_once(':') else {
+    return Err("expected credentials in the format \"username:password\"".to_string());
+  };
+  if username.is_empty() || password.is_empty() {
+    return Err("expected credentials in the format \"username:password\"".to_string());
+  }
+
+  let password = if password.starts_with("$2") {
+    password
+      .parse::<bcrypt::HashParts>()
+      .map_err(|e| format!("invalid bcrypt hash for \"{username}\": {e}"))?;
+    Password::Bcrypt(Secret::new(password))
+  } else if password.starts_with("$argon2") {
+    argon2::PasswordHash::new(password)
+      .map_err(|e| format!("invalid argon2 hash for \"{username}\": {e}"))?;
+    Password::Argon2(Secret::new(password))
+  } else {
+    Password::Plain(Secret::new(password))
+  };
+
+  Ok(BasicAuthUser {
+    username: username.to_string(),
+    password,
+  })
+}
+
+/// Parses a network such as "10.0.0.0/8", or a single address.
+pub(super) fn parse_network(network: &str) -> Result<Option<IpNet>, String> {
+  if network.is_empty() {
+    return Ok(None);
+  }
+  if let Ok(ip) = network.parse::<IpAddr>() {
+    return Ok(Some(IpNet::from(ip)));
+  }
+  network
+    .parse::<IpNet>()
+    .map(Some)
+    .map_err(|_| format!("invalid address or network \"{network}\""))
+}
+
+pub(super) fn serialize_networks<S: Serializer>(
+  networks: &[IpNet],
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.collect_seq(networks.iter().map(ToString::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::cfg::testing;
+
+  fn config(pairs: &[(&str, &str)]) -> Result<BasicAuthConfig, Vec<String>> {
+    testing::try_section(pairs, |values| {
+      BasicAuthConfig::from_values(values, "SWAGGER_BASIC_AUTH", true)
+    })
+  }
+
+  #[test]
+  fn test_users_with_plain_and_hashed_passwords() {
+    let argon2 =
+      "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$Q0eFCjDAFNbmDFnHnJHyS0VJQAyj1wmPqyC4TlbhVKs";
+    let bcrypt = bcrypt::hash("secret", 4).unwrap();
+    let users = format!("alice:secret bob:{bcrypt}\ncarol:{argon2}");
+    let config = config(&[("SWAGGER_BASIC_AUTH", &users)]).unwrap();
+
+    let kinds: Vec<_> = config
+      .users
+      .iter()
+      .map(|user| match user.password {
+        Password::Plain(_) => ("plain", user.username.as_str()),
+        Password::Bcrypt(_) => ("bcrypt", user.username.as_str()),
+        Password::Argon2(_) => ("argon2", user.username.as_str()),
+      })
+      .collect();
+    assert_eq!(
+      kinds,
+      [("plain", "alice"), ("bcrypt", "bob"), ("argon2", "carol")]
+    );
+    assert!(!serde_json::to_string(&config).unwrap().contains("secret"));
+  }
+
+  #[test]
+  fn test_invalid_values_are_reported() {
+    let errors = config(&[
+      ("SWAGGER_BASIC_AUTH", "admin alice:secret"),
+      ("SWAGGER_BASIC_AUTH_ALLOWED_IPS", "10.0.0.0/8, localhost"),
+    ])
+    .unwrap_err();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors[0].starts_with("SWAGGER_BASIC_AUTH: expected credentials"));
+    assert!(errors[1].starts_with("SWAGGER_BASIC_AUTH_ALLOWED_IPS: invalid address"));
+
+    let errors = config(&[("SWAGGER_BASIC_AUTH", "admin:$2b$04$short")]).unwrap_err();
+    assert!(errors[0].contains("invalid bcrypt hash for \"admin\""));
+
+    let errors = config(&[("SWAGGER_BASIC_AUTH_ALLOWED_IPS", "10.0.0.0/8")]).unwrap_err();
+    assert_eq!(
+      errors,
+      ["SWAGGER_BASIC_AUTH_ALLOWED_IPS: requires SWAGGER_BASIC_AUTH"]
+    );
+  }
+
+  #[test]
+  fn test_allowed_ips() {
+    let config = config(&[
+      ("SWAGGER_BASIC_AUTH", "admin:secret"),
+      ("SWAGGER_BASIC_AUTH_ALLOWED_IPS", "10.0.0.0/8, 192.168.1.7"),
+    ])
+    .unwrap();
+    assert!(config.allows(Some("10.1.2.3".parse().unwrap())));
+    assert!(config.allows(Some("::ffff:192.168.1.7".parse().unwrap())));
+    assert!(!config.allows(Some("192.168.1.8".parse().unwrap())));
+    assert!(!config.allows(None));
+    assert!(BasicAuthConfig::default().allows(None));
+  }
+}
@@ -0,0 +1,318 @@
+use std::{
+  collections::HashMap,
+  fmt::Display,
+  fs,
+  path::{Path, PathBuf},
+  str::FromStr,
+};
+
+use thiserror::Error;
+
+/// Every problem found while loading the configuration.
+#[derive(Debug, Error)]
+#[error("Invalid configuration:\n{}", .0.iter().map(|e| format!("  - {e}")).collect::<Vec<_>>().join("\n"))]
+pub struct ConfigError(pub Vec<String>);
+
+/// Raw configuration values keyed by environment variable name, merged from every source.
+///
+/// Sources are layered from lowest to highest priority:
+/// 1. the defaults passed to the accessors,
+/// 2. `config/{APP_ENV}.toml` (the directory can be changed with `CONFIG_DIR`),
+/// 3. the `.env` file,
+/// 4. environment variables,
+/// 5. `{NAME}_FILE` variables pointing at a file holding the value, e.g. a Docker or Kubernetes
+///    secret.
+///
+/// Empty values are treated as unset. Accessors record errors instead of failing, so that
+/// every invalid or missing value can be reported at once by `finish`.
+#[derive(Debug, Default)]
+pub struct Values {
+  values: HashMap<String, String>,
+  /// The keys of the configuration file overridden by the environment.
+  overridden: Vec<String>,
+  errors: Vec<String>,
+}
+
+impl Values {
+  /// Loads the values from the configuration file, `.env` and the environment.
+  pub fn load() -> Self {
+    // Loads the .env file located in the environment's current directory or its parents in sequence.
+    // .env used only for development, so we discard error in all other cases.
+    // Variables already set in the environment are not overridden.
+    dotenvy::dotenv().ok();
+
+    let mut values = Values::default();
+    if let Some(path) = config_file().filter(|path| path.exists()) {
+      values.merge_file(&path);
+    }
+    for (key, value) in std::env::vars() {
+      if !value.is_empty() && values.values.get(&key).is_some_and(|file| *file != value) {
+        values.overridden.push(key.clone());
+      }
+      values.values.insert(key, value);
+    }
+    values
+  }
+
+  /// Creates values from key-value pairs, without reading any source.
+  pub fn from_pairs<K: Into<String>, V: Into<String>>(
+    pairs: impl IntoIterator<Item = (K, V)>,
+  ) -> Self {
+    Self {
+      values: pairs
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect(),
+      overridden: Vec::new(),
+      errors: Vec::new(),
+    }
+  }
+
+  /// Merges a TOML file. Nested tables are joined with `_` and keys are upper-cased, so
+  /// `[database] url = "..."` sets `DATABASE_URL`. Arrays are joined with `,`.
+  pub fn merge_file(&mut self, path: &Path) {
+    let table = match fs::read_to_string(path)
+      .map_err(|e| e.to_string())
+      .and_then(|content| content.parse::<toml::Table>().map_err(|e| e.to_string()))
+    {
+      Ok(table) => table,
+      Err(err) => {
+        self.errors.push(format!("{}: {}", path.display(), err));
+        return;
+      }
+    };
+    flatten("", &table, &mut self.values);
+  }
+
+  /// Returns the raw value of `key`, reading it from `{key}_FILE` if set.
+  pub fn raw(&mut self, key: &str) -> Option<String> {
+    let file_key = format!("{key}_FILE");
+    let value = self
+      .values
+      .get(key)
+      .filter(|value| !value.is_empty())
+      .cloned();
+    let Some(path) = self
+      .values
+      .get(&file_key)
+      .filter(|value| !value.is_empty())
+      .cloned()
+    else {
+      return value;
+    };
+
+    if value.is_some() {
+      self
+        .errors
+        .push(format!("{key}: both {key} and {file_key} are set"));
+      return None;
+    }
+
+    match fs::read_to_string(&path) {
+      Ok(content) => Some(content.trim_end_matches(['\r', '\n']).to_string()),
+      Err(err) => {
+        self
+          .errors
+          .push(format!("{file_key}: unable to read \"{path}\": {err}"));
+        None
+      }
+    }
+  }
+
+  /// Returns the value of `key` or `default` if it is not set.
+  pub fn string(&mut self, key: &str, default: &str) -> String {
+    self.raw(key).unwrap_or_else(|| default.to_string())
+  }
+
+  /// Parses the value of `key`, recording an error if it is missing or invalid.
+  pub fn required<T>(&mut self, key: &str) -> Option<T>
+  where
+    T: FromStr,
+    T::Err: Display,
+  {
+    let errors = self.errors.len();
+    match self.raw(key) {
+      Some(raw) => self.parse(key, raw),
+      None => {
+        // Do not report a missing value when reading `{key}_FILE` already failed.
+        if self.errors.len() == errors {
+          self.errors.push(format!("{key}: missing value"));
+        }
+        None
+      }
+    }
+  }
+
+  /// Parses the value of `key` or returns `default` if it is not set.
+  pub fn parse_or<T>(&mut self, key: &str, default: T) -> T
+  where
+    T: FromStr,
+    T::Err: Display,
+  {
+    self.optional(key).unwrap_or(default)
+  }
+
+  /// Parses the value of `key` if it is set.
+  pub fn optional<T>(&mut self, key: &str) -> Option<T>
+  where
+    T: FromStr,
+    T::Err: Display,
+  {
+    let raw = self.raw(key)?;
+    self.parse(key, raw)
+  }
+
+  fn parse<T>(&mut self, key: &str, raw: String) -> Option<T>
+  where
+    T: FromStr,
+    T::Err: Display,
+  {
+    match raw.parse::<T>() {
+      Ok(value) => Some(value),
+      Err(err) => {
+        self
+          .errors
+          .push(format!("{key}: invalid value \"{raw}\": {err}"));
+        None
+      }
+    }
+  }
+
+  /// Whether the configuration file sets `key` to a value the environment overrides.
+  pub fn is_overridden(&self, key: &str) -> bool {
+    self.overridden.iter().any(|overridden| overridden == key)
+  }
+
+  /// Records an error for a value that parsed but failed validation.
+  pub fn invalid(&mut self, key: &str, message: impl Display) {
+    self.errors.push(format!("{key}: {message}"));
+  }
+
+  /// Returns every recorded error.
+  pub fn finish(self) -> Result<(), ConfigError> {
+    if self.errors.is_empty() {
+      Ok(())
+    } else {
+      Err(ConfigError(self.errors))
+    }
+  }
+}
+
+/// Returns the path of the configuration file selected by `APP_ENV`, which may not exist.
+pub fn config_file() -> Option<PathBuf> {
+  let app_env = std::env::var("APP_ENV")
+    .ok()
+    .filter(|value| !value.is_empty())?;
+  let config_dir = std::env::var("CONFIG_DIR").unwrap_or_else(|_| "config".to_string());
+  Some(Path::new(&config_dir).join(format!("{app_env}.toml")))
+}
+
+fn flatten(prefix: &str, table: &toml::Table, out: &mut HashMap<String, String>) {
+  for (key, value) in table {
+    let key = key.to_uppercase().replace('-', "_");
+    let name = if prefix.is_empty() {
+      key
+    } else {
+      format!("{prefix}_{key}")
+    };
+    match value {
+      toml::Value::Table(table) => flatten(&name, table, out),
+      toml::Value::Array(items) => {
+        let items: Vec<String> = items.iter().map(scalar).collect();
+        out.insert(name, items.join(","));
+      }
+      value => {
+        out.insert(name, scalar(value));
+      }
+    }
+  }
+}
+
+fn scalar(value: &toml::Value) -> String {
+  match value {
+    toml::Value::String(value) => value.clone(),
+    value => value.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_accessors_collect_every_error() {
+    let mut values = Values::from_pairs([("PORT", "not-a-port"), ("TIMEOUT", "-1")]);
+
+    assert_eq!(values.required::<u16>("PORT"), None);
+    assert_eq!(values.parse_or::<u64>("TIMEOUT", 5), 5);
+    assert_eq!(values.required::<String>("DATABASE_URL"), None);
+
+    let errors = values.finish().unwrap_err().0;
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0].starts_with("PORT: invalid value \"not-a-port\""));
+    assert!(errors[1].starts_with("TIMEOUT: invalid value \"-1\""));
+    assert_eq!(errors[2], "DATABASE_URL: missing value");
+  }
+
+  #[test]
+  fn test_empty_values_are_unset() {
+    let mut values = Values::from_pairs([("SWAGGER_ENDPOINT", "")]);
+    assert_eq!(values.string("SWAGGER_ENDPOINT", "/docs"), "/docs");
+    assert!(values.finish().is_ok());
+  }
+
+  #[test]
+  fn test_toml_tables_are_flattened() {
+    let table: toml::Table = r#"
+      port = 8080
+      [database]
+      url = "postgres://localhost/example"
+      run-migrations = true
+      [cors]
+      allowed_origins = ["http://a.test", "http://b.test"]
+    "#
+    .parse()
+    .unwrap();
+
+    let mut out = HashMap::new();
+    flatten("", &table, &mut out);
+    assert_eq!(out["PORT"], "8080");
+    assert_eq!(out["DATABASE_URL"], "postgres://localhost/example");
+    assert_eq!(out["DATABASE_RUN_MIGRATIONS"], "true");
+    assert_eq!(out["CORS_ALLOWED_ORIGINS"], "http://a.test,http://b.test");
+  }
+
+  #[test]
+  fn test_value_read_from_file() {
+    let path = std::env::temp_dir().join(format!("cfg-secret-{}", uuid::Uuid::now_v7()));
+    fs::write(&path, "s3cret\n").unwrap();
+
+    let mut values = Values::from_pairs([("JWT_SECRET_FILE", path.to_str().unwrap())]);
+    assert_eq!(values.raw("JWT_SECRET").as_deref(), Some("s3cret"));
+    assert!(values.finish().is_ok());
+    fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_value_and_file_conflict() {
+    let mut values =
+      Values::from_pairs([("JWT_SECRET", "a"), ("JWT_SECRET_FILE", "/run/secrets/jwt")]);
+    assert_eq!(values.required::<String>("JWT_SECRET"), None);
+
+    let errors = values.finish().unwrap_err().0;
+    assert_eq!(
+      errors,
+      vec!["JWT_SECRET: both JWT_SECRET and JWT_SECRET_FILE are set"]
+    );
+  }
+
+  #[test]
+  fn test_missing_file_is_reported() {
+    let mut values = Values::from_pairs([("DATABASE_URL_FILE", "/nonexistent/secret")]);
+    assert_eq!(values.required::<String>("DATABASE_URL"), None);
+
+    let errors = values.finish().unwrap_err().0;
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("DATABASE_URL_FILE: unable to read \"/nonexistent/secret\""));
+  }
+}
@@ -0,0 +1,771 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt,
+  net::{Ipv6Addr, SocketAddr},
+  str::FromStr,
+  sync::Arc,
+};
+
+use crate::common::graphql::persisted::Allowlist;
+
+pub mod basic_auth;
+pub mod loader;
+pub mod profile;
+pub mod runtime;
+pub mod secret;
+pub mod security_headers;
+pub mod session_cookie;
+#[cfg(test)]
+pub mod testing;
+pub mod trusted_proxies;
+
+pub use basic_auth::BasicAuthConfig;
+pub use loader::{ConfigError, Values};
+pub use profile::{EnvironmentProfile, LogFormat};
+pub use runtime::RuntimeSettings;
+pub use secret::{Dsn, Secret};
+pub use security_headers::SecurityHeadersConfig;
+pub use session_cookie::SessionCookieConfig;
+pub use trusted_proxies::TrustedProxies;
+
+pub type Config = Arc<Configuration>;
+
+/// The JWT signing secret used when `JWT_SECRET` is not set.
+pub const DEFAULT_JWT_SECRET: &str = "a-string-secret-at-least-256-bits-long";
+
+/// The configuration of the application, read from the environment and the configuration file.
+///
+/// It is not `Deserialize`: `Configuration::from_values` reads it through `Values`, which supports
+/// the `{KEY}_FILE` variants, applies the defaults of the environment profile and reports every
+/// invalid value at once. A derived implementation would bypass all of that.
+#[derive(Serialize, Debug)]
+pub struct Configuration {
+  /// The environment in which to run the application.
+  pub env: Environment,
+
+  /// The behaviour attached to the environment
+  pub profile: EnvironmentProfile,
+
+  /// The address to listen on.
+  pub listen_address: SocketAddr,
+
+  /// The port to listen on.
+  pub app_port: u16,
+
+  /// The swagger endpoint
+  pub swagger_endpoint: String,
+
+  /// The users allowed to reach the Swagger endpoint with basic authentication, and the networks
+  /// it can be reached from. If no user is set, the Swagger endpoint will not be protected.
+  pub swagger_basic_auth: BasicAuthConfig,
+
+  /// The graphql endpoint
+  pub graphql_endpoint: String,
+
+  /// The users allowed to reach GraphiQL with basic authentication, and the networks it can be
+  /// reached from. If no user is set, GraphiQL will not be protected.
+  pub graphql_basic_auth: BasicAuthConfig,
+
+  /// Maximum nesting depth of a GraphQL operation, 0 disables the limit
+  pub graphql_depth_limit: usize,
+
+  /// Maximum complexity of a GraphQL operation, i.e. the number of fields it selects,
+  /// 0 disables the limit
+  pub graphql_complexity_limit: usize,
+
+  /// Maximum number of items of a GraphQL page, also used when no pagination is given
+  pub graphql_max_page_size: u64,
+
+  /// Whether GraphQL introspection queries are allowed
+  pub graphql_introspection: bool,
+
+  /// Where the queries registered with Automatic Persisted Queries are kept
+  pub graphql_persisted_queries: PersistedQueryCache,
+
+  /// Maximum number of persisted queries kept in memory
+  pub graphql_persisted_queries_capacity: usize,
+
+  /// Maximum number of persisted queries kept in the database
+  pub graphql_persisted_queries_max_rows: u64,
+
+  /// Maximum length in bytes of the queries registered with Automatic Persisted Queries,
+  /// 0 disables the limit
+  pub graphql_persisted_query_max_length: usize,
+
+  /// The JSON file mapping the SHA-256 of the allowed GraphQL operations to their query.
+  /// If not set, every operation is allowed.
+  pub graphql_allowlist_path: Option<String>,
+
+  /// The allow-list read from `graphql_allowlist_path`
+  #[serde(skip)]
+  pub graphql_allowlist: Option<Arc<Allowlist>>,
+
+  /// Whether only the operations of the allow-list are executed
+  pub graphql_allowlist_strict: bool,
+
+  /// The basic auth users allowed to see the effective configuration on the info endpoint, in
+  /// addition to admins.
+  pub info_basic_auth: BasicAuthConfig,
+
+  /// The security headers set on every response, e.g. HSTS and the content security policies
+  pub security_headers: SecurityHeadersConfig,
+
+  /// The attributes of the cookies set by the cookie login
+  pub session_cookie: SessionCookieConfig,
+
+  /// The reverse proxies trusted to forward the client address in `X-Forwarded-For`
+  pub trusted_proxies: TrustedProxies,
+
+  /// The secret used to sign and verify JWTs
+  pub jwt_secret: Secret,
+
+  /// The DSN for the database. Currently, only PostgreSQL is supported.
+  pub db_dsn: Dsn,
+
+  /// The DSNs of the read replicas, empty if reads go to the primary
+  pub db_replica_dsns: Vec<Dsn>,
+
+  /// Interval in seconds between read replica health checks
+  pub db_replica_check_interval: u64,
+
+  /// Seconds during which a client that wrote keeps reading from the primary, 0 disables it
+  pub db_read_your_writes_window: u64,
+
+  /// Maximum number of connections in the database pool
+  pub db_pool_max_size: u32,
+
+  /// Database connection timeout in seconds
+  pub db_timeout: u64,
+
+  /// The DSN used to apply migrations, e.g. for a role allowed to change the schema.
+  /// If not set, migrations run with `db_dsn`.
+  pub db_migration_dsn: Option<Dsn>,
+
+  /// What to do with pending database migrations on startup
+  pub db_migrations: MigrationMode,
+
+  /// Whether the seeders run on startup
+  pub seed_on_startup: bool,
+
+  /// The directory holding the YAML and JSON fixture files
+  pub seed_fixtures_dir: String,
+
+  /// The email of the admin created by the seeders. If not set, no admin is created.
+  pub admin_email: Option<String>,
+
+  /// The name of the admin created by the seeders
+  pub admin_name: String,
+
+  /// The password of the admin created by the seeders
+  pub admin_password: Option<Secret>,
+
+  /// Timeout in seconds for each readiness check
+  pub health_check_timeout: u64,
+
+  /// Seconds to wait after the readiness probe starts failing before draining requests
+  pub shutdown_pre_stop_delay: u64,
+
+  /// Maximum number of seconds to wait for in-flight requests and background tasks on shutdown
+  pub shutdown_drain_timeout: u64,
+
+  /// Seconds between checks of the configuration file for changes, 0 disables watching.
+  /// Runtime settings are also reloaded on SIGHUP.
+  pub config_reload_interval: u64,
+
+  /// The settings that can be reloaded at runtime, as loaded at startup. The current ones are
+  /// held by `Settings`.
+  pub runtime: RuntimeSettings,
+
+  /// Whether to expose Prometheus metrics
+  pub metrics_enabled: bool,
+
+  /// The metrics endpoint
+  pub metrics_endpoint: String,
+
+  /// The address of a dedicated metrics listener.
+  /// If not set, metrics are served on the main listener and require an admin token.
+  pub metrics_listen_address: Option<SocketAddr>,
+
+  /// The OTLP collector endpoint, e.g. "http://localhost:4317".
+  /// If not set, spans are not exported.
+  pub otel_exporter_endpoint: Option<String>,
+
+  /// The protocol used to export spans to the OTLP collector.
+  pub otel_exporter_protocol: OtlpProtocol,
+
+  /// The service name reported with exported spans
+  pub otel_service_name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+  Development,
+  Test,
+  Staging,
+  Production,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtlpProtocol {
+  Grpc,
+  HttpProtobuf,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationMode {
+  /// Apply pending migrations before serving
+  Apply,
+  /// Refuse to start if migrations are pending, for deployments that migrate in a separate job
+  Verify,
+  /// Do not check migrations
+  Skip,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PersistedQueryCache {
+  /// Automatic Persisted Queries are not served
+  Disabled,
+  /// Each instance keeps the queries in memory
+  Memory,
+  /// The queries are shared by every instance in the database
+  Postgres,
+}
+
+impl Configuration {
+  /// Loads the configuration, returning every invalid value on failure.
+  pub fn new() -> Result<Configuration, Vec<String>> {
+    Self::load().map_err(|err| err.0)
+  }
+
+  /// Loads the configuration from the layered sources described in [`Values`].
+  pub fn load() -> Result<Configuration, ConfigError> {
+    Self::from_values(Values::load())
+  }
+
+  /// Builds the configuration from already loaded values.
+  pub fn from_values(mut values: Values) -> Result<Configuration, ConfigError> {
+    let env = values.required::<Environment>("APP_ENV");
+    let profile = env.map(|env| env.profile());
+    let app_port = values.required::<u16>("PORT");
+
+    // Swagger endpoint
+    let swagger_endpoint = values.string("SWAGGER_ENDPOINT", "/docs");
+
+    // Swagger basic auth users and allowed networks
+    let swagger_basic_auth = BasicAuthConfig::from_values(&mut values, "SWAGGER_BASIC_AUTH", true);
+
+    // Graphql endpoint
+    let graphql_endpoint = values.string("GRAPHQL_ENDPOINT", "/graphql");
+
+    // GraphiQL basic auth users and allowed networks
+    let graphql_basic_auth = BasicAuthConfig::from_values(&mut values, "GRAPHQL_BASIC_AUTH", true);
+
+    // GraphQL limits, so that a single operation cannot exhaust the database pool
+    let graphql_depth_limit = values.parse_or::<usize>("GRAPHQL_DEPTH_LIMIT", 10);
+    let graphql_complexity_limit = values.parse_or::<usize>("GRAPHQL_COMPLEXITY_LIMIT", 200);
+    let graphql_max_page_size = values.parse_or::<u64>("GRAPHQL_MAX_PAGE_SIZE", 100);
+    if graphql_max_page_size == 0 {
+      values.invalid("GRAPHQL_MAX_PAGE_SIZE", "must be at least 1");
+    }
+
+    // Introspection follows the API documentation by default
+    let graphql_introspection = values.parse_or::<bool>(
+      "GRAPHQL_INTROSPECTION",
+      profile.is_some_and(|profile| profile.mount_docs),
+    );
+
+    // Persisted queries are kept in memory by default
+    let graphql_persisted_queries = values
+      .parse_or::<PersistedQueryCache>("GRAPHQL_PERSISTED_QUERIES", PersistedQueryCache::Memory);
+    let graphql_persisted_queries_capacity =
+      values.parse_or::<usize>("GRAPHQL_PERSISTED_QUERIES_CAPACITY", 1000);
+    if graphql_persisted_queries_capacity == 0 {
+      values.invalid("GRAPHQL_PERSISTED_QUERIES_CAPACITY", "must be at least 1");
+    }
+    let graphql_persisted_queries_max_rows =
+      values.parse_or::<u64>("GRAPHQL_PERSISTED_QUERIES_MAX_ROWS", 10_000);
+    if graphql_persisted_queries_max_rows == 0 {
+      values.invalid("GRAPHQL_PERSISTED_QUERIES_MAX_ROWS", "must be at least 1");
+    }
+    let graphql_persisted_query_max_length =
+      values.parse_or::<usize>("GRAPHQL_PERSISTED_QUERY_MAX_LENGTH", 10_000);
+
+    // The allow-list is enforced in production by default, once one is configured
+    let graphql_allowlist_path = values.optional::<String>("GRAPHQL_ALLOWLIST_PATH");
+    let graphql_allowlist = graphql_allowlist_path
+      .as_ref()
+      .and_then(|path| match Allowlist::load(path) {
+        Ok(allowlist) => Some(Arc::new(allowlist)),
+        Err(e) => {
+          values.invalid("GRAPHQL_ALLOWLIST_PATH", e);
+          None
+        }
+      });
+    let graphql_allowlist_strict = values.parse_or::<bool>(
+      "GRAPHQL_ALLOWLIST_STRICT",
+      graphql_allowlist_path.is_some()
+        && profile.is_some_and(|profile| profile.enforce_graphql_allowlist),
+    );
+    if graphql_allowlist_strict && graphql_allowlist_path.is_none() {
+      values.invalid(
+        "GRAPHQL_ALLOWLIST_STRICT",
+        "requires GRAPHQL_ALLOWLIST_PATH",
+      );
+    }
+
+    // Info endpoint basic auth users
+    let info_basic_auth = BasicAuthConfig::from_values(&mut values, "INFO_BASIC_AUTH", false);
+
+    // HSTS is enabled by default in the environments served over HTTPS
+    let security_headers = SecurityHeadersConfig::from_values(
+      &mut values,
+      profile.is_some_and(|profile| profile.strict_transport_security),
+    );
+
+    // Session cookies are secure by default in the environments served over HTTPS
+    let session_cookie = SessionCookieConfig::from_values(
+      &mut values,
+      profile.is_some_and(|profile| profile.secure_cookies),
+    );
+
+    // No proxy is trusted to forward the client address by default
+    let trusted_proxies = TrustedProxies::from_values(&mut values);
+
+    let jwt_secret = Secret::new(values.string("JWT_SECRET", DEFAULT_JWT_SECRET));
+    if let (Some(env), Some(profile)) = (env, profile) {
+      if profile.require_jwt_secret && jwt_secret.expose() == DEFAULT_JWT_SECRET {
+        values.invalid(
+          "JWT_SECRET",
+          format!("the default secret must not be used in {env}"),
+        );
+      }
+    }
+
+    let db_dsn = values.required::<String>("DATABASE_URL").map(Dsn::new);
+
+    // Read replicas, comma-separated
+    let db_replica_dsns = values
+      .string("DATABASE_REPLICA_URLS", "")
+      .split(',')
+      .map(str::trim)
+      .filter(|dsn| !dsn.is_empty())
+      .map(Dsn::new)
+      .collect();
+
+    // Replicas are checked every 5 seconds by default
+    let db_replica_check_interval = values.parse_or::<u64>("DATABASE_REPLICA_CHECK_INTERVAL", 5);
+
+    // Clients read from the primary for 5 seconds after writing by default
+    let db_read_your_writes_window = values.parse_or::<u64>("DATABASE_READ_YOUR_WRITES_WINDOW", 5);
+
+    // Default pool size is 10 if not specified
+    let db_pool_max_size = values.parse_or::<u32>("DATABASE_POOL_MAX_SIZE", 10);
+
+    // Default timeout is 5 seconds if not specified
+    let db_timeout = values.parse_or::<u64>("DATABASE_TIMEOUT", 5);
+
+    // Privileged DSN for migrations, the application DSN is used if not specified
+    let db_migration_dsn = values
+      .optional::<String>("DATABASE_MIGRATION_URL")
+      .map(Dsn::new);
+
+    // Default to applying in development and test, skipping in staging and production
+    let db_migrations = values.parse_or::<MigrationMode>(
+      "DATABASE_RUN_MIGRATIONS",
+      if profile.is_some_and(|profile| profile.run_migrations) {
+        MigrationMode::Apply
+      } else {
+        MigrationMode::Skip
+      },
+    );
+
+    // Seeding on startup is opt-in. The seeders load the fixture users, whose passwords are
+    // public, so they are refused where destructive commands are.
+    let seed_on_startup = values.parse_or::<bool>("SEED_ON_STARTUP", false);
+    if seed_on_startup && profile.is_some_and(|profile| !profile.allow_destructive_commands) {
+      values.invalid(
+        "SEED_ON_STARTUP",
+        "loads the fixture users, which is only allowed in development and test",
+      );
+    }
+
+    // Fixture files are read from "fixtures" by default
+    let seed_fixtures_dir = values.string("SEED_FIXTURES_DIR", "fixtures");
+
+    // Initial admin, none if not specified
+    let admin_email = values.optional::<String>("ADMIN_EMAIL");
+    let admin_name = values.string("ADMIN_NAME", "Admin");
+    let admin_password = values.optional::<String>("ADMIN_PASSWORD").map(Secret::new);
+    if admin_email.is_some() && admin_password.is_none() {
+      values.invalid("ADMIN_PASSWORD", "required when ADMIN_EMAIL is set");
+    }
+
+    // Default health check timeout is 2 seconds if not specified
+    let health_check_timeout = values.parse_or::<u64>("HEALTH_CHECK_TIMEOUT", 2);
+
+    // No pre-stop delay by default
+    let shutdown_pre_stop_delay = values.parse_or::<u64>("SHUTDOWN_PRE_STOP_DELAY", 0);
+
+    // Default drain timeout is 30 seconds if not specified
+    let shutdown_drain_timeout = values.parse_or::<u64>("SHUTDOWN_DRAIN_TIMEOUT", 30);
+
+    // The configuration file is checked for changes every 5 seconds by default
+    let config_reload_interval = values.parse_or::<u64>("CONFIG_RELOAD_INTERVAL", 5);
+
+    let runtime = RuntimeSettings::from_values(&mut values);
+
+    // Metrics are enabled by default
+    let metrics_enabled = values.parse_or::<bool>("METRICS_ENABLED", true);
+
+    // Metrics endpoint
+    let metrics_endpoint = values.string("METRICS_ENDPOINT", "/metrics");
+
+    // Dedicated metrics listener, e.g. "0.0.0.0:9090"
+    let metrics_listen_address = values.optional::<SocketAddr>("METRICS_LISTEN_ADDRESS");
+
+    // OTLP exporter, disabled unless an endpoint is set
+    let otel_exporter_endpoint = values.optional::<String>("OTEL_EXPORTER_OTLP_ENDPOINT");
+
+    // Default protocol is gRPC if not specified
+    let otel_exporter_protocol =
+      values.parse_or::<OtlpProtocol>("OTEL_EXPORTER_OTLP_PROTOCOL", OtlpProtocol::Grpc);
+
+    // Service name reported to the collector
+    let otel_service_name = values.string("OTEL_SERVICE_NAME", env!("CARGO_PKG_NAME"));
+
+    values.finish()?;
+    // Every required value is set once `finish` succeeds.
+    let (Some(env), Some(profile), Some(app_port), Some(db_dsn)) = (env, profile, app_port, db_dsn)
+    else {
+      unreachable!("missing required values are reported as errors");
+    };
+
+    let listen_address = SocketAddr::from((Ipv6Addr::UNSPECIFIED, app_port));
+
+    Ok(Configuration {
+      env,
+      profile,
+      listen_address,
+      app_port,
+      swagger_endpoint,
+      swagger_basic_auth,
+      graphql_endpoint,
+      graphql_basic_auth,
+      graphql_depth_limit,
+      graphql_complexity_limit,
+      graphql_max_page_size,
+      graphql_introspection,
+      graphql_persisted_queries,
+      graphql_persisted_queries_capacity,
+      graphql_persisted_queries_max_rows,
+      graphql_persisted_query_max_length,
+      graphql_allowlist_path,
+      graphql_allowlist,
+      graphql_allowlist_strict,
+      info_basic_auth,
+      security_headers,
+      session_cookie,
+      trusted_proxies,
+      jwt_secret,
+      db_dsn,
+      db_replica_dsns,
+      db_replica_check_interval,
+      db_read_your_writes_window,
+      db_pool_max_size,
+      db_timeout,
+      db_migration_dsn,
+      db_migrations,
+      seed_on_startup,
+      seed_fixtures_dir,
+      admin_email,
+      admin_name,
+      admin_password,
+      health_check_timeout,
+      shutdown_pre_stop_delay,
+      shutdown_drain_timeout,
+      config_reload_interval,
+      runtime,
+      metrics_enabled,
+      metrics_endpoint,
+      metrics_listen_address,
+      otel_exporter_endpoint,
+      otel_exporter_protocol,
+      otel_service_name,
+    })
+  }
+
+  /// Sets the database DSN.
+  /// This method is used in tests to override the database DSN.
+  pub fn set_dsn(&mut self, db_dsn: String) {
+    self.db_dsn = Dsn::new(db_dsn)
+  }
+}
+
+impl FromStr for Environment {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "development" => Ok(Environment::Development),
+      "test" => Ok(Environment::Test),
+      "staging" => Ok(Environment::Staging),
+      "production" => Ok(Environment::Production),
+      _ => Err(format!(
+        "Invalid environment: {}. Please make sure it is one of \"development\", \"test\", \"staging\" or \"production\".",
+        s
+      )),
+    }
+  }
+}
+
+impl fmt::Display for Environment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Environment::Development => "development",
+      Environment::Test => "test",
+      Environment::Staging => "staging",
+      Environment::Production => "production",
+    };
+    f.write_str(name)
+  }
+}
+
+impl FromStr for OtlpProtocol {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "grpc" => Ok(OtlpProtocol::Grpc),
+      "http/protobuf" | "http" => Ok(OtlpProtocol::HttpProtobuf),
+      _ => Err(format!(
+        "Invalid OTLP protocol: {}. Please make sure it is either \"grpc\" or \"http/protobuf\".",
+        s
+      )),
+    }
+  }
+}
+
+impl FromStr for MigrationMode {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    // Booleans are accepted for compatibility with the former flag.
+    match s {
+      "apply" | "true" => Ok(MigrationMode::Apply),
+      "verify" => Ok(MigrationMode::Verify),
+      "skip" | "false" => Ok(MigrationMode::Skip),
+      _ => Err(format!(
+        "Invalid migration mode: {}. Please make sure it is one of \"apply\", \"verify\" or \"skip\".",
+        s
+      )),
+    }
+  }
+}
+
+impl FromStr for PersistedQueryCache {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "disabled" | "false" => Ok(PersistedQueryCache::Disabled),
+      "memory" => Ok(PersistedQueryCache::Memory),
+      "postgres" => Ok(PersistedQueryCache::Postgres),
+      _ => Err(format!(
+        "Invalid persisted query cache: {}. Please make sure it is one of \"disabled\", \"memory\" or \"postgres\".",
+        s
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_environment_from_str() {
+    assert!(matches!(
+      "development".parse::<Environment>(),
+      Ok(Environment::Development)
+    ));
+    assert!(matches!(
+      "staging".parse::<Environment>(),
+      Ok(Environment::Staging)
+    ));
+    assert!(matches!(
+      "production".parse::<Environment>(),
+      Ok(Environment::Production)
+    ));
+    assert!("unknown".parse::<Environment>().is_err());
+    assert_eq!(Environment::Staging.to_string(), "staging");
+  }
+
+  #[test]
+  fn test_migration_mode_from_str() {
+    assert_eq!("true".parse::<MigrationMode>(), Ok(MigrationMode::Apply));
+    assert_eq!("verify".parse::<MigrationMode>(), Ok(MigrationMode::Verify));
+    assert_eq!("false".parse::<MigrationMode>(), Ok(MigrationMode::Skip));
+    assert!("yes".parse::<MigrationMode>().is_err());
+  }
+
+  fn values() -> Vec<(&'static str, &'static str)> {
+    vec![
+      ("APP_ENV", "production"),
+      ("PORT", "8080"),
+      (
+        "DATABASE_URL",
+        "postgres://postgres:password@db:5432/example",
+      ),
+      ("JWT_SECRET", "a-production-secret-at-least-256-bits-long"),
+    ]
+  }
+
+  #[test]
+  fn test_defaults() {
+    let cfg = Configuration::from_values(Values::from_pairs(values())).unwrap();
+    assert_eq!(cfg.swagger_endpoint, "/docs");
+    assert_eq!(cfg.db_pool_max_size, 10);
+    assert_eq!(cfg.db_migrations, MigrationMode::Skip);
+    assert!(cfg.db_migration_dsn.is_none());
+    assert_eq!(cfg.otel_exporter_protocol, OtlpProtocol::Grpc);
+    assert_eq!(cfg.profile, Environment::Production.profile());
+    assert_eq!(cfg.graphql_persisted_queries, PersistedQueryCache::Memory);
+    assert!(!cfg.graphql_allowlist_strict);
+    assert_eq!(cfg.security_headers.hsts_max_age, 31_536_000);
+    assert!(cfg.session_cookie.secure);
+  }
+
+  #[test]
+  fn test_graphql_allowlist_is_strict_in_production() {
+    let path = std::env::temp_dir().join(format!("allowlist-{}.json", uuid::Uuid::now_v7()));
+    std::fs::write(&path, "{}").unwrap();
+    let mut pairs = values();
+    pairs.push(("GRAPHQL_ALLOWLIST_PATH", path.to_str().unwrap()));
+    let cfg = Configuration::from_values(Values::from_pairs(pairs.clone())).unwrap();
+    assert!(cfg.graphql_allowlist_strict);
+    assert!(cfg.graphql_allowlist.is_some());
+
+    pairs.push(("APP_ENV", "staging"));
+    let cfg = Configuration::from_values(Values::from_pairs(pairs)).unwrap();
+    assert!(!cfg.graphql_allowlist_strict);
+
+    let mut pairs = values();
+    pairs.push(("GRAPHQL_ALLOWLIST_STRICT", "true"));
+    let errors = Configuration::from_values(Values::from_pairs(pairs))
+      .unwrap_err()
+      .0;
+    assert_eq!(
+      errors,
+      vec!["GRAPHQL_ALLOWLIST_STRICT: requires GRAPHQL_ALLOWLIST_PATH"]
+    );
+
+    std::fs::write(&path, r#"{ "00": "{ __typename }" }"#).unwrap();
+    let mut pairs = values();
+    pairs.push(("GRAPHQL_ALLOWLIST_PATH", path.to_str().unwrap()));
+    let errors = Configuration::from_values(Values::from_pairs(pairs))
+      .unwrap_err()
+      .0;
+    assert_eq!(
+      errors,
+      vec!["GRAPHQL_ALLOWLIST_PATH: The hash 00 of the allow-list does not match its query"]
+    );
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_default_jwt_secret_is_rejected_by_profile() {
+    let mut pairs = values();
+    pairs.retain(|(key, _)| *key != "JWT_SECRET");
+
+    let errors = Configuration::from_values(Values::from_pairs(pairs.clone()))
+      .unwrap_err()
+      .0;
+    assert_eq!(
+      errors,
+      vec!["JWT_SECRET: the default secret must not be used in production"]
+    );
+
+    pairs.push(("APP_ENV", "development"));
+    let cfg = Configuration::from_values(Values::from_pairs(pairs)).unwrap();
+    assert_eq!(cfg.jwt_secret.expose(), DEFAULT_JWT_SECRET);
+    assert_eq!(cfg.db_migrations, MigrationMode::Apply);
+  }
+
+  #[test]
+  fn test_every_error_is_reported() {
+    let mut pairs = values();
+    pairs.retain(|(key, _)| *key != "DATABASE_URL");
+    pairs.extend([
+      ("PORT", "http"),
+      ("DATABASE_TIMEOUT", "soon"),
+      ("GRAPHQL_BASIC_AUTH", "admin"),
+      ("RUST_LOG", "app=loud"),
+    ]);
+
+    let errors = Configuration::from_values(Values::from_pairs(pairs))
+      .unwrap_err()
+      .0;
+    let keys: Vec<&str> = errors
+      .iter()
+      .map(|error| error.split(':').next().unwrap())
+      .collect();
+    assert_eq!(
+      keys,
+      vec![
+        "PORT",
+        "GRAPHQL_BASIC_AUTH",
+        "DATABASE_URL",
+        "DATABASE_TIMEOUT",
+        "RUST_LOG"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_seeding_is_validated() {
+    let mut pairs = values();
+    pairs.push(("APP_ENV", "development"));
+    let cfg = Configuration::from_values(Values::from_pairs(pairs)).unwrap();
+    assert!(!cfg.seed_on_startup);
+
+    let mut pairs = values();
+    pairs.extend([
+      ("SEED_ON_STARTUP", "true"),
+      ("ADMIN_EMAIL", "admin@example.com"),
+    ]);
+    let errors = Configuration::from_values(Values::from_pairs(pairs.clone()))
+      .unwrap_err()
+      .0;
+    assert_eq!(
+      errors,
+      vec![
+        "SEED_ON_STARTUP: loads the fixture users, which is only allowed in development and test",
+        "ADMIN_PASSWORD: required when ADMIN_EMAIL is set"
+      ]
+    );
+
+    pairs.extend([("APP_ENV", "test"), ("ADMIN_PASSWORD", "admin-password")]);
+    let cfg = Configuration::from_values(Values::from_pairs(pairs)).unwrap();
+    assert!(cfg.seed_on_startup);
+  }
+
+  #[test]
+  fn test_debug_redacts_secrets() {
+    let mut pairs = values();
+    pairs.extend([
+      ("SWAGGER_BASIC_AUTH", "admin:swagger-password"),
+      ("INFO_BASIC_AUTH", "ops:info-password"),
+      ("JWT_SECRET", "jwt-signing-key"),
+    ]);
+    let cfg = Configuration::from_values(Values::from_pairs(pairs)).unwrap();
+
+    let debug = format!("{:?}", cfg);
+    assert!(!debug.contains("swagger-password"));
+    assert!(!debug.contains("info-password"));
+    assert!(!debug.contains("jwt-signing-key"));
+    assert!(!debug.contains(":password@"));
+    assert!(debug.contains("postgres://postgres:[REDACTED]@db:5432/example"));
+
+    let json = serde_json::to_string(&cfg).unwrap();
+    assert!(!json.contains("swagger-password"));
+    assert!(!json.contains("jwt-signing-key"));
+  }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use super::Environment;
+
+/// Behaviour that depends on the environment the application runs in.
+///
+/// Code should check the profile rather than matching on [`Environment`], so that adding an
+/// environment only requires deciding its profile here.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvironmentProfile {
+  /// The format of log lines
+  pub log_format: LogFormat,
+
+  /// Whether the Swagger UI and GraphiQL are mounted
+  pub mount_docs: bool,
+
+  /// Whether error responses include the underlying error, e.g. database errors
+  pub error_details: bool,
+
+  /// Whether cookies are only sent over HTTPS
+  pub secure_cookies: bool,
+
+  /// Whether browsers are told to only use HTTPS, with `Strict-Transport-Security`
+  pub strict_transport_security: bool,
+
+  /// Whether the JWT secret must be changed from its default value
+  pub require_jwt_secret: bool,
+
+  /// Whether pending migrations are applied on startup unless configured otherwise
+  pub run_migrations: bool,
+
+  /// Whether commands that can lose data, e.g. `migrate fresh`, run without `--force`
+  pub allow_destructive_commands: bool,
+
+  /// Whether only the operations of the GraphQL allow-list are executed, when one is configured
+  pub enforce_graphql_allowlist: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+  /// Human readable, multi-line logs
+  Pretty,
+  /// One JSON object per line, for log collectors
+  Json,
+}
+
+impl Environment {
+  /// Returns the behaviour attached to the environment.
+  pub fn profile(&self) -> EnvironmentProfile {
+    match self {
+      Environment::Development => EnvironmentProfile {
+        log_format: LogFormat::Pretty,
+        mount_docs: true,
+        error_details: true,
+        secure_cookies: false,
+        strict_transport_security: false,
+        require_jwt_secret: false,
+        run_migrations: true,
+        allow_destructive_commands: true,
+        enforce_graphql_allowlist: false,
+      },
+      Environment::Test => EnvironmentProfile {
+        log_format: LogFormat::Pretty,
+        mount_docs: true,
+        error_details: true,
+        secure_cookies: false,
+        strict_transport_security: false,
+        require_jwt_secret: false,
+        run_migrations: true,
+        allow_destructive_commands: true,
+        enforce_graphql_allowlist: false,
+      },
+      Environment::Staging => EnvironmentProfile {
+        log_format: LogFormat::Json,
+        mount_docs: true,
+        error_details: false,
+        secure_cookies: true,
+        strict_transport_security: true,
+        require_jwt_secret: true,
+        run_migrations: false,
+        allow_destructive_commands: false,
+        enforce_graphql_allowlist: false,
+      },
+      Environment::Production => EnvironmentProfile {
+        log_format: LogFormat::Json,
+        mount_docs: false,
+        error_details: false,
+        secure_cookies: true,
+        strict_transport_security: true,
+        require_jwt_secret: true,
+        run_migrations: false,
+        allow_destructive_commands: false,
+        enforce_graphql_allowlist: true,
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_production_profile_is_locked_down() {
+    let profile = Environment::Production.profile();
+    assert_eq!(profile.log_format, LogFormat::Json);
+    assert!(!profile.mount_docs);
+    assert!(!profile.error_details);
+    assert!(profile.secure_cookies);
+    assert!(profile.strict_transport_security);
+    assert!(profile.require_jwt_secret);
+    assert!(!profile.allow_destructive_commands);
+  }
+
+  #[test]
+  fn test_staging_profile_mounts_docs() {
+    let profile = Environment::Staging.profile();
+    assert!(profile.mount_docs);
+    assert!(!profile.error_details);
+    assert!(profile.require_jwt_secret);
+  }
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+
+use super::loader::{ConfigError, Values};
+
+/// The keys of the settings that can be reloaded.
+const KEYS: [&str; 4] = [
+  "RUST_LOG",
+  "CORS_ALLOWED_ORIGINS",
+  "RATE_LIMIT_PER_SECOND",
+  "RATE_LIMIT_BURST",
+];
+
+/// Settings that can be changed while the server is running.
+///
+/// They are reloaded on SIGHUP or when the configuration file changes, see
+/// [`crate::common::settings::Settings`]. The environment of the process cannot change, and still
+/// takes precedence over the file, so only the settings it does not set can be reloaded.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeSettings {
+  /// The log filter directives, e.g. "info,sqlx=warn"
+  pub log_filter: String,
+
+  /// The origins allowed to make cross-origin requests. "*" allows any origin.
+  pub cors_allowed_origins: Vec<String>,
+
+  /// Maximum sustained number of requests per second per client IP, 0 disables rate limiting
+  pub rate_limit_per_second: u32,
+
+  /// Number of requests a client IP can make in a burst above the sustained rate
+  pub rate_limit_burst: u32,
+}
+
+impl RuntimeSettings {
+  /// Loads the settings from the layered configuration sources.
+  pub fn load() -> Result<RuntimeSettings, ConfigError> {
+    let mut values = Values::load();
+    for key in KEYS.into_iter().filter(|key| values.is_overridden(key)) {
+      tracing::warn!("{key} of the configuration file is overridden by the environment");
+    }
+    let settings = Self::from_values(&mut values);
+    values.finish()?;
+    Ok(settings)
+  }
+
+  /// Reads the settings, recording invalid values in `values`.
+  pub fn from_values(values: &mut Values) -> RuntimeSettings {
+    // Default log filter is debug if not specified
+    let log_filter = values.string("RUST_LOG", "debug");
+    if let Err(err) = EnvFilter::try_new(&log_filter) {
+      values.invalid(
+        "RUST_LOG",
+        format!("invalid filter \"{log_filter}\": {err}"),
+      );
+    }
+
+    // Any origin is allowed by default
+    let cors_allowed_origins = values
+      .string("CORS_ALLOWED_ORIGINS", "*")
+      .split(',')
+      .map(|origin| origin.trim().trim_end_matches('/').to_string())
+      .filter(|origin| !origin.is_empty())
+      .collect();
+
+    // Rate limiting is disabled by default
+    let rate_limit_per_second = values.parse_or::<u32>("RATE_LIMIT_PER_SECOND", 0);
+    let rate_limit_burst = values.parse_or::<u32>("RATE_LIMIT_BURST", 50);
+
+    RuntimeSettings {
+      log_filter,
+      cors_allowed_origins,
+      rate_limit_per_second,
+      rate_limit_burst,
+    }
+  }
+
+  /// Whether cross-origin requests from `origin` are allowed.
+  pub fn is_origin_allowed(&self, origin: &str) -> bool {
+    self
+      .cors_allowed_origins
+      .iter()
+      .any(|allowed| allowed == "*" || allowed == origin)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_defaults() {
+    let mut values = Values::default();
+    let settings = RuntimeSettings::from_values(&mut values);
+    assert!(values.finish().is_ok());
+    assert_eq!(settings.log_filter, "debug");
+    assert!(settings.is_origin_allowed("https://example.com"));
+    assert_eq!(settings.rate_limit_per_second, 0);
+  }
+
+  #[test]
+  fn test_allowed_origins() {
+    let mut values = Values::from_pairs([(
+      "CORS_ALLOWED_ORIGINS",
+      "https://app.example.com/, https://admin.example.com",
+    )]);
+    let settings = RuntimeSettings::from_values(&mut values);
+    assert!(settings.is_origin_allowed("https://app.example.com"));
+    assert!(settings.is_origin_allowed("https://admin.example.com"));
+    assert!(!settings.is_origin_allowed("https://evil.example.com"));
+  }
+}
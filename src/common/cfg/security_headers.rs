@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use axum::http::HeaderValue;
+use serde::{Deserialize, Serialize};
+
+use super::Values;
+
+/// The placeholder of content security policies replaced by the nonce of each response.
+pub const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// One year, the max-age expected by the HSTS preload list.
+const DEFAULT_HSTS_MAX_AGE: u64 = 31_536_000;
+
+const DEFAULT_CSP: &str = "default-src 'none'; base-uri 'none'; form-action 'none'";
+
+/// The Swagger UI loads its assets from the application, but styles elements inline.
+const DEFAULT_SWAGGER_CSP: &str = "default-src 'none'; script-src 'self' 'nonce-{nonce}'; \
+  style-src 'self' 'unsafe-inline'; img-src 'self' data:; connect-src 'self'; base-uri 'none'; \
+  form-action 'none'";
+
+/// GraphiQL loads its assets from unpkg and starts with an inline script.
+const DEFAULT_GRAPHIQL_CSP: &str = "default-src 'none'; \
+  script-src 'nonce-{nonce}' https://unpkg.com; style-src 'unsafe-inline' https://unpkg.com; \
+  img-src 'self' data: https://graphql.org; font-src data: https://unpkg.com; \
+  connect-src 'self'; base-uri 'none'; form-action 'none'";
+
+const DEFAULT_PERMISSIONS_POLICY: &str = "accelerometer=(), camera=(), geolocation=(), \
+  gyroscope=(), magnetometer=(), microphone=(), payment=(), usb=()";
+
+/// The security headers set on every response.
+#[derive(Clone, Debug, Serialize)]
+pub struct SecurityHeadersConfig {
+  /// Seconds browsers should only use HTTPS for, 0 disables `Strict-Transport-Security`
+  pub hsts_max_age: u64,
+
+  /// Whether HSTS also applies to the subdomains
+  pub hsts_include_subdomains: bool,
+
+  /// Who may embed the responses in a frame, with both `X-Frame-Options` and `frame-ancestors`
+  pub frame_options: FrameOptions,
+
+  /// The `Referrer-Policy` header
+  pub referrer_policy: String,
+
+  /// The `Permissions-Policy` header
+  pub permissions_policy: String,
+
+  /// The `Content-Security-Policy` of the API
+  pub content_security_policy: String,
+
+  /// The `Content-Security-Policy` of the Swagger UI, where `{nonce}` is replaced by the nonce
+  /// of the response
+  pub swagger_content_security_policy: String,
+
+  /// The `Content-Security-Policy` of GraphiQL, where `{nonce}` is replaced by the nonce of the
+  /// response
+  pub graphiql_content_security_policy: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameOptions {
+  /// No page may embed the responses
+  Deny,
+  /// Only the pages of the application may embed the responses
+  SameOrigin,
+}
+
+impl SecurityHeadersConfig {
+  /// Reads the `SECURITY_*` values, HSTS being enabled by default if `strict_transport_security`
+  /// is set.
+  pub fn from_values(values: &mut Values, strict_transport_security: bool) -> Self {
+    let hsts_max_age = values.parse_or::<u64>(
+      "SECURITY_HSTS_MAX_AGE",
+      if strict_transport_security {
+        DEFAULT_HSTS_MAX_AGE
+      } else {
+        0
+      },
+    );
+    let hsts_include_subdomains = values.parse_or::<bool>("SECURITY_HSTS_INCLUDE_SUBDOMAINS", true);
+    let frame_options =
+      values.parse_or::<FrameOptions>("SECURITY_FRAME_OPTIONS", FrameOptions::Deny);
+
+    let mut header = |key: &str, default: &str| {
+      let value = values.string(key, default);
+      if HeaderValue::from_str(&value).is_err() {
+        values.invalid(key, "must be a valid header value");
+      }
+      value
+    };
+    let referrer_policy = header("SECURITY_REFERRER_POLICY", "no-referrer");
+    let permissions_policy = header("SECURITY_PERMISSIONS_POLICY", DEFAULT_PERMISSIONS_POLICY);
+    let content_security_policy = header("SECURITY_CSP", DEFAULT_CSP);
+    let swagger_content_security_policy = header("SECURITY_CSP_SWAGGER", DEFAULT_SWAGGER_CSP);
+    let graphiql_content_security_policy = header("SECURITY_CSP_GRAPHIQL", DEFAULT_GRAPHIQL_CSP);
+
+    Self {
+      hsts_max_age,
+      hsts_include_subdomains,
+      frame_options,
+      referrer_policy,
+      permissions_policy,
+      content_security_policy,
+      swagger_content_security_policy,
+      graphiql_content_security_policy,
+    }
+  }
+
+  /// The `Strict-Transport-Security` header, if enabled.
+  pub fn strict_transport_security(&self) -> Option<String> {
+    if self.hsts_max_age == 0 {
+      return None;
+    }
+    let mut value = format!("max-age={}", self.hsts_max_age);
+    if self.hsts_include_subdomains {
+      value.push_str("; includeSubDomains");
+    }
+    Some(value)
+  }
+
+  /// Completes a content security policy with the `frame-ancestors` directive matching
+  /// `X-Frame-Options`, unless it has its own.
+  pub fn with_frame_ancestors(&self, policy: &str) -> String {
+    let policy = policy.trim().trim_end_matches(';');
+    if policy
+      .split(';')
+      .any(|directive| directive.trim().starts_with("frame-ancestors"))
+    {
+      return policy.to_string();
+    }
+    let ancestors = match self.frame_options {
+      FrameOptions::Deny => "'none'",
+      FrameOptions::SameOrigin => "'self'",
+    };
+    format!("{policy}; frame-ancestors {ancestors}")
+  }
+}
+
+impl FrameOptions {
+  pub fn header(&self) -> &'static str {
+    match self {
+      FrameOptions::Deny => "DENY",
+      FrameOptions::SameOrigin => "SAMEORIGIN",
+    }
+  }
+}
+
+impl FromStr for FrameOptions {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "deny" => Ok(FrameOptions::Deny),
+      "sameorigin" => Ok(FrameOptions::SameOrigin),
+      _ => Err(format!(
+        "Invalid frame options: {}. Please make sure it is either \"deny\" or \"sameorigin\".",
+        s
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::cfg::testing;
+
+  fn config(pairs: &[(&str, &str)], strict_transport_security: bool) -> SecurityHeadersConfig {
+    testing::section(pairs, |values| {
+      SecurityHeadersConfig::from_values(values, strict_transport_security)
+    })
+  }
+
+  #[test]
+  fn test_hsts_follows_the_profile() {
+    assert_eq!(
+      config(&[], true).strict_transport_security().as_deref(),
+      Some("max-age=31536000; includeSubDomains")
+    );
+    assert_eq!(config(&[], false).strict_transport_security(), None);
+
+    let config = config(
+      &[
+        ("SECURITY_HSTS_MAX_AGE", "600"),
+        ("SECURITY_HSTS_INCLUDE_SUBDOMAINS", "false"),
+      ],
+      false,
+    );
+    assert_eq!(
+      config.strict_transport_security().as_deref(),
+      Some("max-age=600")
+    );
+  }
+
+  #[test]
+  fn test_frame_ancestors_follow_frame_options() {
+    let deny = config(&[], false);
+    assert_eq!(
+      deny.with_frame_ancestors("default-src 'none';"),
+      "default-src 'none'; frame-ancestors 'none'"
+    );
+
+    let same_origin = config(&[("SECURITY_FRAME_OPTIONS", "SAMEORIGIN")], false);
+    assert_eq!(same_origin.frame_options.header(), "SAMEORIGIN");
+    assert_eq!(
+      same_origin.with_frame_ancestors("default-src 'self'"),
+      "default-src 'self'; frame-ancestors 'self'"
+    );
+    assert_eq!(
+      same_origin.with_frame_ancestors("frame-ancestors https://example.com"),
+      "frame-ancestors https://example.com"
+    );
+  }
+
+  #[test]
+  fn test_invalid_values_are_reported() {
+    let errors = testing::try_section(
+      &[
+        ("SECURITY_FRAME_OPTIONS", "allow-from"),
+        ("SECURITY_CSP", "default-src 'self'\n"),
+      ],
+      |values| SecurityHeadersConfig::from_values(values, false),
+    )
+    .unwrap_err();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors[0].starts_with("SECURITY_FRAME_OPTIONS"));
+    assert_eq!(errors[1], "SECURITY_CSP: must be a valid header value");
+  }
+}
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::Values;
+
+/// The attributes of the session and CSRF cookies set by the cookie login.
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionCookieConfig {
+  /// Whether the cookies are only sent over HTTPS
+  pub secure: bool,
+
+  /// Whether the cookies are sent with cross-site requests
+  pub same_site: SameSite,
+
+  /// The domain the cookies are sent to, only the host that set them if not set
+  pub domain: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+  /// Only sent with same-site requests
+  Strict,
+  /// Also sent when navigating to the application from another site
+  Lax,
+  /// Sent with every request, which requires secure cookies
+  None,
+}
+
+impl SessionCookieConfig {
+  /// Reads the `SESSION_COOKIE_*` values, the cookies being secure by default if `secure_cookies`
+  /// is set.
+  pub fn from_values(values: &mut Values, secure_cookies: bool) -> Self {
+    let secure = values.parse_or::<bool>("SESSION_COOKIE_SECURE", secure_cookies);
+    let same_site = values.parse_or::<SameSite>("SESSION_COOKIE_SAME_SITE", SameSite::Lax);
+    if same_site == SameSite::None && !secure {
+      values.invalid("SESSION_COOKIE_SAME_SITE", "none requires secure cookies");
+    }
+    let domain = values.optional::<String>("SESSION_COOKIE_DOMAIN");
+
+    Self {
+      secure,
+      same_site,
+      domain,
+    }
+  }
+
+  /// The name of the cookie holding the session token.
+  pub fn session_name(&self) -> &'static str {
+    if self.host_only() {
+      "__Host-session"
+    } else {
+      "session"
+    }
+  }
+
+  /// The name of the cookie holding the CSRF token.
+  pub fn csrf_name(&self) -> &'static str {
+    if self.host_only() {
+      "__Host-csrf"
+    } else {
+      "csrf"
+    }
+  }
+
+  /// Whether the names carry the `__Host-` prefix, with which browsers reject cookies set by
+  /// other subdomains, e.g. to plant a known CSRF token.
+  fn host_only(&self) -> bool {
+    self.secure && self.domain.is_none()
+  }
+}
+
+impl FromStr for SameSite {
+  type Err = String;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "strict" => Ok(SameSite::Strict),
+      "lax" => Ok(SameSite::Lax),
+      "none" => Ok(SameSite::None),
+      _ => Err(format!(
+        "Invalid SameSite: {}. Please make sure it is one of \"strict\", \"lax\" or \"none\".",
+        s
+      )),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_cookie_names_follow_the_attributes() {
+    let mut values = Values::from_pairs([("SESSION_COOKIE_SAME_SITE", "Strict")]);
+    let secure = SessionCookieConfig::from_values(&mut values, true);
+    values.finish().unwrap();
+    assert_eq!(secure.same_site, SameSite::Strict);
+    assert_eq!(secure.session_name(), "__Host-session");
+    assert_eq!(secure.csrf_name(), "__Host-csrf");
+
+    let mut values = Values::from_pairs([("SESSION_COOKIE_DOMAIN", "example.com")]);
+    let shared = SessionCookieConfig::from_values(&mut values, true);
+    values.finish().unwrap();
+    assert_eq!(shared.session_name(), "session");
+
+    let mut values = Values::from_pairs([("SESSION_COOKIE_SAME_SITE", "none")]);
+    SessionCookieConfig::from_values(&mut values, false);
+    assert_eq!(
+      values.finish().unwrap_err().0,
+      ["SESSION_COOKIE_SAME_SITE: none requires secure cookies"]
+    );
+  }
+}
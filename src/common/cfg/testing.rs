@@ -0,0 +1,38 @@
+//! Fixtures for the tests of the modules that read the configuration.
+
+use super::{loader::Values, Configuration};
+
+/// The variables the configuration requires, in the test environment.
+pub fn pairs() -> Vec<(&'static str, &'static str)> {
+  vec![
+    ("APP_ENV", "test"),
+    ("PORT", "8080"),
+    (
+      "DATABASE_URL",
+      "postgres://postgres:password@db:5432/example",
+    ),
+  ]
+}
+
+/// The configuration of the test environment, with `overrides` set on top of [`pairs`].
+pub fn configuration(overrides: &[(&'static str, &'static str)]) -> Configuration {
+  let mut pairs = pairs();
+  pairs.extend_from_slice(overrides);
+  Configuration::from_values(Values::from_pairs(pairs)).unwrap()
+}
+
+/// Reads a section of the configuration from `pairs` with `read`, panicking if it is invalid.
+pub fn section<T>(pairs: &[(&str, &str)], read: impl FnOnce(&mut Values) -> T) -> T {
+  try_section(pairs, read).unwrap()
+}
+
+/// Reads a section of the configuration from `pairs` with `read`, returning the errors if it is
+/// invalid.
+pub fn try_section<T>(
+  pairs: &[(&str, &str)],
+  read: impl FnOnce(&mut Values) -> T,
+) -> Result<T, Vec<String>> {
+  let mut values = Values::from_pairs(pairs.iter().copied());
+  let section = read(&mut values);
+  values.finish().map(|()| section).map_err(|e| e.0)
+}
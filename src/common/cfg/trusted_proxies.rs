@@ -0,0 +1,131 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+  extract::{ConnectInfo, Request},
+  http::HeaderMap,
+};
+use ipnet::IpNet;
+use serde::Serialize;
+
+use super::{basic_auth, Values};
+
+/// The header in which proxies append the address of the client they forward a request for.
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The reverse proxies whose `X-Forwarded-For` header is trusted to tell the client address.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TrustedProxies {
+  #[serde(serialize_with = "basic_auth::serialize_networks")]
+  pub networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+  /// Reads `TRUSTED_PROXIES`, a comma separated list of addresses and networks.
+  pub fn from_values(values: &mut Values) -> Self {
+    let mut networks = Vec::new();
+    for network in values
+      .string("TRUSTED_PROXIES", "")
+      .split(',')
+      .map(str::trim)
+    {
+      match basic_auth::parse_network(network) {
+        Ok(Some(network)) => networks.push(network),
+        Ok(None) => {}
+        Err(message) => values.invalid("TRUSTED_PROXIES", message),
+      }
+    }
+    Self { networks }
+  }
+
+  /// The address of the client that sent `req`, if it can be told.
+  pub fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+    let peer = req
+      .extensions()
+      .get::<ConnectInfo<SocketAddr>>()
+      .map(|ConnectInfo(addr)| addr.ip());
+    self.resolve(peer, req.headers())
+  }
+
+  /// Walks the `X-Forwarded-For` addresses from the connected peer back, as long as they are
+  /// trusted proxies. The first untrusted address is the client, since entries further left may
+  /// be sent by the client itself.
+  fn resolve(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+    let mut client = peer?.to_canonical();
+    let forwarded: Vec<&str> = headers
+      .get_all(FORWARDED_FOR)
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .flat_map(|value| value.split(','))
+      .map(str::trim)
+      .collect();
+
+    for entry in forwarded.into_iter().rev() {
+      if !self.is_trusted(client) {
+        break;
+      }
+      client = parse_address(entry)?.to_canonical();
+    }
+    Some(client)
+  }
+
+  fn is_trusted(&self, ip: IpAddr) -> bool {
+    self.networks.iter().any(|network| network.contains(&ip))
+  }
+}
+
+/// Parses an address of `X-Forwarded-For`, which some proxies send with a port.
+fn parse_address(entry: &str) -> Option<IpAddr> {
+  entry
+    .parse::<IpAddr>()
+    .or_else(|_| entry.parse::<SocketAddr>().map(|addr| addr.ip()))
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn proxies(networks: &str) -> TrustedProxies {
+    let mut values = Values::from_pairs([("TRUSTED_PROXIES", networks)]);
+    let proxies = TrustedProxies::from_values(&mut values);
+    values.finish().unwrap();
+    proxies
+  }
+
+  fn forwarded_for(values: &[&str]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for value in values {
+      headers.append(FORWARDED_FOR, value.parse().unwrap());
+    }
+    headers
+  }
+
+  #[test]
+  fn test_forwarded_for_is_only_trusted_from_proxies() {
+    let proxies = proxies("10.0.0.0/8, 192.168.1.1");
+    let headers = forwarded_for(&["203.0.113.7, 10.0.0.2"]);
+    let ip = |addr: &str| addr.parse::<IpAddr>().ok();
+
+    assert_eq!(proxies.resolve(ip("10.0.0.1"), &headers), ip("203.0.113.7"));
+    // Untrusted peers cannot choose their address.
+    assert_eq!(
+      proxies.resolve(ip("198.51.100.1"), &headers),
+      ip("198.51.100.1")
+    );
+    // Entries prepended by the client are ignored.
+    let spoofed = forwarded_for(&["1.2.3.4", "203.0.113.7:4711", "192.168.1.1"]);
+    assert_eq!(proxies.resolve(ip("10.0.0.1"), &spoofed), ip("203.0.113.7"));
+    assert_eq!(proxies.resolve(None, &headers), None);
+    assert_eq!(
+      proxies.resolve(ip("10.0.0.1"), &forwarded_for(&["unknown"])),
+      None
+    );
+  }
+
+  #[test]
+  fn test_invalid_networks_are_reported() {
+    let mut values = Values::from_pairs([("TRUSTED_PROXIES", "10.0.0.0/33")]);
+    TrustedProxies::from_values(&mut values);
+    assert!(values.finish().is_err());
+  }
+}
@@ -0,0 +1,77 @@
+use tokio::sync::broadcast;
+
+use crate::common::transaction::Tx;
+
+/// Number of events kept for subscribers that fall behind; older events are dropped for them.
+const CAPACITY: usize = 256;
+
+/// Broadcasts the events of a module to its subscribers in this process, e.g. GraphQL
+/// subscriptions.
+///
+/// Publishing never blocks nor fails: events without subscribers are dropped.
+#[derive(Clone)]
+pub struct EventBus<E> {
+  sender: broadcast::Sender<E>,
+  tx: Option<Tx>,
+}
+
+impl<E: Clone + Send + 'static> EventBus<E> {
+  pub fn new() -> Self {
+    Self {
+      sender: broadcast::Sender::new(CAPACITY),
+      tx: None,
+    }
+  }
+
+  /// Returns a bus publishing once `tx` is committed, so that subscribers are not told about
+  /// changes that are rolled back.
+  pub fn in_transaction(&self, tx: &Tx) -> Self {
+    Self {
+      sender: self.sender.clone(),
+      tx: Some(tx.clone()),
+    }
+  }
+
+  pub fn publish(&self, event: E) {
+    match &self.tx {
+      Some(tx) => {
+        let sender = self.sender.clone();
+        tx.after_commit(move || {
+          let _ = sender.send(event);
+        });
+      }
+      None => {
+        let _ = self.sender.send(event);
+      }
+    }
+  }
+
+  pub fn subscribe(&self) -> broadcast::Receiver<E> {
+    self.sender.subscribe()
+  }
+}
+
+impl<E: Clone + Send + 'static> Default for EventBus<E> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_publish_to_subscribers() {
+    let bus = EventBus::new();
+    bus.publish("dropped");
+
+    let mut first = bus.subscribe();
+    let mut second = bus.clone().subscribe();
+    bus.publish("created");
+
+    assert_eq!(first.try_recv(), Ok("created"));
+    assert_eq!(second.try_recv(), Ok("created"));
+    assert!(first.try_recv().is_err());
+  }
+}
@@ -0,0 +1,314 @@
+use std::{collections::HashMap, fmt};
+
+use async_graphql::parser::{
+  self,
+  types::{
+    BaseType, FieldDefinition, InputValueDefinition, Type, TypeDefinition, TypeKind,
+    TypeSystemDefinition,
+  },
+  Positioned,
+};
+
+/// A change of the schema that can break the clients written against the previous one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BreakingChange {
+  /// A type was removed.
+  TypeRemoved(String),
+  /// A type changed kind, e.g. from an object to an interface.
+  KindChanged {
+    name: String,
+    from: &'static str,
+    to: &'static str,
+  },
+  /// A field of an object, interface or input object was removed.
+  FieldRemoved(String),
+  /// A field or argument changed type, or became stricter about null.
+  TypeChanged {
+    path: String,
+    from: String,
+    to: String,
+  },
+  /// An argument was removed.
+  ArgumentRemoved(String),
+  /// A required argument or input field was added.
+  RequiredAdded(String),
+  /// A value of an enum was removed.
+  EnumValueRemoved(String),
+  /// A member of a union was removed.
+  UnionMemberRemoved(String),
+}
+
+impl fmt::Display for BreakingChange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::TypeRemoved(name) => write!(f, "type `{name}` was removed"),
+      Self::KindChanged { name, from, to } => {
+        write!(f, "type `{name}` changed from {from} to {to}")
+      }
+      Self::FieldRemoved(path) => write!(f, "field `{path}` was removed"),
+      Self::TypeChanged { path, from, to } => {
+        write!(f, "`{path}` changed type from `{from}` to `{to}`")
+      }
+      Self::ArgumentRemoved(path) => write!(f, "argument `{path}` was removed"),
+      Self::RequiredAdded(path) => write!(f, "required `{path}` was added"),
+      Self::EnumValueRemoved(path) => write!(f, "enum value `{path}` was removed"),
+      Self::UnionMemberRemoved(path) => write!(f, "union member `{path}` was removed"),
+    }
+  }
+}
+
+/// Returns the changes from the `old` schema to the `new` one that can break existing clients.
+///
+/// Additions are safe, except for required arguments and input fields. Output fields may become
+/// non-null, since clients already handle the values, while arguments and input fields may only
+/// become nullable.
+pub fn breaking_changes(old: &str, new: &str) -> parser::Result<Vec<BreakingChange>> {
+  let old = types(old)?;
+  let new = types(new)?;
+
+  let mut changes = Vec::new();
+  let mut names: Vec<_> = old.keys().collect();
+  names.sort();
+  for name in names {
+    let Some(new_type) = new.get(name) else {
+      changes.push(BreakingChange::TypeRemoved(name.to_string()));
+      continue;
+    };
+    compare_types(name, &old[name].kind, &new_type.kind, &mut changes);
+  }
+  Ok(changes)
+}
+
+fn types(sdl: &str) -> parser::Result<HashMap<String, TypeDefinition>> {
+  Ok(
+    parser::parse_schema(sdl)?
+      .definitions
+      .into_iter()
+      .filter_map(|definition| match definition {
+        TypeSystemDefinition::Type(ty) => Some((ty.node.name.node.to_string(), ty.node)),
+        _ => None,
+      })
+      .collect(),
+  )
+}
+
+fn compare_types(name: &str, old: &TypeKind, new: &TypeKind, changes: &mut Vec<BreakingChange>) {
+  match (old, new) {
+    (TypeKind::Object(old), TypeKind::Object(new)) => {
+      compare_fields(name, &old.fields, &new.fields, changes)
+    }
+    (TypeKind::Interface(old), TypeKind::Interface(new)) => {
+      compare_fields(name, &old.fields, &new.fields, changes)
+    }
+    (TypeKind::InputObject(old), TypeKind::InputObject(new)) => compare_inputs(
+      name,
+      &old.fields,
+      &new.fields,
+      changes,
+      BreakingChange::FieldRemoved,
+    ),
+    (TypeKind::Enum(old), TypeKind::Enum(new)) => {
+      for value in &old.values {
+        let value = &value.node.value.node;
+        if !new.values.iter().any(|v| v.node.value.node == *value) {
+          changes.push(BreakingChange::EnumValueRemoved(format!("{name}.{value}")));
+        }
+      }
+    }
+    (TypeKind::Union(old), TypeKind::Union(new)) => {
+      for member in &old.members {
+        if !new.members.iter().any(|m| m.node == member.node) {
+          changes.push(BreakingChange::UnionMemberRemoved(format!(
+            "{name}.{}",
+            member.node
+          )));
+        }
+      }
+    }
+    (TypeKind::Scalar, TypeKind::Scalar) => {}
+    (old, new) => changes.push(BreakingChange::KindChanged {
+      name: name.to_string(),
+      from: kind(old),
+      to: kind(new),
+    }),
+  }
+}
+
+fn compare_fields(
+  type_name: &str,
+  old: &[Positioned<FieldDefinition>],
+  new: &[Positioned<FieldDefinition>],
+  changes: &mut Vec<BreakingChange>,
+) {
+  for field in old {
+    let field = &field.node;
+    let path = format!("{type_name}.{}", field.name.node);
+    let Some(new_field) = new.iter().find(|f| f.node.name.node == field.name.node) else {
+      changes.push(BreakingChange::FieldRemoved(path));
+      continue;
+    };
+    let new_field = &new_field.node;
+    if !output_compatible(&field.ty.node, &new_field.ty.node) {
+      changes.push(BreakingChange::TypeChanged {
+        path: path.clone(),
+        from: field.ty.node.to_string(),
+        to: new_field.ty.node.to_string(),
+      });
+    }
+    compare_inputs(
+      &path,
+      &field.arguments,
+      &new_field.arguments,
+      changes,
+      BreakingChange::ArgumentRemoved,
+    );
+  }
+}
+
+/// Compares the arguments of a field or the fields of an input object.
+fn compare_inputs(
+  parent: &str,
+  old: &[Positioned<InputValueDefinition>],
+  new: &[Positioned<InputValueDefinition>],
+  changes: &mut Vec<BreakingChange>,
+  removed: impl Fn(String) -> BreakingChange,
+) {
+  for input in old {
+    let input = &input.node;
+    let path = format!("{parent}.{}", input.name.node);
+    match new.iter().find(|i| i.node.name.node == input.name.node) {
+      None => changes.push(removed(path)),
+      Some(new_input) if !input_compatible(&input.ty.node, &new_input.node.ty.node) => changes
+        .push(BreakingChange::TypeChanged {
+          path,
+          from: input.ty.node.to_string(),
+          to: new_input.node.ty.node.to_string(),
+        }),
+      Some(_) => {}
+    }
+  }
+  for input in new {
+    let input = &input.node;
+    let added = !old.iter().any(|i| i.node.name.node == input.name.node);
+    if added && !input.ty.node.nullable && input.default_value.is_none() {
+      changes.push(BreakingChange::RequiredAdded(format!(
+        "{parent}.{}",
+        input.name.node
+      )));
+    }
+  }
+}
+
+/// Whether the values of the `new` type can be read as the `old` one.
+fn output_compatible(old: &Type, new: &Type) -> bool {
+  (old.nullable || !new.nullable)
+    && match (&old.base, &new.base) {
+      (BaseType::Named(old), BaseType::Named(new)) => old == new,
+      (BaseType::List(old), BaseType::List(new)) => output_compatible(old, new),
+      _ => false,
+    }
+}
+
+/// Whether the values of the `old` type are still accepted by the `new` one.
+fn input_compatible(old: &Type, new: &Type) -> bool {
+  (!old.nullable || new.nullable)
+    && match (&old.base, &new.base) {
+      (BaseType::Named(old), BaseType::Named(new)) => old == new,
+      (BaseType::List(old), BaseType::List(new)) => input_compatible(old, new),
+      _ => false,
+    }
+}
+
+fn kind(kind: &TypeKind) -> &'static str {
+  match kind {
+    TypeKind::Scalar => "a scalar",
+    TypeKind::Object(_) => "an object",
+    TypeKind::Interface(_) => "an interface",
+    TypeKind::Union(_) => "a union",
+    TypeKind::Enum(_) => "an enum",
+    TypeKind::InputObject(_) => "an input object",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SCHEMA: &str = r#"
+    enum Role { ADMIN USER }
+    input UsersFilter { email: String role: Role }
+    type User { id: ID! email: String! name: String roles: [Role!]! }
+    type Query { users(filter: UsersFilter, limit: Int): [User!]! }
+  "#;
+
+  fn changes(new: &str) -> Vec<String> {
+    breaking_changes(SCHEMA, new)
+      .unwrap()
+      .iter()
+      .map(ToString::to_string)
+      .collect()
+  }
+
+  #[test]
+  fn test_additions_are_not_breaking() {
+    let new = r#"
+      enum Role { ADMIN USER GUEST }
+      input UsersFilter { email: String role: Role name: String }
+      type User { id: ID! email: String! name: String! roles: [Role!]! createdAt: String }
+      type Query { users(filter: UsersFilter, limit: Int, offset: Int = 0): [User!]! me: User }
+    "#;
+    assert_eq!(changes(new), Vec::<String>::new());
+  }
+
+  #[test]
+  fn test_removals_are_breaking() {
+    let new = r#"
+      enum Role { ADMIN }
+      input UsersFilter { email: String }
+      type User { id: ID! email: String! name: String }
+      type Query { users(filter: UsersFilter): [User!]! }
+    "#;
+    assert_eq!(
+      changes(new),
+      [
+        "argument `Query.users.limit` was removed",
+        "enum value `Role.USER` was removed",
+        "field `User.roles` was removed",
+        "field `UsersFilter.role` was removed",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_type_changes_are_breaking() {
+    let new = r#"
+      enum Role { ADMIN USER }
+      input UsersFilter { email: String! role: Role }
+      type User { id: ID! email: String name: Int roles: [Role]! }
+      interface Query { users(filter: UsersFilter, limit: Int): [User!]! }
+      type Mutation { deleteUser(id: ID!): Boolean }
+    "#;
+    assert_eq!(
+      changes(new),
+      [
+        "type `Query` changed from an object to an interface",
+        "`User.email` changed type from `String!` to `String`",
+        "`User.name` changed type from `String` to `Int`",
+        "`User.roles` changed type from `[Role!]!` to `[Role]!`",
+        "`UsersFilter.email` changed type from `String` to `String!`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_required_arguments_are_breaking() {
+    let new = SCHEMA.replace("limit: Int)", "limit: Int, tenant: ID!)");
+    assert_eq!(changes(&new), ["required `Query.users.tenant` was added"]);
+
+    let removed = SCHEMA.replace("type User", "type Account");
+    assert_eq!(
+      breaking_changes(SCHEMA, &removed).unwrap(),
+      [BreakingChange::TypeRemoved("User".to_string())]
+    );
+  }
+}
@@ -0,0 +1,413 @@
+use std::sync::{Arc, Mutex};
+
+use async_graphql::{
+  async_trait,
+  extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+    NextValidation,
+  },
+  indexmap::IndexMap,
+  parser::types::{
+    DocumentOperations, ExecutableDocument, Field, OperationType, Selection, SelectionSet,
+  },
+  registry::{MetaTypeName, Registry},
+  ErrorExtensionValues, Name, Pos, Positioned, Request, ServerError, ServerResult,
+  ValidationResult, Value, Variables,
+};
+use tracing::info;
+
+use crate::common::cfg::Configuration;
+
+/// The argument of seaography connection fields holding the pagination.
+const PAGINATION: &str = "pagination";
+
+/// The pagination styles of seaography, each with a `limit`.
+const PAGINATION_STYLES: [&str; 3] = ["cursor", "page", "offset"];
+
+/// Limits of the GraphQL operations, so that a single operation cannot exhaust the database pool.
+///
+/// Over-limit operations are rejected before they are executed, with an error whose `code`
+/// extension tells which limit was exceeded.
+#[derive(Clone, Debug)]
+pub struct Limits {
+  pub depth: Option<usize>,
+  pub complexity: Option<usize>,
+  pub max_page_size: Option<u64>,
+  pub introspection: bool,
+}
+
+impl Limits {
+  pub fn from_config(cfg: &Configuration) -> Self {
+    Self {
+      depth: Some(cfg.graphql_depth_limit).filter(|limit| *limit > 0),
+      complexity: Some(cfg.graphql_complexity_limit).filter(|limit| *limit > 0),
+      max_page_size: Some(cfg.graphql_max_page_size),
+      introspection: cfg.graphql_introspection,
+    }
+  }
+
+  /// No limits, e.g. to export the schema.
+  pub fn none() -> Self {
+    Self {
+      depth: None,
+      complexity: None,
+      max_page_size: None,
+      introspection: true,
+    }
+  }
+}
+
+impl ExtensionFactory for Limits {
+  fn create(&self) -> Arc<dyn Extension> {
+    Arc::new(LimitsExtension {
+      limits: self.clone(),
+      operation: Mutex::new(None),
+    })
+  }
+}
+
+struct LimitsExtension {
+  limits: Limits,
+  operation: Mutex<Option<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for LimitsExtension {
+  async fn prepare_request(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    request: Request,
+    next: NextPrepareRequest<'_>,
+  ) -> ServerResult<Request> {
+    *self.operation.lock().unwrap_or_else(|e| e.into_inner()) = request.operation_name.clone();
+    next.run(ctx, request).await
+  }
+
+  async fn parse_query(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    query: &str,
+    variables: &Variables,
+    next: NextParseQuery<'_>,
+  ) -> ServerResult<ExecutableDocument> {
+    let mut document = next.run(ctx, query, variables).await?;
+    if let Some(max_page_size) = self.limits.max_page_size {
+      Paginator {
+        registry: &ctx.schema_env.registry,
+        variables,
+        max_page_size,
+      }
+      .paginate(&mut document)?;
+    }
+    Ok(document)
+  }
+
+  async fn validation(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    next: NextValidation<'_>,
+  ) -> Result<ValidationResult, Vec<ServerError>> {
+    let result = next.run(ctx).await?;
+    let operation = self
+      .operation
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .clone();
+    info!(
+      operation = operation.as_deref().unwrap_or("anonymous"),
+      complexity = result.complexity,
+      depth = result.depth,
+      "GraphQL operation analyzed"
+    );
+
+    if let Some(limit) = self.limits.depth.filter(|limit| result.depth > *limit) {
+      return Err(vec![limit_error(
+        "DEPTH_LIMIT_EXCEEDED",
+        format!(
+          "Query is nested too deep: depth {} exceeds the limit of {limit}",
+          result.depth
+        ),
+        result.depth as u64,
+        limit as u64,
+        None,
+      )]);
+    }
+    if let Some(limit) = self
+      .limits
+      .complexity
+      .filter(|limit| result.complexity > *limit)
+    {
+      return Err(vec![limit_error(
+        "COMPLEXITY_LIMIT_EXCEEDED",
+        format!(
+          "Query is too complex: complexity {} exceeds the limit of {limit}",
+          result.complexity
+        ),
+        result.complexity as u64,
+        limit as u64,
+        None,
+      )]);
+    }
+    Ok(result)
+  }
+}
+
+/// Caps the page size of the connection fields of a document.
+struct Paginator<'a> {
+  registry: &'a Registry,
+  variables: &'a Variables,
+  max_page_size: u64,
+}
+
+impl Paginator<'_> {
+  fn paginate(&self, document: &mut ExecutableDocument) -> ServerResult<()> {
+    let operations: Vec<_> = match &mut document.operations {
+      DocumentOperations::Single(operation) => vec![operation],
+      DocumentOperations::Multiple(operations) => operations.values_mut().collect(),
+    };
+    for operation in operations {
+      let root = match operation.node.ty {
+        OperationType::Query => Some(self.registry.query_type.as_str()),
+        OperationType::Mutation => self.registry.mutation_type.as_deref(),
+        OperationType::Subscription => self.registry.subscription_type.as_deref(),
+      };
+      if let Some(root) = root {
+        self.visit(&mut operation.node.selection_set.node, root)?;
+      }
+    }
+    for fragment in document.fragments.values_mut() {
+      let type_name = fragment.node.type_condition.node.on.node.to_string();
+      self.visit(&mut fragment.node.selection_set.node, &type_name)?;
+    }
+    Ok(())
+  }
+
+  fn visit(&self, selection_set: &mut SelectionSet, type_name: &str) -> ServerResult<()> {
+    for selection in &mut selection_set.items {
+      match &mut selection.node {
+        Selection::Field(field) => {
+          let Some(meta_field) = self
+            .registry
+            .types
+            .get(type_name)
+            .and_then(|ty| ty.field_by_name(&field.node.name.node))
+          else {
+            continue;
+          };
+          if meta_field.args.contains_key(PAGINATION) {
+            self.cap(&mut field.node)?;
+          }
+          let field_type = MetaTypeName::concrete_typename(&meta_field.ty).to_string();
+          self.visit(&mut field.node.selection_set.node, &field_type)?;
+        }
+        Selection::InlineFragment(fragment) => {
+          let type_name = fragment
+            .node
+            .type_condition
+            .as_ref()
+            .map_or(type_name.to_string(), |condition| {
+              condition.node.on.node.to_string()
+            });
+          self.visit(&mut fragment.node.selection_set.node, &type_name)?;
+        }
+        // Fragment definitions are visited on their own.
+        Selection::FragmentSpread(_) => {}
+      }
+    }
+    Ok(())
+  }
+
+  /// Rejects a page larger than the maximum, and paginates fields requested without a limit.
+  fn cap(&self, field: &mut Field) -> ServerResult<()> {
+    let argument = field
+      .arguments
+      .iter_mut()
+      .find(|(name, _)| name.node == PAGINATION);
+
+    if let Some((_, value)) = &argument {
+      let pagination = value
+        .node
+        .clone()
+        .into_const_with(|name| self.variables.get(&name).cloned().ok_or(()))
+        .ok();
+      let limits: Vec<u64> = PAGINATION_STYLES
+        .iter()
+        .filter_map(|style| match &pagination {
+          Some(Value::Object(pagination)) => pagination.get(*style),
+          _ => None,
+        })
+        .filter_map(|style| match style {
+          Value::Object(style) => style.get("limit"),
+          _ => None,
+        })
+        .filter_map(|limit| match limit {
+          Value::Number(limit) => limit.as_u64(),
+          _ => None,
+        })
+        .collect();
+
+      if let Some(size) = limits.iter().find(|size| **size > self.max_page_size) {
+        return Err(limit_error(
+          "PAGE_SIZE_LIMIT_EXCEEDED",
+          format!(
+            "Page size {size} of \"{}\" exceeds the limit of {}",
+            field.name.node, self.max_page_size
+          ),
+          *size,
+          self.max_page_size,
+          Some(value.pos),
+        ));
+      }
+      if !limits.is_empty() {
+        return Ok(());
+      }
+    }
+
+    let default = Value::Object(IndexMap::from([(
+      Name::new("offset"),
+      Value::Object(IndexMap::from([
+        (Name::new("limit"), Value::from(self.max_page_size)),
+        (Name::new("offset"), Value::from(0)),
+      ])),
+    )]));
+    match argument {
+      Some((_, value)) => value.node = default.into_value(),
+      None => field.arguments.push((
+        Positioned::new(Name::new(PAGINATION), field.name.pos),
+        Positioned::new(default.into_value(), field.name.pos),
+      )),
+    }
+    Ok(())
+  }
+}
+
+fn limit_error(
+  code: &str,
+  message: String,
+  actual: u64,
+  limit: u64,
+  pos: Option<Pos>,
+) -> ServerError {
+  let mut error = ServerError::new(message, pos);
+  let mut extensions = ErrorExtensionValues::default();
+  extensions.set("code", code);
+  extensions.set("actual", actual);
+  extensions.set("limit", limit);
+  error.extensions = Some(extensions);
+  error
+}
+
+#[cfg(test)]
+mod tests {
+  use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputObject, InputValue, Object, Schema, TypeRef,
+  };
+
+  use super::*;
+  use crate::common::graphql::testing::error_code;
+
+  /// A schema with a paginated `items` field returning the pagination it received.
+  fn schema(limits: Limits) -> Schema {
+    let style = |name: &str, field: &str| {
+      InputObject::new(name)
+        .field(InputValue::new("limit", TypeRef::named_nn(TypeRef::INT)))
+        .field(InputValue::new(field, TypeRef::named_nn(TypeRef::INT)))
+    };
+    let pagination = InputObject::new("PaginationInput")
+      .field(InputValue::new("page", TypeRef::named("PageInput")))
+      .field(InputValue::new("offset", TypeRef::named("OffsetInput")));
+    let item = Object::new("Item").field(Field::new("child", TypeRef::named("Item"), |_| {
+      FieldFuture::new(async { Ok(Some(FieldValue::owned_any(()))) })
+    }));
+    let query = Object::new("Query")
+      .field(
+        Field::new("items", TypeRef::named_nn(TypeRef::STRING), |ctx| {
+          FieldFuture::new(async move {
+            let pagination = ctx.args.try_get(PAGINATION)?.as_value().to_string();
+            Ok(Some(Value::from(pagination)))
+          })
+        })
+        .argument(InputValue::new(
+          PAGINATION,
+          TypeRef::named("PaginationInput"),
+        )),
+      )
+      .field(Field::new("item", TypeRef::named("Item"), |_| {
+        FieldFuture::new(async { Ok(Some(FieldValue::owned_any(()))) })
+      }));
+
+    Schema::build("Query", None, None)
+      .register(style("PageInput", "page"))
+      .register(style("OffsetInput", "offset"))
+      .register(pagination)
+      .register(item)
+      .register(query)
+      .extension(limits)
+      .finish()
+      .unwrap()
+  }
+
+  fn limits() -> Limits {
+    Limits {
+      depth: Some(3),
+      complexity: Some(4),
+      max_page_size: Some(100),
+      introspection: true,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_page_size_is_capped() {
+    let schema = schema(limits());
+
+    let response = schema.execute("{ items }").await;
+    assert_eq!(
+      response.data.into_json().unwrap()["items"],
+      "{offset: {limit: 100, offset: 0}}"
+    );
+
+    let response = schema
+      .execute("{ items(pagination: { page: { limit: 5, page: 2 } }) }")
+      .await;
+    assert_eq!(
+      response.data.into_json().unwrap()["items"],
+      "{page: {limit: 5, page: 2}}"
+    );
+
+    let request = async_graphql::Request::new(
+      "query Items($pagination: PaginationInput) { items(pagination: $pagination) }",
+    )
+    .variables(Variables::from_json(serde_json::json!({
+      "pagination": { "offset": { "limit": 500, "offset": 0 } }
+    })));
+    let response = schema.execute(request).await;
+    assert_eq!(
+      error_code(&response),
+      Some(Value::from("PAGE_SIZE_LIMIT_EXCEEDED"))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_depth_and_complexity_limits() {
+    let schema = schema(limits());
+
+    let response = schema
+      .execute("{ item { child { child { child { __typename } } } } }")
+      .await;
+    assert_eq!(
+      error_code(&response),
+      Some(Value::from("DEPTH_LIMIT_EXCEEDED"))
+    );
+
+    let response = schema
+      .execute("{ a: items b: items c: items d: items e: items }")
+      .await;
+    assert_eq!(
+      error_code(&response),
+      Some(Value::from("COMPLEXITY_LIMIT_EXCEEDED"))
+    );
+
+    let response = schema.execute("{ item { child { __typename } } }").await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+  }
+}
@@ -0,0 +1,6 @@
+pub mod breaking;
+pub mod limits;
+pub mod persisted;
+pub mod telemetry;
+#[cfg(test)]
+pub mod testing;
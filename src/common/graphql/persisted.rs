@@ -0,0 +1,519 @@
+use std::{collections::HashMap, num::NonZeroUsize, path::Path, sync::Arc, sync::Mutex};
+
+use async_graphql::{
+  async_trait,
+  extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest, NextValidation},
+  parser::types::DocumentOperations,
+  ErrorExtensionValues, Request, ServerError, ServerResult, ValidationResult, Value,
+};
+use lru::LruCache;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Statement};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::common::cfg::{Configuration, PersistedQueryCache};
+use crate::database::conn::Conn;
+
+/// The request extension of the Automatic Persisted Queries protocol.
+const PERSISTED_QUERY: &str = "persistedQuery";
+
+/// The only version of the protocol.
+const VERSION: i64 = 1;
+
+/// Stores the queries registered with Automatic Persisted Queries, by the hex SHA-256 of their text.
+#[async_trait::async_trait]
+pub trait QueryStore: Send + Sync {
+  async fn get(&self, hash: &str) -> Result<Option<String>, DbErr>;
+  async fn insert(&self, hash: &str, query: &str) -> Result<(), DbErr>;
+}
+
+/// Keeps the most recently used queries in memory.
+pub struct MemoryQueryStore {
+  queries: Mutex<LruCache<String, String>>,
+}
+
+impl MemoryQueryStore {
+  pub fn new(capacity: NonZeroUsize) -> Self {
+    Self {
+      queries: Mutex::new(LruCache::new(capacity)),
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl QueryStore for MemoryQueryStore {
+  async fn get(&self, hash: &str) -> Result<Option<String>, DbErr> {
+    let mut queries = self.queries.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(queries.get(hash).cloned())
+  }
+
+  async fn insert(&self, hash: &str, query: &str) -> Result<(), DbErr> {
+    let mut queries = self.queries.lock().unwrap_or_else(|e| e.into_inner());
+    queries.put(hash.to_string(), query.to_string());
+    Ok(())
+  }
+}
+
+/// Keeps the queries in the `graphql_persisted_queries` table, shared by every instance, in front
+/// of which the most recently used ones are kept in memory.
+///
+/// The table holds at most `max_rows` queries, past which new ones are only kept in memory.
+pub struct PostgresQueryStore {
+  db: Conn,
+  cache: MemoryQueryStore,
+  max_rows: u64,
+}
+
+impl PostgresQueryStore {
+  pub fn new(db: DatabaseConnection, capacity: NonZeroUsize, max_rows: u64) -> Self {
+    Self {
+      db: db.into(),
+      cache: MemoryQueryStore::new(capacity),
+      max_rows,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl QueryStore for PostgresQueryStore {
+  async fn get(&self, hash: &str) -> Result<Option<String>, DbErr> {
+    if let Some(query) = self.cache.get(hash).await? {
+      return Ok(Some(query));
+    }
+    let row = self
+      .db
+      .query_one(Statement::from_sql_and_values(
+        self.db.get_database_backend(),
+        "SELECT query FROM graphql_persisted_queries WHERE hash = $1",
+        [hash.into()],
+      ))
+      .await?;
+    let Some(row) = row else {
+      return Ok(None);
+    };
+    let query: String = row.try_get("", "query")?;
+    self.cache.insert(hash, &query).await?;
+    Ok(Some(query))
+  }
+
+  async fn insert(&self, hash: &str, query: &str) -> Result<(), DbErr> {
+    self
+      .db
+      .execute(Statement::from_sql_and_values(
+        self.db.get_database_backend(),
+        "INSERT INTO graphql_persisted_queries (hash, query) \
+         SELECT $1, $2 WHERE (SELECT count(*) FROM graphql_persisted_queries) < $3 \
+         ON CONFLICT (hash) DO NOTHING",
+        [
+          hash.into(),
+          query.into(),
+          i64::try_from(self.max_rows).unwrap_or(i64::MAX).into(),
+        ],
+      ))
+      .await?;
+    self.cache.insert(hash, query).await
+  }
+}
+
+/// The operations registered ahead of time, read from a JSON file mapping the hex SHA-256 of each
+/// query to its text, e.g. `{ "7f56e67d...": "{ __typename }" }`.
+#[derive(Debug, Default)]
+pub struct Allowlist {
+  queries: HashMap<String, String>,
+}
+
+impl Allowlist {
+  pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+      .map_err(|e| anyhow::anyhow!("Unable to read {}: {e}", path.display()))?;
+    let queries: HashMap<String, String> = serde_json::from_str(&content)
+      .map_err(|e| anyhow::anyhow!("Invalid allow-list {}: {e}", path.display()))?;
+    Self::new(queries)
+  }
+
+  /// Builds the allow-list, rejecting entries whose hash is not the one of their query.
+  pub fn new(queries: HashMap<String, String>) -> anyhow::Result<Self> {
+    let queries = queries
+      .into_iter()
+      .map(|(hash, query)| {
+        let hash = hash.to_ascii_lowercase();
+        if hash != sha256(&query) {
+          anyhow::bail!("The hash {hash} of the allow-list does not match its query");
+        }
+        Ok((hash, query))
+      })
+      .collect::<anyhow::Result<_>>()?;
+    Ok(Self { queries })
+  }
+
+  pub fn get(&self, hash: &str) -> Option<&str> {
+    self.queries.get(hash).map(String::as_str)
+  }
+
+  /// The names of the operations of the allowed queries.
+  pub fn operation_names(&self) -> impl Iterator<Item = String> + '_ {
+    self
+      .queries
+      .values()
+      .filter_map(|query| async_graphql::parser::parse_query(query).ok())
+      .flat_map(|document| match document.operations {
+        DocumentOperations::Single(_) => Vec::new(),
+        DocumentOperations::Multiple(operations) => operations
+          .into_keys()
+          .map(|name| name.to_string())
+          .collect(),
+      })
+  }
+}
+
+/// Serves Automatic Persisted Queries and restricts the operations to an allow-list.
+///
+/// A client may send the SHA-256 of a query in the `persistedQuery` request extension instead of
+/// its text. Unknown hashes are answered with `PersistedQueryNotFound`, after which the client
+/// sends the text with the hash to register it.
+///
+/// In strict mode, only the operations of the allow-list are executed, whether sent by hash or
+/// as text, and no query is registered. Otherwise the allow-list only seeds the known queries, and
+/// operations missing from it are logged.
+///
+/// Queries are only registered once validated, and if they are at most `max_query_length` bytes
+/// long, 0 disabling the limit.
+#[derive(Clone, Default)]
+pub struct PersistedQueries {
+  pub store: Option<Arc<dyn QueryStore>>,
+  pub allowlist: Option<Arc<Allowlist>>,
+  pub strict: bool,
+  pub max_query_length: usize,
+}
+
+impl PersistedQueries {
+  pub fn from_config(cfg: &Configuration, db: DatabaseConnection) -> Self {
+    let capacity =
+      NonZeroUsize::new(cfg.graphql_persisted_queries_capacity).unwrap_or(NonZeroUsize::MIN);
+    let store: Option<Arc<dyn QueryStore>> = match cfg.graphql_persisted_queries {
+      PersistedQueryCache::Disabled => None,
+      PersistedQueryCache::Memory => Some(Arc::new(MemoryQueryStore::new(capacity))),
+      PersistedQueryCache::Postgres => Some(Arc::new(PostgresQueryStore::new(
+        db,
+        capacity,
+        cfg.graphql_persisted_queries_max_rows,
+      ))),
+    };
+    Self {
+      store,
+      allowlist: cfg.graphql_allowlist.clone(),
+      strict: cfg.graphql_allowlist_strict,
+      max_query_length: cfg.graphql_persisted_query_max_length,
+    }
+  }
+
+  /// Fills in the query of a request sent by hash, and rejects the operations not allowed.
+  ///
+  /// Also returns the hash of the query to register once it is validated, if any.
+  async fn resolve(&self, mut request: Request) -> ServerResult<(Request, Option<String>)> {
+    let hash = persisted_hash(&request)?;
+
+    if request.query.trim().is_empty() {
+      let Some(hash) = hash else {
+        return Ok((request, None));
+      };
+      request.query = self.lookup(&hash).await.ok_or_else(|| {
+        error(
+          "PERSISTED_QUERY_NOT_FOUND",
+          "PersistedQueryNotFound".to_string(),
+        )
+      })?;
+      return Ok((request, None));
+    }
+
+    let actual = sha256(&request.query);
+    if hash.as_ref().is_some_and(|hash| *hash != actual) {
+      return Err(error(
+        "PERSISTED_QUERY_HASH_MISMATCH",
+        "The provided sha256Hash does not match the query".to_string(),
+      ));
+    }
+
+    let allowed = self
+      .allowlist
+      .as_ref()
+      .map(|list| list.get(&actual).is_some());
+    match allowed {
+      Some(false) if self.strict => {
+        return Err(error(
+          "OPERATION_NOT_ALLOWED",
+          "The operation is not in the allow-list".to_string(),
+        ));
+      }
+      Some(false) => warn!(
+        operation = request.operation_name.as_deref().unwrap_or("anonymous"),
+        hash = actual,
+        "GraphQL operation is not in the allow-list"
+      ),
+      _ => {}
+    }
+
+    // Queries sent with their hash are registered, unless they are already allowed.
+    let register = hash.is_some()
+      && self.store.is_some()
+      && !self.strict
+      && allowed != Some(true)
+      && (self.max_query_length == 0 || request.query.len() <= self.max_query_length);
+    Ok((request, register.then_some(actual)))
+  }
+
+  /// Registers a validated query.
+  async fn register(&self, hash: &str, query: &str) {
+    let Some(store) = &self.store else {
+      return;
+    };
+    if let Err(e) = store.insert(hash, query).await {
+      warn!(error = %e, "Failed to persist a GraphQL query");
+    }
+  }
+
+  /// Returns the query a request will execute, looking up the ones sent by hash, e.g. to tell
+  /// queries from mutations before executing them. Nothing is registered.
+  pub async fn query(&self, request: &Request) -> Option<String> {
+    if !request.query.trim().is_empty() {
+      return Some(request.query.clone());
+    }
+    let hash = persisted_hash(request).ok()??;
+    self.lookup(&hash).await
+  }
+
+  /// Finds a query by hash, only in the allow-list in strict mode.
+  async fn lookup(&self, hash: &str) -> Option<String> {
+    if let Some(query) = self.allowlist.as_ref().and_then(|list| list.get(hash)) {
+      return Some(query.to_string());
+    }
+    if self.strict {
+      return None;
+    }
+    match self.store.as_ref()?.get(hash).await {
+      Ok(query) => query,
+      Err(e) => {
+        warn!(error = %e, "Failed to look up a persisted GraphQL query");
+        None
+      }
+    }
+  }
+}
+
+impl ExtensionFactory for PersistedQueries {
+  fn create(&self) -> Arc<dyn Extension> {
+    Arc::new(PersistedQueriesExtension {
+      persisted: self.clone(),
+      registration: Mutex::default(),
+    })
+  }
+}
+
+struct PersistedQueriesExtension {
+  persisted: PersistedQueries,
+  /// The hash and text of the query to register once it is validated.
+  registration: Mutex<Option<(String, String)>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+  async fn prepare_request(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    request: Request,
+    next: NextPrepareRequest<'_>,
+  ) -> ServerResult<Request> {
+    let (request, register) = self.persisted.resolve(request).await?;
+    if let Some(hash) = register {
+      *self.registration.lock().unwrap_or_else(|e| e.into_inner()) =
+        Some((hash, request.query.clone()));
+    }
+    next.run(ctx, request).await
+  }
+
+  async fn validation(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    next: NextValidation<'_>,
+  ) -> Result<ValidationResult, Vec<ServerError>> {
+    let result = next.run(ctx).await?;
+    let registration = self
+      .registration
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .take();
+    if let Some((hash, query)) = registration {
+      self.persisted.register(&hash, &query).await;
+    }
+    Ok(result)
+  }
+}
+
+/// The hex SHA-256 of a query, as sent by the clients.
+pub fn sha256(query: &str) -> String {
+  format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// Reads the hash of the `persistedQuery` extension, if any.
+fn persisted_hash(request: &Request) -> ServerResult<Option<String>> {
+  let Some(extension) = request.extensions.get(PERSISTED_QUERY) else {
+    return Ok(None);
+  };
+  let Value::Object(extension) = extension else {
+    return Err(invalid_extension());
+  };
+  match extension.get("version") {
+    Some(Value::Number(version)) if version.as_i64() == Some(VERSION) => {}
+    _ => {
+      return Err(error(
+        "PERSISTED_QUERY_NOT_SUPPORTED",
+        "Unsupported persisted query version".to_string(),
+      ))
+    }
+  }
+  match extension.get("sha256Hash") {
+    Some(Value::String(hash)) => Ok(Some(hash.to_ascii_lowercase())),
+    _ => Err(invalid_extension()),
+  }
+}
+
+fn invalid_extension() -> ServerError {
+  error(
+    "BAD_REQUEST",
+    "The persistedQuery extension requires a version and a sha256Hash".to_string(),
+  )
+}
+
+fn error(code: &str, message: String) -> ServerError {
+  let mut error = ServerError::new(message, None);
+  let mut extensions = ErrorExtensionValues::default();
+  extensions.set("code", code);
+  error.extensions = Some(extensions);
+  error
+}
+
+#[cfg(test)]
+mod tests {
+  use async_graphql::dynamic::{Field, FieldFuture, Object, Schema, TypeRef};
+  use serde_json::json;
+
+  use super::*;
+  use crate::common::graphql::testing::error_code;
+
+  const QUERY: &str = "{ value }";
+
+  fn schema(persisted: PersistedQueries) -> Schema {
+    let query =
+      Object::new("Query").field(Field::new("value", TypeRef::named_nn(TypeRef::INT), |_| {
+        FieldFuture::new(async { Ok(Some(Value::from(1))) })
+      }));
+    Schema::build("Query", None, None)
+      .register(query)
+      .extension(persisted)
+      .finish()
+      .unwrap()
+  }
+
+  fn request(query: &str, hash: &str) -> Request {
+    let mut request = Request::new(query);
+    request.extensions.insert(
+      PERSISTED_QUERY.to_string(),
+      Value::from_json(json!({ "version": 1, "sha256Hash": hash })).unwrap(),
+    );
+    request
+  }
+
+  fn memory() -> Option<Arc<dyn QueryStore>> {
+    Some(Arc::new(MemoryQueryStore::new(
+      NonZeroUsize::new(10).unwrap(),
+    )))
+  }
+
+  #[tokio::test]
+  async fn test_automatic_persisted_queries() {
+    let schema = schema(PersistedQueries {
+      store: memory(),
+      ..Default::default()
+    });
+    let hash = sha256(QUERY);
+
+    let missing = schema.execute(request("", &hash)).await;
+    assert_eq!(missing.errors[0].message, "PersistedQueryNotFound");
+
+    let mismatch = schema.execute(request(QUERY, &sha256("{ other }"))).await;
+    assert_eq!(
+      error_code(&mismatch),
+      Some(Value::from("PERSISTED_QUERY_HASH_MISMATCH"))
+    );
+
+    let registered = schema.execute(request(QUERY, &hash)).await;
+    assert!(registered.is_ok(), "{:?}", registered.errors);
+
+    let persisted = schema.execute(request("", &hash)).await;
+    assert_eq!(
+      persisted.data,
+      Value::from_json(json!({ "value": 1 })).unwrap()
+    );
+  }
+
+  #[tokio::test]
+  async fn test_only_valid_queries_are_registered() {
+    let schema = schema(PersistedQueries {
+      store: memory(),
+      max_query_length: 20,
+      ..Default::default()
+    });
+
+    // Unknown fields, syntax errors and queries longer than the limit.
+    for query in ["{ unknown }", "{ value", "{ value, __typename }"] {
+      schema.execute(request(query, &sha256(query))).await;
+      let missing = schema.execute(request("", &sha256(query))).await;
+      assert_eq!(missing.errors[0].message, "PersistedQueryNotFound");
+    }
+  }
+
+  #[tokio::test]
+  async fn test_strict_allowlist() {
+    let allowlist = Allowlist::new(HashMap::from([(sha256(QUERY), QUERY.to_string())])).unwrap();
+    let schema = schema(PersistedQueries {
+      store: memory(),
+      allowlist: Some(Arc::new(allowlist)),
+      strict: true,
+      ..Default::default()
+    });
+
+    let by_hash = schema.execute(request("", &sha256(QUERY))).await;
+    assert!(by_hash.is_ok(), "{:?}", by_hash.errors);
+    assert!(schema.execute(QUERY).await.is_ok());
+
+    let other = "{ __typename }";
+    let rejected = schema.execute(other).await;
+    assert_eq!(
+      error_code(&rejected),
+      Some(Value::from("OPERATION_NOT_ALLOWED"))
+    );
+
+    // Unlisted queries cannot be registered either.
+    schema.execute(request(other, &sha256(other))).await;
+    let missing = schema.execute(request("", &sha256(other))).await;
+    assert_eq!(missing.errors[0].message, "PersistedQueryNotFound");
+  }
+
+  #[test]
+  fn test_allowlist_rejects_mismatched_hashes() {
+    let queries = HashMap::from([(sha256("{ other }"), QUERY.to_string())]);
+    assert!(Allowlist::new(queries).is_err());
+  }
+
+  #[test]
+  fn test_allowlist_operation_names() {
+    let named = "query Users { __typename }";
+    let allowlist = Allowlist::new(HashMap::from([
+      (sha256(QUERY), QUERY.to_string()),
+      (sha256(named), named.to_string()),
+    ]))
+    .unwrap();
+    assert_eq!(allowlist.operation_names().collect::<Vec<_>>(), ["Users"]);
+  }
+}
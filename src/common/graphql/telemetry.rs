@@ -0,0 +1,299 @@
+use std::{
+  any::TypeId,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+
+use async_graphql::{
+  async_trait,
+  extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest, NextRequest,
+    NextSubscribe,
+  },
+  futures_util::{stream::BoxStream, StreamExt},
+  parser::types::{DocumentOperations, ExecutableDocument, OperationType},
+  Request, Response, ServerError, ServerResult, Variables,
+};
+use sea_orm::DbErr;
+use tracing::{error, field::Empty, info, info_span, warn, Instrument, Span};
+
+use crate::common::{api_error::ApiError, telemetry};
+
+/// The `x-request-id` of the HTTP request or WebSocket connection an operation came from.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Traces the GraphQL operations and masks the internal errors of their responses.
+///
+/// Each operation gets a `graphql` span recording its name, type, duration and number of errors,
+/// and is logged once complete.
+///
+/// Errors get a `code` extension and the `requestId` of the request. Like the REST errors, database
+/// and internal errors are logged and replaced with a generic message, the underlying error being
+/// returned in a `details` extension only where the environment enables error details.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Telemetry {
+  /// Whether the environment profile enables error details
+  pub error_details: bool,
+}
+
+impl ExtensionFactory for Telemetry {
+  fn create(&self) -> Arc<dyn Extension> {
+    Arc::new(TelemetryExtension {
+      error_details: self.error_details,
+      ..Default::default()
+    })
+  }
+}
+
+#[derive(Default)]
+struct TelemetryExtension {
+  error_details: bool,
+  operation: Mutex<Option<String>>,
+  request_id: Mutex<Option<String>>,
+}
+
+impl TelemetryExtension {
+  fn request_id(&self, ctx: &ExtensionContext<'_>) -> Option<String> {
+    let request_id = self
+      .request_id
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .clone();
+    request_id.or_else(|| ctx.data_opt::<RequestId>().map(|id| id.0.clone()))
+  }
+}
+
+#[async_trait::async_trait]
+impl Extension for TelemetryExtension {
+  async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+    let span = info_span!(
+      "graphql",
+      graphql.operation.name = Empty,
+      graphql.operation.type = Empty,
+      graphql.errors = Empty,
+      duration_ms = Empty,
+    );
+    let start = Instant::now();
+    let response = next.run(ctx).instrument(span.clone()).await;
+    let duration_ms = start.elapsed().as_millis() as u64;
+
+    let request_id = self.request_id(ctx);
+    let response = mask(response, request_id.as_deref(), self.error_details);
+
+    span.record("graphql.errors", response.errors.len());
+    span.record("duration_ms", duration_ms);
+    let _entered = span.enter();
+    if response.errors.is_empty() {
+      info!(duration_ms, "GraphQL operation completed");
+    } else {
+      warn!(
+        duration_ms,
+        errors = response.errors.len(),
+        "GraphQL operation completed with errors"
+      );
+    }
+    response
+  }
+
+  fn subscribe<'s>(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    stream: BoxStream<'s, Response>,
+    next: NextSubscribe<'_>,
+  ) -> BoxStream<'s, Response> {
+    let request_id = self.request_id(ctx);
+    let details = self.error_details;
+    next
+      .run(ctx, stream)
+      .map(move |response| mask(response, request_id.as_deref(), details))
+      .boxed()
+  }
+
+  async fn prepare_request(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    request: Request,
+    next: NextPrepareRequest<'_>,
+  ) -> ServerResult<Request> {
+    *self.operation.lock().unwrap_or_else(|e| e.into_inner()) = request.operation_name.clone();
+    *self.request_id.lock().unwrap_or_else(|e| e.into_inner()) = request
+      .data
+      .get(&TypeId::of::<RequestId>())
+      .and_then(|data| data.downcast_ref::<RequestId>())
+      .map(|id| id.0.clone());
+    next.run(ctx, request).await
+  }
+
+  async fn parse_query(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    query: &str,
+    variables: &Variables,
+    next: NextParseQuery<'_>,
+  ) -> ServerResult<ExecutableDocument> {
+    let document = next.run(ctx, query, variables).await?;
+    let name = self
+      .operation
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .clone();
+    let span = Span::current();
+    span.record(
+      "graphql.operation.name",
+      name.as_deref().unwrap_or("anonymous"),
+    );
+    if let Some(ty) = operation_type(&document, name.as_deref()) {
+      span.record("graphql.operation.type", ty.to_string());
+    }
+    Ok(document)
+  }
+}
+
+/// The type of the operation named `name` of a document, or of its only operation.
+pub fn operation_type(document: &ExecutableDocument, name: Option<&str>) -> Option<OperationType> {
+  let operation = match (&document.operations, name) {
+    (DocumentOperations::Single(operation), _) => Some(operation),
+    (DocumentOperations::Multiple(operations), Some(name)) => operations.get(name),
+    (DocumentOperations::Multiple(_), None) => None,
+  };
+  operation.map(|operation| operation.node.ty)
+}
+
+fn mask(mut response: Response, request_id: Option<&str>, details: bool) -> Response {
+  for error in &mut response.errors {
+    mask_error(error, request_id, details);
+  }
+  response
+}
+
+/// Sets the extensions of an error, replacing the message of internal errors.
+///
+/// Errors raised by the resolvers keep their source: `ApiError`s are reported as such, and any
+/// other source, e.g. a `DbErr` from the generated resolvers, is an internal error. Errors without
+/// a source, e.g. validation errors, are reported as they are.
+fn mask_error(error: &mut ServerError, request_id: Option<&str>, details: bool) {
+  // The status of the error and, for internal errors, the message replacing the underlying one.
+  let classified = if let Some(e) = error.source::<ApiError>() {
+    Some((e.status(), e.is_masked().then(|| e.to_string())))
+  } else if error.source::<DbErr>().is_some() {
+    let e = ApiError::DatabaseError(DbErr::Custom(String::new()));
+    Some((e.status(), Some(e.to_string())))
+  } else if error.source.is_some() {
+    let e = ApiError::InternalError(anyhow::anyhow!("unknown source"));
+    Some((e.status(), Some(e.to_string())))
+  } else {
+    None
+  };
+
+  let mut extensions = error.extensions.take().unwrap_or_default();
+  if let Some((status, masked)) = classified {
+    if let Some(masked) = masked {
+      let underlying = std::mem::replace(&mut error.message, masked);
+      error!(error = underlying, path = ?error.path, "GraphQL resolver failed");
+      if details {
+        extensions.set("details", underlying);
+      }
+    }
+    if extensions.get("code").is_none() {
+      extensions.set("code", code(status));
+    }
+  }
+  if let Some(request_id) = request_id {
+    extensions.set("requestId", request_id);
+  }
+  if let Some(trace_id) = telemetry::current_trace_id() {
+    extensions.set("traceId", trace_id);
+  }
+  error.extensions = Some(extensions);
+}
+
+/// The code of a status, e.g. `NOT_FOUND`.
+fn code(status: axum::http::StatusCode) -> String {
+  status
+    .canonical_reason()
+    .unwrap_or("Unknown")
+    .to_ascii_uppercase()
+    .replace(' ', "_")
+}
+
+#[cfg(test)]
+mod tests {
+  use async_graphql::{
+    dynamic::{Field, FieldFuture, Object, Schema, TypeRef},
+    Value,
+  };
+
+  use super::*;
+
+  fn schema() -> Schema {
+    let field = |name: &str, error: fn() -> async_graphql::Error| {
+      Field::new(name, TypeRef::named(TypeRef::INT), move |_| {
+        FieldFuture::new(async move { Err::<Option<Value>, _>(error()) })
+      })
+    };
+    let query = Object::new("Query")
+      .field(field("database", || {
+        DbErr::Custom("relation \"users\" does not exist".to_string()).into()
+      }))
+      .field(field("missing", || {
+        ApiError::NotFound("User not found".to_string()).into()
+      }));
+    Schema::build("Query", None, None)
+      .register(query)
+      .extension(Telemetry::default())
+      .finish()
+      .unwrap()
+  }
+
+  fn extension(error: &ServerError, name: &str) -> Option<Value> {
+    error.extensions.as_ref()?.get(name).cloned()
+  }
+
+  #[tokio::test]
+  async fn test_internal_errors_are_masked() {
+    let request = Request::new("{ database }").data(RequestId("request-1".to_string()));
+    let response = schema().execute(request).await;
+
+    let error = &response.errors[0];
+    assert_eq!(error.message, "A database error has occurred.");
+    assert_eq!(
+      extension(error, "code"),
+      Some(Value::from("INTERNAL_SERVER_ERROR"))
+    );
+    assert_eq!(
+      extension(error, "requestId"),
+      Some(Value::from("request-1"))
+    );
+  }
+
+  #[tokio::test]
+  async fn test_api_errors_keep_their_message() {
+    let response = schema().execute("{ missing }").await;
+
+    let error = &response.errors[0];
+    assert_eq!(error.message, "Not Found: User not found");
+    assert_eq!(extension(error, "code"), Some(Value::from("NOT_FOUND")));
+    assert_eq!(extension(error, "details"), None);
+  }
+
+  #[test]
+  fn test_details_are_only_given_if_enabled() {
+    let error = || {
+      let mut error = ServerError::new("connection refused", None);
+      error.source = Some(Arc::new(DbErr::Custom("connection refused".to_string())));
+      error
+    };
+
+    let mut hidden = error();
+    mask_error(&mut hidden, None, false);
+    assert_eq!(extension(&hidden, "details"), None);
+
+    let mut detailed = error();
+    mask_error(&mut detailed, None, true);
+    assert_eq!(
+      extension(&detailed, "details"),
+      Some(Value::from("connection refused"))
+    );
+  }
+}
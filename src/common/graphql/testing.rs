@@ -0,0 +1,11 @@
+//! Helpers for the tests of the GraphQL extensions.
+
+use async_graphql::Value;
+
+/// The `code` extension of the first error of `response`.
+pub fn error_code(response: &async_graphql::Response) -> Option<Value> {
+  response.errors[0]
+    .extensions
+    .as_ref()
+    .and_then(|extensions| extensions.get("code").cloned())
+}
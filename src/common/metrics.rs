@@ -0,0 +1,332 @@
+use std::{
+  cell::Cell,
+  collections::HashSet,
+  future::Future,
+  sync::{Mutex, OnceLock},
+  time::{Duration, Instant},
+};
+
+use axum::{
+  extract::{MatchedPath, Request, State},
+  http::header,
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::app::AppState;
+use crate::database::Db;
+
+const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+const HTTP_REQUESTS_DURATION_SECONDS: &str = "http_requests_duration_seconds";
+const HTTP_REQUESTS_IN_FLIGHT: &str = "http_requests_in_flight";
+const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+const DB_POOL_IDLE_CONNECTIONS: &str = "db_pool_idle_connections";
+const DB_POOL_MAX_CONNECTIONS: &str = "db_pool_max_connections";
+const DB_POOL_ACQUIRE_DURATION_SECONDS: &str = "db_pool_acquire_duration_seconds";
+const GRAPHQL_OPERATION_DURATION_SECONDS: &str = "graphql_operation_duration_seconds";
+const AUTH_LOGINS_TOTAL: &str = "auth_logins_total";
+const AUTH_LOGIN_FAILURES_TOTAL: &str = "auth_login_failures_total";
+const AUTH_REGISTRATIONS_TOTAL: &str = "auth_registrations_total";
+
+/// Number of GraphQL operation names labelled as sent by the clients, in addition to the ones of
+/// the allow-list. Later names are recorded as "other".
+const MAX_OPERATION_LABELS: usize = 100;
+
+/// Histogram buckets (in seconds) used for every `*_duration_seconds` metric.
+const DURATION_BUCKETS: &[f64] = &[
+  0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Returns the handle of the global Prometheus recorder.
+///
+/// The recorder is installed the first time this function is called, so every router built in the
+/// same process (including tests) shares the same registry.
+pub fn handle() -> PrometheusHandle {
+  HANDLE
+    .get_or_init(|| {
+      let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+          Matcher::Suffix("duration_seconds".to_string()),
+          DURATION_BUCKETS,
+        )
+        .expect("Invalid histogram buckets")
+        .install_recorder()
+        .expect("Failed to install Prometheus recorder");
+      describe_metrics();
+      handle
+    })
+    .clone()
+}
+
+fn describe_metrics() {
+  describe_counter!(HTTP_REQUESTS_TOTAL, "Total number of HTTP requests");
+  describe_histogram!(
+    HTTP_REQUESTS_DURATION_SECONDS,
+    "HTTP request latency in seconds"
+  );
+  describe_gauge!(
+    HTTP_REQUESTS_IN_FLIGHT,
+    "Number of HTTP requests currently being served"
+  );
+  describe_gauge!(
+    DB_POOL_CONNECTIONS,
+    "Number of connections currently open in the database pool"
+  );
+  describe_gauge!(
+    DB_POOL_IDLE_CONNECTIONS,
+    "Number of idle connections in the database pool"
+  );
+  describe_gauge!(
+    DB_POOL_MAX_CONNECTIONS,
+    "Maximum number of connections in the database pool"
+  );
+  describe_histogram!(
+    DB_POOL_ACQUIRE_DURATION_SECONDS,
+    "Time spent acquiring a connection from the database pool"
+  );
+  describe_histogram!(
+    GRAPHQL_OPERATION_DURATION_SECONDS,
+    "GraphQL operation execution time in seconds"
+  );
+  describe_counter!(AUTH_LOGINS_TOTAL, "Total number of successful logins");
+  describe_counter!(AUTH_LOGIN_FAILURES_TOTAL, "Total number of failed logins");
+  describe_counter!(AUTH_REGISTRATIONS_TOTAL, "Total number of registrations");
+}
+
+/// Decrements the in-flight gauge when dropped, so requests cancelled by an outer layer
+/// (e.g. the timeout layer) are not counted forever.
+struct InFlightGuard;
+
+impl InFlightGuard {
+  fn new() -> Self {
+    gauge!(HTTP_REQUESTS_IN_FLIGHT).increment(1);
+    Self
+  }
+}
+
+impl Drop for InFlightGuard {
+  fn drop(&mut self) {
+    gauge!(HTTP_REQUESTS_IN_FLIGHT).decrement(1);
+  }
+}
+
+/// Middleware that records request count, latency and in-flight requests.
+///
+/// Requests are labelled by matched route rather than by raw path to keep the label cardinality
+/// bounded, so it must be applied with `Router::route_layer`.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+  let path = match req.extensions().get::<MatchedPath>() {
+    Some(matched_path) => matched_path.as_str().to_owned(),
+    None => "unmatched".to_owned(),
+  };
+  let method = req.method().to_string();
+
+  let _in_flight = InFlightGuard::new();
+  let start = Instant::now();
+  let response = next.run(req).await;
+  let latency = start.elapsed().as_secs_f64();
+
+  let labels = [
+    ("method", method),
+    ("path", path),
+    ("status", response.status().as_u16().to_string()),
+  ];
+  counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+  histogram!(HTTP_REQUESTS_DURATION_SECONDS, &labels).record(latency);
+
+  response
+}
+
+/// Records the current state of the database connection pools, labelled by role.
+pub fn record_db_pool(db: &Db) {
+  for (role, stats) in db.pool_stats() {
+    let labels = [("role", role)];
+    gauge!(DB_POOL_CONNECTIONS, &labels).set(stats.size as f64);
+    gauge!(DB_POOL_IDLE_CONNECTIONS, &labels).set(stats.idle as f64);
+    gauge!(DB_POOL_MAX_CONNECTIONS, &labels).set(stats.max_size as f64);
+  }
+}
+
+/// Records the time it took to acquire a connection from the pool of `role`.
+pub fn record_db_acquire(role: &'static str, elapsed: Duration) {
+  histogram!(DB_POOL_ACQUIRE_DURATION_SECONDS, "role" => role).record(elapsed.as_secs_f64());
+}
+
+tokio::task_local! {
+  /// The role of the pool and the duration of the statement run by `time_db_acquire`, set by
+  /// the metric callback of the pool.
+  static DB_STATEMENT: Cell<Option<(&'static str, Duration)>>;
+}
+
+/// Runs a statement on a pool, recording how long the connection took to acquire.
+///
+/// The pool acquires the connection before it starts timing the statement, so the acquire time
+/// is what remains of the whole call. Nothing is recorded if the pool reported no statement.
+pub async fn time_db_acquire<T>(statement: impl Future<Output = T>) -> T {
+  let start = Instant::now();
+  let (result, reported) = DB_STATEMENT
+    .scope(Cell::new(None), async {
+      let result = statement.await;
+      (result, DB_STATEMENT.with(Cell::get))
+    })
+    .await;
+  if let Some((role, elapsed)) = reported {
+    record_db_acquire(role, start.elapsed().saturating_sub(elapsed));
+  }
+  result
+}
+
+/// Reports a statement run on the pool of `role` to the enclosing `time_db_acquire`, if any.
+pub fn report_db_statement(role: &'static str, elapsed: Duration) {
+  let _ = DB_STATEMENT.try_with(|statement| statement.set(Some((role, elapsed))));
+}
+
+/// The values of the `operation` label of the GraphQL metrics.
+///
+/// Operation names are chosen by the clients, so only the ones of the allow-list and the first
+/// [`MAX_OPERATION_LABELS`] others are used as is, keeping the number of series bounded.
+#[derive(Default)]
+pub struct OperationLabels {
+  known: HashSet<String>,
+  seen: Mutex<HashSet<String>>,
+}
+
+impl OperationLabels {
+  pub fn new(known: impl IntoIterator<Item = String>) -> Self {
+    Self {
+      known: known.into_iter().collect(),
+      seen: Mutex::default(),
+    }
+  }
+
+  /// Returns the label of an operation.
+  pub fn label(&self, operation: Option<&str>) -> String {
+    let Some(operation) = operation else {
+      return "anonymous".to_string();
+    };
+    if self.known.contains(operation) {
+      return operation.to_string();
+    }
+
+    let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+    if seen.contains(operation) {
+      return operation.to_string();
+    }
+    if seen.len() < MAX_OPERATION_LABELS && is_name(operation) {
+      seen.insert(operation.to_string());
+      return operation.to_string();
+    }
+    "other".to_string()
+  }
+}
+
+/// Whether a value is a GraphQL name of reasonable length.
+fn is_name(value: &str) -> bool {
+  let mut chars = value.chars();
+  value.len() <= 64
+    && chars
+      .next()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Records the execution time of a GraphQL operation, labelled with [`OperationLabels`].
+pub fn record_graphql_operation(operation: &str, elapsed: Duration, is_ok: bool) {
+  let labels = [
+    ("operation", operation.to_owned()),
+    ("status", if is_ok { "ok" } else { "error" }.to_owned()),
+  ];
+  histogram!(GRAPHQL_OPERATION_DURATION_SECONDS, &labels).record(elapsed.as_secs_f64());
+}
+
+/// Increments the successful login counter.
+pub fn record_login() {
+  counter!(AUTH_LOGINS_TOTAL).increment(1);
+}
+
+/// Increments the failed login counter.
+pub fn record_failed_login() {
+  counter!(AUTH_LOGIN_FAILURES_TOTAL).increment(1);
+}
+
+/// Increments the registration counter.
+pub fn record_registration() {
+  counter!(AUTH_REGISTRATIONS_TOTAL).increment(1);
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+  record_db_pool(&state.db);
+  (
+    [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+    handle().render(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_handle_can_be_requested_twice() {
+    handle();
+    handle();
+  }
+
+  #[test]
+  fn test_render_contains_auth_counters() {
+    let handle = handle();
+    record_login();
+    record_failed_login();
+    record_registration();
+
+    let output = handle.render();
+    assert!(output.contains(AUTH_LOGINS_TOTAL));
+    assert!(output.contains(AUTH_LOGIN_FAILURES_TOTAL));
+    assert!(output.contains(AUTH_REGISTRATIONS_TOTAL));
+  }
+
+  #[test]
+  fn test_render_contains_graphql_histogram() {
+    let handle = handle();
+    record_graphql_operation("testOperation", Duration::from_millis(12), true);
+
+    let output = handle.render();
+    assert!(output.contains("graphql_operation_duration_seconds_bucket"));
+    assert!(output.contains("operation=\"testOperation\""));
+  }
+
+  #[tokio::test]
+  async fn test_acquire_time_is_recorded_by_role() {
+    let handle = handle();
+    let result = time_db_acquire(async {
+      report_db_statement("test-role", Duration::ZERO);
+      42
+    })
+    .await;
+    assert_eq!(result, 42);
+    // Statements reported outside of `time_db_acquire`, e.g. in transactions, are ignored.
+    report_db_statement("untimed-role", Duration::ZERO);
+
+    let output = handle.render();
+    assert!(output.contains("db_pool_acquire_duration_seconds_count{role=\"test-role\"} 1"));
+    assert!(!output.contains("untimed-role"));
+  }
+
+  #[test]
+  fn test_operation_labels_are_bounded() {
+    let labels = OperationLabels::new(["Listed".to_string()]);
+    assert_eq!(labels.label(None), "anonymous");
+    assert_eq!(labels.label(Some("not a name")), "other");
+    for i in 0..MAX_OPERATION_LABELS {
+      assert_eq!(labels.label(Some(&format!("Op{i}"))), format!("Op{i}"));
+    }
+    assert_eq!(labels.label(Some("Op0")), "Op0");
+    assert_eq!(labels.label(Some("OneTooMany")), "other");
+    assert_eq!(labels.label(Some("Listed")), "Listed");
+  }
+}
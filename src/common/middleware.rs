@@ -1,13 +1,18 @@
 use std::time::Duration;
 
-use axum::{extract::Request, http::HeaderName};
+use axum::{
+  extract::Request,
+  http::{HeaderName, StatusCode},
+};
 use tower_http::{
-  cors::{AllowHeaders, Any, CorsLayer},
+  cors::{AllowHeaders, AllowOrigin, Any, CorsLayer},
   normalize_path::NormalizePathLayer,
   request_id::{MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
   timeout::TimeoutLayer,
 };
 
+use crate::common::settings::Settings;
+
 #[derive(Clone, Default)]
 pub struct Id;
 
@@ -37,18 +42,24 @@ pub fn propagate_request_id_layer() -> PropagateRequestIdLayer {
 }
 
 /// Layer that applies the Cors middleware which adds headers for CORS.
-pub fn cors_layer() -> CorsLayer {
+///
+/// Allowed origins are read from the runtime settings on every request, so they follow reloads.
+pub fn cors_layer(settings: Settings) -> CorsLayer {
   CorsLayer::new()
-    .allow_origin(Any)
+    .allow_origin(AllowOrigin::predicate(move |origin, _| {
+      origin
+        .to_str()
+        .is_ok_and(|origin| settings.current().is_origin_allowed(origin))
+    }))
     .allow_methods(Any)
     .allow_headers(AllowHeaders::mirror_request())
     .max_age(Duration::from_secs(600))
 }
 
 /// Layer that applies the Timeout middleware which apply a timeout to requests.
-/// The default timeout value is set to 15 seconds.
+/// The default timeout value is set to 15 seconds, after which `408 Request Timeout` is returned.
 pub fn timeout_layer() -> TimeoutLayer {
-  TimeoutLayer::new(Duration::from_secs(15))
+  TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, Duration::from_secs(15))
 }
 
 /// Middleware that normalizes paths.
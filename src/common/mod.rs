@@ -1,5 +1,14 @@
 pub mod api_error;
 pub mod cfg;
+pub mod events;
+pub mod graphql;
+pub mod metrics;
 pub mod middleware;
+pub mod rate_limit;
+pub mod read_your_writes;
+pub mod security_headers;
+pub mod settings;
+pub mod shutdown;
 pub mod telemetry;
+pub mod transaction;
 pub mod utils;
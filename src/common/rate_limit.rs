@@ -0,0 +1,170 @@
+use std::{
+  net::IpAddr,
+  num::NonZeroUsize,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+
+use axum::{
+  extract::{Request, State},
+  middleware::Next,
+  response::Response,
+};
+use lru::LruCache;
+
+use crate::{app::AppState, common::api_error::ApiError};
+
+/// Number of tracked clients, past which the least recently seen one is forgotten.
+const MAX_TRACKED_CLIENTS: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+
+/// The health probes, which are never limited.
+const UNLIMITED_PATHS: [&str; 2] = ["/api/v1/health/live", "/api/v1/health/ready"];
+
+/// Per client IP token bucket rate limiter.
+///
+/// The rate and burst are passed on every check so that they follow the reloadable
+/// runtime settings.
+#[derive(Clone)]
+pub struct RateLimiter {
+  buckets: Arc<Mutex<LruCache<IpAddr, Bucket>>>,
+}
+
+struct Bucket {
+  tokens: f64,
+  updated_at: Instant,
+}
+
+impl Default for RateLimiter {
+  fn default() -> Self {
+    Self::with_capacity(MAX_TRACKED_CLIENTS)
+  }
+}
+
+impl RateLimiter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A limiter tracking at most `capacity` clients.
+  fn with_capacity(capacity: NonZeroUsize) -> Self {
+    Self {
+      buckets: Arc::new(Mutex::new(LruCache::new(capacity))),
+    }
+  }
+
+  /// Takes a token for `client`, returning `false` if the client exceeded the rate.
+  pub fn check(&self, client: IpAddr, per_second: u32, burst: u32, now: Instant) -> bool {
+    let capacity = f64::from(burst.max(1));
+    let rate = f64::from(per_second);
+    // The buckets are left consistent by every check, so a poisoned lock can be taken over.
+    let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+    let bucket = buckets.get_or_insert_mut(client, || Bucket {
+      tokens: capacity,
+      updated_at: now,
+    });
+    let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+    bucket.updated_at = now;
+
+    if bucket.tokens < 1.0 {
+      return false;
+    }
+    bucket.tokens -= 1.0;
+    true
+  }
+}
+
+/// Rejects requests from clients exceeding the configured rate with 429 Too Many Requests.
+///
+/// Health probes are never limited, so that a busy client cannot make the instance look unhealthy.
+/// Requests whose client address cannot be told are rejected rather than sharing a bucket.
+pub async fn rate_limit(
+  State(state): State<AppState>,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let settings = state.settings.current();
+  if settings.rate_limit_per_second == 0 || is_unlimited(req.uri().path()) {
+    return Ok(next.run(req).await);
+  }
+
+  let Some(client) = state.cfg.trusted_proxies.client_ip(&req) else {
+    return Err(ApiError::InvalidRequest(
+      "Unable to determine the client address".to_string(),
+    ));
+  };
+
+  if !state.rate_limiter.check(
+    client,
+    settings.rate_limit_per_second,
+    settings.rate_limit_burst,
+    Instant::now(),
+  ) {
+    return Err(ApiError::TooManyRequests);
+  }
+
+  Ok(next.run(req).await)
+}
+
+/// Whether `path` is a health probe. Other health endpoints, such as the info one that checks
+/// credentials, are limited.
+fn is_unlimited(path: &str) -> bool {
+  UNLIMITED_PATHS.contains(&path)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+
+  #[test]
+  fn test_burst_then_refill() {
+    let limiter = RateLimiter::new();
+    let client = IpAddr::from([127, 0, 0, 1]);
+    let now = Instant::now();
+
+    assert!(limiter.check(client, 1, 2, now));
+    assert!(limiter.check(client, 1, 2, now));
+    assert!(!limiter.check(client, 1, 2, now));
+
+    assert!(limiter.check(client, 1, 2, now + Duration::from_secs(1)));
+    assert!(!limiter.check(client, 1, 2, now + Duration::from_secs(1)));
+  }
+
+  #[test]
+  fn test_clients_are_limited_separately() {
+    let limiter = RateLimiter::new();
+    let now = Instant::now();
+
+    assert!(limiter.check(IpAddr::from([10, 0, 0, 1]), 1, 1, now));
+    assert!(!limiter.check(IpAddr::from([10, 0, 0, 1]), 1, 1, now));
+    assert!(limiter.check(IpAddr::from([10, 0, 0, 2]), 1, 1, now));
+  }
+
+  #[test]
+  fn test_only_the_probes_are_unlimited() {
+    assert!(is_unlimited("/api/v1/health/live"));
+    assert!(is_unlimited("/api/v1/health/ready"));
+    assert!(!is_unlimited("/api/v1/health/info"));
+    assert!(!is_unlimited("/api/v1/health"));
+    assert!(!is_unlimited("/api/v1/health/live/extra"));
+  }
+
+  #[test]
+  fn test_least_recently_seen_clients_are_forgotten() {
+    let limiter = RateLimiter::with_capacity(NonZeroUsize::new(2).unwrap());
+    let now = Instant::now();
+    let clients = [1, 2, 3].map(|host| IpAddr::from([10, 0, 0, host]));
+
+    assert!(limiter.check(clients[0], 1, 1, now));
+    assert!(limiter.check(clients[1], 1, 1, now));
+    assert!(!limiter.check(clients[0], 1, 1, now));
+    // The second client is forgotten for the third, the first one being seen since.
+    assert!(limiter.check(clients[2], 1, 1, now));
+    assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+    assert!(!limiter.check(clients[0], 1, 1, now));
+    assert!(limiter.check(clients[1], 1, 1, now));
+  }
+}
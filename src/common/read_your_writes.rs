@@ -0,0 +1,110 @@
+use std::convert::Infallible;
+
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  http::{header, request::Parts, HeaderMap, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use sea_orm::DatabaseConnection;
+
+use crate::{app::AppState, common::cfg::Config};
+
+/// Cookie set after a write, so that the client's next reads see it.
+pub const COOKIE: &str = "read_primary";
+
+/// Header a client can send to read from the primary, e.g. when it cannot keep cookies.
+pub const HEADER: &str = "x-read-your-writes";
+
+/// Whether the request asks to read from the primary.
+pub fn requested(headers: &HeaderMap) -> bool {
+  headers.contains_key(HEADER)
+    || headers
+      .get_all(header::COOKIE)
+      .iter()
+      .filter_map(|value| value.to_str().ok())
+      .flat_map(|cookies| cookies.split(';'))
+      .any(|cookie| cookie.trim().split('=').next() == Some(COOKIE))
+}
+
+/// Sets the cookie sending the client's reads to the primary for the configured window.
+///
+/// Nothing is set when there are no replicas to lag behind.
+pub fn mark(headers: &mut HeaderMap, state: &AppState) {
+  if !state.db.has_replicas() || state.cfg.db_read_your_writes_window == 0 {
+    return;
+  }
+  if let Ok(cookie) = HeaderValue::from_str(&cookie(&state.cfg)) {
+    headers.append(header::SET_COOKIE, cookie);
+  }
+}
+
+fn cookie(cfg: &Config) -> String {
+  let secure = if cfg.profile.secure_cookies {
+    "; Secure"
+  } else {
+    ""
+  };
+  format!(
+    "{COOKIE}=1; Max-Age={}; Path=/; HttpOnly; SameSite=Lax{secure}",
+    cfg.db_read_your_writes_window
+  )
+}
+
+/// Marks the responses of successful writes, i.e. requests with an unsafe method.
+pub async fn read_your_writes(State(state): State<AppState>, req: Request, next: Next) -> Response {
+  let is_write = !req.method().is_safe();
+  let mut response = next.run(req).await;
+  if is_write && response.status().is_success() {
+    mark(response.headers_mut(), &state);
+  }
+  response
+}
+
+/// A connection for read-only queries.
+///
+/// Reads go to a replica, unless the client recently wrote or asked to read from the primary.
+pub struct Reader(pub DatabaseConnection);
+
+impl FromRequestParts<AppState> for Reader {
+  type Rejection = Infallible;
+
+  async fn from_request_parts(
+    parts: &mut Parts,
+    state: &AppState,
+  ) -> Result<Self, Self::Rejection> {
+    let conn = if requested(&parts.headers) {
+      state.db.writer()
+    } else {
+      state.db.reader()
+    };
+    Ok(Reader(conn.clone()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_requested() {
+    let mut headers = HeaderMap::new();
+    assert!(!requested(&headers));
+
+    headers.insert(
+      header::COOKIE,
+      HeaderValue::from_static("theme=dark; read_primary_x=1"),
+    );
+    assert!(!requested(&headers));
+
+    headers.insert(
+      header::COOKIE,
+      HeaderValue::from_static("theme=dark; read_primary=1"),
+    );
+    assert!(requested(&headers));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(HEADER, HeaderValue::from_static("true"));
+    assert!(requested(&headers));
+  }
+}
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use axum::{
+  extract::{Request, State},
+  http::{header, HeaderName, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+
+use crate::common::cfg::{
+  security_headers::NONCE_PLACEHOLDER, Configuration, SecurityHeadersConfig,
+};
+
+/// The nonce of a response, allowing the inline scripts of a page under a relaxed policy.
+#[derive(Clone, Debug)]
+pub struct CspNonce(pub String);
+
+impl CspNonce {
+  fn new() -> Self {
+    Self(uuid::Uuid::new_v4().simple().to_string())
+  }
+
+  /// Sets the nonce on every script of a page.
+  pub fn apply(&self, html: &str) -> String {
+    html.replace("<script", &format!("<script nonce=\"{}\"", self.0))
+  }
+}
+
+/// The security headers of the responses, with the content security policies of the routes
+/// that need a relaxed one.
+pub struct SecurityHeaders {
+  headers: Vec<(HeaderName, HeaderValue)>,
+  policy: HeaderValue,
+  /// Path prefixes and their policy, which may contain the nonce placeholder.
+  routes: Vec<(String, String)>,
+}
+
+impl SecurityHeaders {
+  pub fn new(config: &SecurityHeadersConfig) -> Self {
+    let mut headers = vec![
+      (
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+      ),
+      (
+        header::X_FRAME_OPTIONS,
+        HeaderValue::from_static(config.frame_options.header()),
+      ),
+      (
+        header::REFERRER_POLICY,
+        header_value(&config.referrer_policy),
+      ),
+      (
+        HeaderName::from_static("permissions-policy"),
+        header_value(&config.permissions_policy),
+      ),
+    ];
+    if let Some(hsts) = config.strict_transport_security() {
+      headers.push((header::STRICT_TRANSPORT_SECURITY, header_value(&hsts)));
+    }
+
+    Self {
+      headers,
+      policy: header_value(&config.with_frame_ancestors(&config.content_security_policy)),
+      routes: Vec::new(),
+    }
+  }
+
+  /// Builds the headers of the application, relaxing the policy of the Swagger UI and GraphiQL
+  /// where they are mounted.
+  pub fn from_config(cfg: &Configuration) -> Arc<Self> {
+    let config = &cfg.security_headers;
+    let mut headers = Self::new(config);
+    if cfg.profile.mount_docs {
+      headers = headers
+        .route(
+          &cfg.swagger_endpoint,
+          &config.with_frame_ancestors(&config.swagger_content_security_policy),
+        )
+        .route(
+          &cfg.graphql_endpoint,
+          &config.with_frame_ancestors(&config.graphiql_content_security_policy),
+        );
+    }
+    Arc::new(headers)
+  }
+
+  /// Overrides the content security policy of the paths under `prefix`.
+  pub fn route(mut self, prefix: &str, policy: &str) -> Self {
+    self
+      .routes
+      .push((prefix.trim_end_matches('/').to_string(), policy.to_string()));
+    // The most specific prefix wins.
+    self
+      .routes
+      .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    self
+  }
+
+  /// The policy overriding the default one for `path`, if any.
+  fn route_policy(&self, path: &str) -> Option<&str> {
+    self
+      .routes
+      .iter()
+      .find(|(prefix, _)| {
+        path
+          .strip_prefix(prefix.as_str())
+          .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+      })
+      .map(|(_, policy)| policy.as_str())
+  }
+}
+
+/// Sets the security headers on every response, unless the handler set them itself.
+///
+/// Requests to a route whose policy uses a nonce are given a [`CspNonce`] extension.
+pub async fn security_headers(
+  State(security): State<Arc<SecurityHeaders>>,
+  mut req: Request,
+  next: Next,
+) -> Response {
+  let policy = match security.route_policy(req.uri().path()) {
+    Some(policy) if policy.contains(NONCE_PLACEHOLDER) => {
+      let nonce = CspNonce::new();
+      let policy = header_value(&policy.replace(NONCE_PLACEHOLDER, &nonce.0));
+      req.extensions_mut().insert(nonce);
+      policy
+    }
+    Some(policy) => header_value(policy),
+    None => security.policy.clone(),
+  };
+
+  let mut response = next.run(req).await;
+  let headers = response.headers_mut();
+  for (name, value) in &security.headers {
+    headers.entry(name).or_insert_with(|| value.clone());
+  }
+  headers
+    .entry(header::CONTENT_SECURITY_POLICY)
+    .or_insert(policy);
+  response
+}
+
+/// Values are validated when the configuration is loaded.
+fn header_value(value: &str) -> HeaderValue {
+  HeaderValue::from_str(value).expect("Invalid security header value")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::cfg::Values;
+
+  fn security_headers() -> SecurityHeaders {
+    let mut values = Values::from_pairs([("SECURITY_CSP", "default-src 'none'")]);
+    let config = SecurityHeadersConfig::from_values(&mut values, true);
+    values.finish().unwrap();
+    SecurityHeaders::new(&config)
+      .route("/docs/", "script-src 'self'")
+      .route("/graphql", "script-src 'nonce-{nonce}'")
+      .route("/graphql/ws", "default-src 'none'")
+  }
+
+  #[test]
+  fn test_route_policies() {
+    let headers = security_headers();
+    assert_eq!(headers.policy, "default-src 'none'; frame-ancestors 'none'");
+    assert_eq!(headers.route_policy("/docs"), Some("script-src 'self'"));
+    assert_eq!(
+      headers.route_policy("/docs/swagger-initializer.js"),
+      Some("script-src 'self'")
+    );
+    assert_eq!(headers.route_policy("/docsearch"), None);
+    assert_eq!(
+      headers.route_policy("/graphql"),
+      Some("script-src 'nonce-{nonce}'")
+    );
+    assert_eq!(
+      headers.route_policy("/graphql/ws"),
+      Some("default-src 'none'")
+    );
+    assert_eq!(headers.route_policy("/api/v1/users"), None);
+  }
+
+  #[test]
+  fn test_headers() {
+    let headers = security_headers();
+    let names: Vec<&str> = headers
+      .headers
+      .iter()
+      .map(|(name, _)| name.as_str())
+      .collect();
+    assert_eq!(
+      names,
+      [
+        "x-content-type-options",
+        "x-frame-options",
+        "referrer-policy",
+        "permissions-policy",
+        "strict-transport-security"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_nonce_is_set_on_scripts() {
+    let nonce = CspNonce("abc".to_string());
+    assert_eq!(
+      nonce.apply("<script src=\"a.js\"></script><script>run()</script>"),
+      "<script nonce=\"abc\" src=\"a.js\"></script><script nonce=\"abc\">run()</script>"
+    );
+    assert_ne!(CspNonce::new().0, CspNonce::new().0);
+  }
+}
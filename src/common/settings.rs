@@ -0,0 +1,159 @@
+use std::{sync::Arc, time::Duration, time::SystemTime};
+
+use arc_swap::ArcSwap;
+
+use crate::common::{
+  cfg::{loader, ConfigError, RuntimeSettings},
+  shutdown::Shutdown,
+  telemetry::LogFilter,
+};
+
+/// Shared handle to the settings that can be changed without a restart.
+///
+/// Readers get a consistent snapshot with `current`. New settings are validated before they are
+/// swapped in, so a bad reload leaves the previous settings in place.
+#[derive(Clone)]
+pub struct Settings {
+  current: Arc<ArcSwap<RuntimeSettings>>,
+  log_filter: Option<LogFilter>,
+}
+
+impl Settings {
+  pub fn new(initial: RuntimeSettings) -> Self {
+    Self {
+      current: Arc::new(ArcSwap::from_pointee(initial)),
+      log_filter: None,
+    }
+  }
+
+  /// Applies log filter changes to the installed tracing subscriber.
+  pub fn with_log_filter(mut self, log_filter: LogFilter) -> Self {
+    self.log_filter = Some(log_filter);
+    self
+  }
+
+  /// Returns a snapshot of the current settings.
+  pub fn current(&self) -> Arc<RuntimeSettings> {
+    self.current.load_full()
+  }
+
+  /// Swaps in new settings, returning whether they differ from the current ones.
+  pub fn apply(&self, settings: RuntimeSettings) -> Result<bool, String> {
+    let current = self.current();
+    if *current == settings {
+      return Ok(false);
+    }
+
+    if current.log_filter != settings.log_filter {
+      if let Some(log_filter) = &self.log_filter {
+        log_filter.set(&settings.log_filter)?;
+      }
+    }
+    self.current.store(Arc::new(settings));
+    Ok(true)
+  }
+
+  /// Reloads the settings from the configuration sources.
+  pub fn reload(&self) -> Result<bool, ConfigError> {
+    let settings = RuntimeSettings::load()?;
+    self
+      .apply(settings)
+      .map_err(|err| ConfigError(vec![format!("RUST_LOG: {err}")]))
+  }
+
+  /// Changes the log filter until the next reload.
+  pub fn set_log_filter(&self, directives: &str) -> Result<(), String> {
+    // Parsing validates the directives before anything is changed.
+    tracing_subscriber::EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+    let mut settings = (*self.current()).clone();
+    settings.log_filter = directives.to_string();
+    self.apply(settings).map(|_| ())
+  }
+
+  /// Reloads the settings, logging the outcome.
+  fn reload_and_log(&self, reason: &str) {
+    match self.reload() {
+      Ok(true) => {
+        tracing::info!(settings = ?self.current(), "Runtime settings reloaded on {reason}")
+      }
+      Ok(false) => tracing::debug!("Runtime settings unchanged on {reason}"),
+      Err(err) => tracing::error!("Keeping the current runtime settings: {err}"),
+    }
+  }
+}
+
+/// Reloads the settings on SIGHUP and whenever the configuration file changes, until shutdown.
+///
+/// The file is polled every `interval`, a zero interval disables watching.
+pub async fn watch(settings: Settings, interval: Duration, shutdown: Shutdown) {
+  #[cfg(unix)]
+  let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+    .expect("Failed to install the SIGHUP handler");
+
+  let config_file = loader::config_file();
+  let mut last_modified = config_file.as_deref().and_then(modified_at);
+  let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+  ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+  loop {
+    #[cfg(unix)]
+    let hangup = hangup.recv();
+    #[cfg(not(unix))]
+    let hangup = std::future::pending::<Option<()>>();
+
+    tokio::select! {
+      _ = shutdown.cancelled() => return,
+      _ = hangup => settings.reload_and_log("SIGHUP"),
+      _ = ticker.tick(), if !interval.is_zero() => {
+        let modified = config_file.as_deref().and_then(modified_at);
+        if modified != last_modified {
+          last_modified = modified;
+          settings.reload_and_log("configuration file change");
+        }
+      }
+    }
+  }
+}
+
+fn modified_at(path: &std::path::Path) -> Option<SystemTime> {
+  std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn settings() -> RuntimeSettings {
+    RuntimeSettings {
+      log_filter: "info".to_string(),
+      cors_allowed_origins: vec!["*".to_string()],
+      rate_limit_per_second: 0,
+      rate_limit_burst: 50,
+    }
+  }
+
+  #[test]
+  fn test_apply_swaps_settings() {
+    let handle = Settings::new(settings());
+    let snapshot = handle.current();
+
+    let mut next = settings();
+    next.rate_limit_per_second = 10;
+    assert_eq!(handle.apply(next.clone()), Ok(true));
+    assert_eq!(handle.apply(next), Ok(false));
+
+    assert_eq!(handle.current().rate_limit_per_second, 10);
+    // Earlier snapshots are not affected.
+    assert_eq!(snapshot.rate_limit_per_second, 0);
+  }
+
+  #[test]
+  fn test_invalid_log_filter_is_rejected() {
+    let handle = Settings::new(settings());
+    assert!(handle.set_log_filter("server=loud").is_err());
+    assert_eq!(handle.current().log_filter, "info");
+
+    assert!(handle.set_log_filter("server=trace").is_ok());
+    assert_eq!(handle.current().log_filter, "server=trace");
+  }
+}
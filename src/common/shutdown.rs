@@ -0,0 +1,198 @@
+use std::{future::Future, pin::pin, time::Duration};
+
+use axum::{
+  body::Body,
+  extract::{ConnectInfo, Request},
+  Router,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+  rt::{TokioExecutor, TokioIo},
+  server::conn::auto::Builder,
+};
+use tokio::net::TcpListener;
+use tokio_util::{
+  sync::CancellationToken,
+  task::{task_tracker::TrackedFuture, TaskTracker},
+};
+use tower::Service;
+
+/// Coordinates the graceful shutdown of the server and its background tasks.
+///
+/// Cloned into `AppState`, so any part of the application can observe the shutdown or spawn
+/// background workers that are waited for before the process exits.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+  token: CancellationToken,
+  /// Cancelled when the drain deadline passed, to close the connections still open.
+  abort: CancellationToken,
+  tracker: TaskTracker,
+}
+
+impl Shutdown {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns a token that is cancelled once the shutdown starts.
+  pub fn token(&self) -> CancellationToken {
+    self.token.clone()
+  }
+
+  /// Completes once the shutdown starts.
+  pub async fn cancelled(&self) {
+    self.token.cancelled().await
+  }
+
+  /// Whether the shutdown has started.
+  pub fn is_cancelled(&self) -> bool {
+    self.token.is_cancelled()
+  }
+
+  /// Signals the listeners and background workers to stop.
+  pub fn trigger(&self) {
+    self.token.cancel();
+  }
+
+  /// Closes the connections still open, dropping their in-flight requests.
+  pub fn abort(&self) {
+    self.abort.cancel();
+  }
+
+  /// Completes once the connections still open must be closed.
+  pub async fn aborted(&self) {
+    self.abort.cancelled().await
+  }
+
+  /// Spawns a background worker that is waited for on shutdown.
+  ///
+  /// The worker is expected to return once `cancelled` completes.
+  pub fn spawn<F>(&self, task: F)
+  where
+    F: Future<Output = ()> + Send + 'static,
+  {
+    self.tracker.spawn(task);
+  }
+
+  /// Tracks a task spawned by someone else, such as the WebSocket connections axum spawns once
+  /// upgraded, so that it is waited for on shutdown.
+  ///
+  /// The task is expected to return once `cancelled` completes, and at the latest once `aborted`
+  /// does.
+  pub fn track<F: Future>(&self, task: F) -> TrackedFuture<F> {
+    self.tracker.track_future(task)
+  }
+
+  /// Waits for the background workers and the connections to finish.
+  pub async fn wait_for_tasks(&self) {
+    self.tracker.close();
+    self.tracker.wait().await
+  }
+
+  /// Number of background workers and connections still running.
+  pub fn running_tasks(&self) -> usize {
+    self.tracker.len()
+  }
+}
+
+/// Serves `router` until the shutdown starts.
+///
+/// Unlike `axum::serve`, each connection runs as a task of `shutdown`, so the drain waits for the
+/// in-flight requests and [`Shutdown::abort`] closes the connections that outlive it. The client
+/// address is available to the handlers as `ConnectInfo<SocketAddr>`.
+pub async fn serve(listener: TcpListener, router: Router, shutdown: Shutdown) {
+  loop {
+    let (stream, addr) = tokio::select! {
+      accepted = listener.accept() => match accepted {
+        Ok(accepted) => accepted,
+        Err(err) => {
+          // Usually out of file descriptors, which other connections may release.
+          tracing::error!("Failed to accept a connection: {}", err);
+          tokio::time::sleep(Duration::from_secs(1)).await;
+          continue;
+        }
+      },
+      _ = shutdown.cancelled() => return,
+    };
+
+    let router = router.clone();
+    let service = hyper::service::service_fn(move |mut req: Request<Incoming>| {
+      req.extensions_mut().insert(ConnectInfo(addr));
+      router.clone().call(req.map(Body::new))
+    });
+    let (token, abort) = (shutdown.token(), shutdown.abort.clone());
+    shutdown.spawn(async move {
+      let mut builder = Builder::new(TokioExecutor::new());
+      // The CONNECT protocol carries WebSockets over HTTP/2.
+      builder.http2().enable_connect_protocol();
+      let mut conn = pin!(builder.serve_connection_with_upgrades(TokioIo::new(stream), service));
+      let mut draining = false;
+      loop {
+        tokio::select! {
+          result = conn.as_mut() => {
+            if let Err(err) = result {
+              tracing::trace!("Failed to serve connection: {:#}", err);
+            }
+            return;
+          }
+          _ = token.cancelled(), if !draining => {
+            // Finish the in-flight requests, then close the connection.
+            conn.as_mut().graceful_shutdown();
+            draining = true;
+          }
+          _ = abort.cancelled() => return,
+        }
+      }
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_workers_stop_on_trigger() {
+    let shutdown = Shutdown::new();
+    let worker = shutdown.clone();
+    shutdown.spawn(async move { worker.cancelled().await });
+    assert_eq!(shutdown.running_tasks(), 1);
+
+    shutdown.trigger();
+    assert!(shutdown.is_cancelled());
+    shutdown.wait_for_tasks().await;
+    assert_eq!(shutdown.running_tasks(), 0);
+  }
+
+  #[tokio::test]
+  async fn test_abort_closes_the_connections_left_after_the_drain() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Router::new().route(
+      "/",
+      axum::routing::get(|| async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+      }),
+    );
+    let shutdown = Shutdown::new();
+    let server = tokio::spawn(serve(listener, router, shutdown.clone()));
+
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tokio::io::AsyncWriteExt::write_all(&mut client, b"GET / HTTP/1.1\r\nHost: test\r\n\r\n")
+      .await
+      .unwrap();
+    while shutdown.running_tasks() == 0 {
+      tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    shutdown.trigger();
+    server.await.unwrap();
+    let drain = tokio::time::timeout(Duration::from_millis(50), shutdown.wait_for_tasks()).await;
+    assert!(drain.is_err(), "the in-flight request is drained");
+
+    shutdown.abort();
+    tokio::time::timeout(Duration::from_secs(1), shutdown.wait_for_tasks())
+      .await
+      .unwrap();
+  }
+}
@@ -1,29 +1,221 @@
+use std::time::SystemTime;
+
+use axum::http::Request;
+use opentelemetry::{
+  global,
+  trace::{Span as _, SpanKind, Status, TraceContextExt, Tracer as _, TracerProvider as _},
+  KeyValue,
+};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
 use tower_http::{
   classify::{ServerErrorsAsFailures, SharedClassifier},
-  trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
+  trace::{DefaultOnRequest, DefaultOnResponse, MakeSpan, TraceLayer},
+};
+use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+  fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
 };
-use tracing::Level;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-/// The `EnvFilter` type is used to filter log events based on the value of an environment variable.
-/// In this case, we are using the `try_from_default_env` method to attempt to read the `RUST_LOG` environment variable,
-/// which is used to set the log level for the application.
-/// If the environment variable is not set, we default to the log level of `debug`.
+use crate::common::cfg::{Configuration, LogFormat, OtlpProtocol};
+
+/// Flushes and shuts down the tracer provider when dropped, so spans buffered by the batch
+/// exporter are not lost on exit.
+pub struct TelemetryGuard {
+  tracer_provider: SdkTracerProvider,
+  log_filter: LogFilter,
+}
+
+impl TelemetryGuard {
+  /// Returns the handle used to change the log filter at runtime.
+  pub fn log_filter(&self) -> LogFilter {
+    self.log_filter.clone()
+  }
+}
+
+impl Drop for TelemetryGuard {
+  fn drop(&mut self) {
+    if let Err(err) = self.tracer_provider.shutdown() {
+      eprintln!("Failed to shut down the tracer provider: {err}");
+    }
+  }
+}
+
+/// Changes the `EnvFilter` of the installed subscriber at runtime.
+#[derive(Clone)]
+pub struct LogFilter(reload::Handle<EnvFilter, Registry>);
+
+impl LogFilter {
+  /// Replaces the filter with the given `RUST_LOG`-style directives.
+  pub fn set(&self, directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives).map_err(|e| e.to_string())?;
+    self.0.reload(filter).map_err(|e| e.to_string())
+  }
+}
+
+/// The `EnvFilter` type is used to filter log events based on the configured log filter.
+/// The filter is read from the `RUST_LOG` setting, which defaults to `debug` and is validated
+/// when the configuration is loaded.
 /// The `RUST_LOG` environment variable is set in the Dockerfile and .env files.
-pub fn setup_tracing() {
-  let env_filter_layer = EnvFilter::try_from_default_env().unwrap_or_else(|_| "debug".into());
-  let formatting_layer = fmt::layer().json();
+/// The filter is wrapped in a reload layer so that it can be changed without a restart.
+///
+/// Logs are pretty-printed or written as JSON depending on the environment profile.
+///
+/// Spans are always bridged to OpenTelemetry so that trace ids are available to log lines and
+/// error responses. They are only exported when an OTLP endpoint is configured.
+pub fn setup_tracing(cfg: &Configuration) -> TelemetryGuard {
+  global::set_text_map_propagator(TraceContextPropagator::new());
+
+  let resource = Resource::builder()
+    .with_service_name(cfg.otel_service_name.clone())
+    .build();
+  let mut tracer_provider = SdkTracerProvider::builder().with_resource(resource);
+  if let Some(endpoint) = &cfg.otel_exporter_endpoint {
+    let exporter = match cfg.otel_exporter_protocol {
+      OtlpProtocol::Grpc => SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build(),
+      OtlpProtocol::HttpProtobuf => SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build(),
+    }
+    .expect("Failed to create the OTLP span exporter");
+    tracer_provider = tracer_provider.with_batch_exporter(exporter);
+  }
+  let tracer_provider = tracer_provider.build();
+  global::set_tracer_provider(tracer_provider.clone());
+
+  let (env_filter_layer, log_filter) = reload::Layer::new(EnvFilter::new(&cfg.runtime.log_filter));
+  // Only one of the formatting layers is installed, depending on the environment.
+  let (pretty_layer, json_layer) = match cfg.profile.log_format {
+    LogFormat::Pretty => (Some(fmt::layer().pretty()), None),
+    LogFormat::Json => (None, Some(fmt::layer().json())),
+  };
+  let otel_layer =
+    tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(env!("CARGO_PKG_NAME")));
   tracing_subscriber::registry()
     .with(env_filter_layer)
-    .with(formatting_layer)
-    .init()
+    .with(pretty_layer)
+    .with(json_layer)
+    .with(otel_layer)
+    .init();
+
+  TelemetryGuard {
+    tracer_provider,
+    log_filter: LogFilter(log_filter),
+  }
 }
 
 /// Returns a `TraceLayer` for HTTP requests and responses.
 /// The `TraceLayer` is used to trace requests and responses in the application.
-pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>> {
+pub fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, OtelMakeSpan> {
   TraceLayer::new_for_http()
-    .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+    .make_span_with(OtelMakeSpan)
     .on_request(DefaultOnRequest::new().level(Level::INFO))
     .on_response(DefaultOnResponse::new().level(Level::INFO))
 }
+
+/// Creates the request span, continuing the trace from the W3C `traceparent` header if present.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OtelMakeSpan;
+
+impl<B> MakeSpan<B> for OtelMakeSpan {
+  fn make_span(&mut self, request: &Request<B>) -> Span {
+    let span = tracing::info_span!(
+      "request",
+      method = %request.method(),
+      uri = %request.uri(),
+      version = ?request.version(),
+      otel.kind = "server",
+      trace_id = tracing::field::Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+      propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    let _ = span.set_parent(parent);
+    if let Some(trace_id) = trace_id(&span) {
+      span.record("trace_id", trace_id);
+    }
+
+    span
+  }
+}
+
+/// Returns the trace id of the current span, if it is part of a valid trace.
+pub fn current_trace_id() -> Option<String> {
+  trace_id(&Span::current())
+}
+
+fn trace_id(span: &Span) -> Option<String> {
+  let context = span.context();
+  let span_context = context.span().span_context().clone();
+  span_context
+    .is_valid()
+    .then(|| span_context.trace_id().to_string())
+}
+
+/// Sea-ORM metric callback that records every executed statement as a client span.
+///
+/// The callback runs right after the statement completed, in the task that issued it, so the
+/// span is attached to the current request span and backdated by the query duration.
+pub fn record_db_query(info: &sea_orm::metric::Info<'_>) {
+  let end_time = SystemTime::now();
+  let start_time = end_time - info.elapsed;
+
+  let tracer = global::tracer("sea-orm");
+  let mut span = tracer
+    .span_builder("db.query")
+    .with_kind(SpanKind::Client)
+    .with_start_time(start_time)
+    .with_attributes([
+      KeyValue::new("db.system", "postgresql"),
+      KeyValue::new("db.statement", info.statement.sql.clone()),
+    ])
+    .start_with_context(&tracer, &Span::current().context());
+  if info.failed {
+    span.set_status(Status::error("query failed"));
+  }
+  span.end_with_timestamp(end_time);
+
+  tracing::debug!(
+    elapsed_ms = info.elapsed.as_millis() as u64,
+    failed = info.failed,
+    "db.query"
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::http::HeaderMap;
+  use opentelemetry::propagation::TextMapPropagator;
+  use opentelemetry_http::HeaderInjector;
+
+  #[test]
+  fn test_current_trace_id_without_subscriber_is_none() {
+    assert!(current_trace_id().is_none());
+  }
+
+  #[test]
+  fn test_trace_context_round_trip() {
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let mut headers = HeaderMap::new();
+    headers.insert("traceparent", traceparent.parse().unwrap());
+
+    let propagator = TraceContextPropagator::new();
+    let context = propagator.extract(&HeaderExtractor(&headers));
+    assert_eq!(
+      context.span().span_context().trace_id().to_string(),
+      "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+
+    let mut injected = HeaderMap::new();
+    propagator.inject_context(&context, &mut HeaderInjector(&mut injected));
+    assert_eq!(injected.get("traceparent").unwrap(), traceparent);
+  }
+}
@@ -0,0 +1,127 @@
+use std::{
+  ops::Deref,
+  sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use axum::{
+  extract::{FromRequestParts, Request, State},
+  http::request::Parts,
+  middleware::Next,
+  response::Response,
+};
+use sea_orm::DatabaseTransaction;
+
+use crate::{app::AppState, common::api_error::ApiError, database::Db};
+
+/// The database transaction of the request, opened by the `transaction` middleware.
+///
+/// Create the repositories of the request on it, so that every statement of the request is
+/// committed or rolled back together.
+#[derive(Clone)]
+pub struct Tx {
+  txn: Arc<DatabaseTransaction>,
+  after_commit: Arc<Mutex<Vec<AfterCommit>>>,
+}
+
+type AfterCommit = Box<dyn FnOnce() + Send>;
+
+impl Tx {
+  /// Opens a transaction on the primary, for the requests that are not wrapped in the
+  /// `transaction` middleware, e.g. GraphQL mutations.
+  pub async fn begin(db: &Db) -> Result<Self, ApiError> {
+    Ok(Self {
+      txn: Arc::new(db.begin().await?),
+      after_commit: Arc::default(),
+    })
+  }
+
+  /// Commits the transaction and runs its `after_commit` callbacks if `commit`, and rolls it back
+  /// otherwise. Every other handle on the transaction must have been dropped.
+  pub async fn finish(self, commit: bool) -> Result<(), ApiError> {
+    let txn = Arc::try_unwrap(self.txn).map_err(|_| {
+      ApiError::InternalError(anyhow!(
+        "The request transaction is still in use after the response, rolling it back"
+      ))
+    })?;
+
+    if commit {
+      txn.commit().await?;
+      let callbacks =
+        std::mem::take(&mut *self.after_commit.lock().unwrap_or_else(|e| e.into_inner()));
+      for callback in callbacks {
+        callback();
+      }
+    } else {
+      txn.rollback().await?;
+    }
+    Ok(())
+  }
+
+  /// Runs `f` once the transaction is committed, e.g. to publish an event about a change only
+  /// when it is visible to other requests. `f` is dropped if the transaction is rolled back.
+  pub fn after_commit(&self, f: impl FnOnce() + Send + 'static) {
+    self
+      .after_commit
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .push(Box::new(f));
+  }
+}
+
+impl Deref for Tx {
+  type Target = DatabaseTransaction;
+
+  fn deref(&self) -> &Self::Target {
+    &self.txn
+  }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Tx {
+  type Rejection = ApiError;
+
+  async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+    parts.extensions.get::<Tx>().cloned().ok_or_else(|| {
+      ApiError::InternalError(anyhow!(
+        "The route must be wrapped in the transaction middleware to use Tx"
+      ))
+    })
+  }
+}
+
+/// Runs the request in a transaction on the primary, committed if the response is successful and
+/// rolled back otherwise, e.g. when the handler returned an `ApiError`.
+///
+/// Opt-in per route with `route_layer` or `Handler::layer`.
+pub async fn transaction(
+  State(state): State<AppState>,
+  mut req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let tx = Tx::begin(&state.db).await?;
+  req.extensions_mut().insert(tx.clone());
+
+  let response = next.run(req).await;
+
+  // The request and its handles are dropped once the handler has returned.
+  let commit = response.status().is_success() || response.status().is_redirection();
+  if !commit {
+    tracing::debug!(status = %response.status(), "Rolling back the request transaction");
+  }
+  tx.finish(commit).await?;
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::Request;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_tx_requires_the_middleware() {
+    let (mut parts, _) = Request::new(()).into_parts();
+    let result = Tx::from_request_parts(&mut parts, &()).await;
+    assert!(matches!(result, Err(ApiError::InternalError(_))));
+  }
+}
@@ -1,42 +1,263 @@
+use std::{
+  num::NonZeroUsize,
+  sync::{Arc, Mutex},
+};
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use axum::{
   body::Body,
   extract::State,
+  http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
   response::{IntoResponse, Response},
 };
 use base64::{engine::general_purpose, Engine};
 use hyper::StatusCode;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::common::cfg::{basic_auth::Password, BasicAuthConfig, TrustedProxies};
+
+/// Number of verified credentials remembered, so that hashes are not verified on every request.
+const VERIFIED_CAPACITY: usize = 64;
+
+/// Number of credentials of a realm verified at once, each holding a blocking thread while a
+/// password hash is computed.
+const VERIFYING_CONCURRENCY: usize = 4;
+
+/// A realm protected with basic authentication, such as the Swagger UI or GraphiQL.
+pub struct BasicAuth {
+  realm: &'static str,
+  config: Arc<BasicAuthConfig>,
+  trusted_proxies: TrustedProxies,
+  /// Digests of the authorization headers already verified against a hashed password.
+  verified: Mutex<LruCache<[u8; 32], ()>>,
+  verifying: Semaphore,
+}
 
-/// Middleware that applies basic authentication.
+impl BasicAuth {
+  pub fn new(
+    realm: &'static str,
+    config: BasicAuthConfig,
+    trusted_proxies: TrustedProxies,
+  ) -> Arc<Self> {
+    Arc::new(Self {
+      realm,
+      config: Arc::new(config),
+      trusted_proxies,
+      verified: Mutex::new(LruCache::new(NonZeroUsize::new(VERIFIED_CAPACITY).unwrap())),
+      verifying: Semaphore::new(VERIFYING_CONCURRENCY),
+    })
+  }
+
+  /// Returns whether a `Basic` authorization header value holds the credentials of a user.
+  ///
+  /// Hashes are verified on a blocking thread, so that they do not stall the runtime.
+  pub async fn authenticate(&self, auth_str: &str) -> bool {
+    let digest = sha256(auth_str.as_bytes());
+    let remembered = self
+      .verified
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .get(&digest)
+      .is_some();
+    if remembered {
+      return true;
+    }
+
+    let Ok(_permit) = self.verifying.acquire().await else {
+      return false;
+    };
+    let config = self.config.clone();
+    let auth_str = auth_str.to_string();
+    let matched = tokio::task::spawn_blocking(move || credentials_match(&config, &auth_str))
+      .await
+      .unwrap_or(false);
+    if !matched {
+      return false;
+    }
+    self
+      .verified
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .put(digest, ());
+    true
+  }
+}
+
+/// Middleware that applies basic authentication, for use with `from_fn_with_state`.
+///
+/// Clients outside the allowed networks are rejected with 403 Forbidden whatever their
+/// credentials, and failed attempts are logged.
 pub async fn basic_auth_layer(
-  State(state): State<crate::app::AppState>,
+  State(auth): State<Arc<BasicAuth>>,
   req: axum::http::Request<Body>,
   next: axum::middleware::Next,
-) -> Result<Response<Body>, StatusCode> {
-  let auth_header = req.headers().get("authorization");
-
-  if let Some(header_value) = auth_header {
-    if let Ok(auth_str) = header_value.to_str() {
-      if auth_str.starts_with("Basic ") {
-        let encoded = &auth_str[6..];
-        if let Ok(decoded) = general_purpose::STANDARD.decode(encoded) {
-          if let Ok(decoded_str) = String::from_utf8(decoded) {
-            let parts: Vec<&str> = decoded_str.splitn(2, ':').collect();
-            let config_parts: Vec<&str> = state.cfg.graphql_basic_auth.split(':').collect();
-            let username = config_parts[0].to_string();
-            let password = config_parts[1].to_string();
-            if parts.len() == 2 && parts[0] == username && parts[1] == password {
-              return Ok(next.run(req).await);
-            }
-          }
-        }
-      }
-    }
+) -> Response {
+  let client = auth.trusted_proxies.client_ip(&req);
+  if !auth.config.allows(client) {
+    warn!(
+      realm = auth.realm,
+      client = ?client,
+      "Basic auth rejected a client outside the allowed networks"
+    );
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let auth_str = req
+    .headers()
+    .get(AUTHORIZATION)
+    .and_then(|value| value.to_str().ok());
+  match auth_str {
+    Some(auth_str) if auth.authenticate(auth_str).await => return next.run(req).await,
+    Some(auth_str) => warn!(
+      realm = auth.realm,
+      username = username(auth_str).as_deref().unwrap_or(""),
+      client = ?client,
+      "Basic auth failed"
+    ),
+    // Browsers send the credentials once challenged.
+    None => {}
   }
 
   let mut response = StatusCode::UNAUTHORIZED.into_response();
   response.headers_mut().insert(
-    "WWW-Authenticate",
-    "Basic realm=\"Restricted\"".parse().unwrap(),
+    WWW_AUTHENTICATE,
+    format!("Basic realm=\"{}\"", auth.realm).parse().unwrap(),
   );
-  Ok(response)
+  response
+}
+
+/// Checks a `Basic` authorization header value against the users of a realm.
+///
+/// Usernames and plain passwords are compared in constant time, and every user is compared so
+/// that the time taken does not tell which usernames exist. For the same reason, the password of
+/// an unknown username is verified against a hash of the realm, with its algorithm and cost.
+///
+/// Verifying a hash takes tens of milliseconds, see `BasicAuth::authenticate` to do so off the
+/// runtime.
+pub fn credentials_match(config: &BasicAuthConfig, auth_str: &str) -> bool {
+  let Some((username, password)) = decode(auth_str) else {
+    return false;
+  };
+
+  let username = sha256(username.as_bytes());
+  let mut user = None;
+  for candidate in &config.users {
+    if bool::from(sha256(candidate.username.as_bytes())[..].ct_eq(&username[..])) {
+      user = Some(candidate);
+    }
+  }
+
+  match user {
+    Some(user) => verify_password(&password, &user.password),
+    None => {
+      let hashed = config
+        .users
+        .iter()
+        .find(|user| !matches!(user.password, Password::Plain(_)));
+      if let Some(hashed) = hashed {
+        verify_password(&password, &hashed.password);
+      }
+      false
+    }
+  }
+}
+
+fn verify_password(password: &str, expected: &Password) -> bool {
+  match expected {
+    Password::Plain(expected) => sha256(password.as_bytes())[..]
+      .ct_eq(&sha256(expected.expose().as_bytes())[..])
+      .into(),
+    Password::Bcrypt(hash) => bcrypt::verify(password, hash.expose()).unwrap_or(false),
+    Password::Argon2(hash) => PasswordHash::new(hash.expose()).is_ok_and(|hash| {
+      Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok()
+    }),
+  }
+}
+
+/// Decodes the username and password of a `Basic` authorization header value.
+fn decode(auth_str: &str) -> Option<(String, String)> {
+  let encoded = auth_str.strip_prefix("Basic ")?;
+  let decoded = String::from_utf8(general_purpose::STANDARD.decode(encoded).ok()?).ok()?;
+  let (username, password) = decoded.split_once(':')?;
+  Some((username.to_string(), password.to_string()))
+}
+
+fn username(auth_str: &str) -> Option<String> {
+  decode(auth_str).map(|(username, _)| username)
+}
+
+fn sha256(value: &[u8]) -> [u8; 32] {
+  Sha256::digest(value).into()
+}
+
+#[cfg(test)]
+mod tests {
+  use argon2::{password_hash::SaltString, PasswordHasher};
+
+  use super::*;
+  use crate::common::cfg::testing;
+
+  fn header(credentials: &str) -> String {
+    format!("Basic {}", general_purpose::STANDARD.encode(credentials))
+  }
+
+  fn config(users: &str) -> BasicAuthConfig {
+    testing::section(&[("BASIC_AUTH", users)], |values| {
+      BasicAuthConfig::from_values(values, "BASIC_AUTH", false)
+    })
+  }
+
+  #[test]
+  fn test_basic_credentials_match() {
+    let config = config("admin:secret ops:other");
+    assert!(credentials_match(&config, &header("admin:secret")));
+    assert!(credentials_match(&config, &header("ops:other")));
+  }
+
+  #[test]
+  fn test_basic_credentials_mismatch() {
+    let config = config("admin:secret");
+    assert!(!credentials_match(&config, &header("admin:wrong")));
+    assert!(!credentials_match(&config, &header("root:secret")));
+    assert!(!credentials_match(&config, "Bearer token"));
+    assert!(!credentials_match(
+      &BasicAuthConfig::default(),
+      &header("admin:secret")
+    ));
+  }
+
+  #[test]
+  fn test_hashed_passwords() {
+    let bcrypt = bcrypt::hash("bcrypt-secret", 4).unwrap();
+    let salt = SaltString::from_b64("c2FsdHNhbHQ").unwrap();
+    let argon2 = Argon2::default()
+      .hash_password(b"argon2-secret", &salt)
+      .unwrap()
+      .to_string();
+    let config = config(&format!("bob:{bcrypt} carol:{argon2}"));
+
+    assert!(credentials_match(&config, &header("bob:bcrypt-secret")));
+    assert!(credentials_match(&config, &header("carol:argon2-secret")));
+    assert!(!credentials_match(&config, &header("bob:argon2-secret")));
+    assert!(!credentials_match(&config, &header("carol:bcrypt-secret")));
+  }
+
+  #[tokio::test]
+  async fn test_verified_credentials_are_remembered() {
+    let auth = BasicAuth::new(
+      "Test",
+      config(&format!("bob:{}", bcrypt::hash("secret", 4).unwrap())),
+      TrustedProxies::default(),
+    );
+    assert!(!auth.authenticate(&header("bob:wrong")).await);
+    assert!(auth.authenticate(&header("bob:secret")).await);
+    assert_eq!(auth.verified.lock().unwrap().len(), 1);
+    assert!(auth.authenticate(&header("bob:secret")).await);
+  }
 }
@@ -1,5 +1,6 @@
 use tokio::signal;
 
+/// Completes when the process receives Ctrl+C or SIGTERM.
 pub async fn shutdown_signal() {
   let ctrl_c = async {
     signal::ctrl_c()
@@ -23,5 +24,5 @@ pub async fn shutdown_signal() {
     _ = terminate => {},
   }
 
-  println!("Shutdown signal received. Shutting down...");
+  tracing::info!("Shutdown signal received. Shutting down...");
 }
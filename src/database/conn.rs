@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use sea_orm::{
+  ConnectionTrait, DatabaseConnection, DbBackend, DbErr, ExecResult, QueryResult, Statement,
+};
+
+use crate::common::{metrics, transaction::Tx};
+
+/// The connection a repository runs its statements on: a pool, or the transaction of the
+/// request.
+///
+/// The time statements run on a pool wait for a connection is recorded, see
+/// [`metrics::time_db_acquire`].
+#[derive(Clone)]
+pub enum Conn {
+  Pool(DatabaseConnection),
+  Tx(Tx),
+}
+
+impl From<DatabaseConnection> for Conn {
+  fn from(conn: DatabaseConnection) -> Self {
+    Conn::Pool(conn)
+  }
+}
+
+impl From<&DatabaseConnection> for Conn {
+  fn from(conn: &DatabaseConnection) -> Self {
+    Conn::Pool(conn.clone())
+  }
+}
+
+impl From<Tx> for Conn {
+  fn from(tx: Tx) -> Self {
+    Conn::Tx(tx)
+  }
+}
+
+#[async_trait]
+impl ConnectionTrait for Conn {
+  fn get_database_backend(&self) -> DbBackend {
+    match self {
+      Conn::Pool(conn) => conn.get_database_backend(),
+      Conn::Tx(tx) => tx.get_database_backend(),
+    }
+  }
+
+  async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+    match self {
+      Conn::Pool(conn) => metrics::time_db_acquire(conn.execute(stmt)).await,
+      Conn::Tx(tx) => tx.execute(stmt).await,
+    }
+  }
+
+  async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+    match self {
+      Conn::Pool(conn) => conn.execute_unprepared(sql).await,
+      Conn::Tx(tx) => tx.execute_unprepared(sql).await,
+    }
+  }
+
+  async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+    match self {
+      Conn::Pool(conn) => metrics::time_db_acquire(conn.query_one(stmt)).await,
+      Conn::Tx(tx) => tx.query_one(stmt).await,
+    }
+  }
+
+  async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+    match self {
+      Conn::Pool(conn) => metrics::time_db_acquire(conn.query_all(stmt)).await,
+      Conn::Tx(tx) => tx.query_all(stmt).await,
+    }
+  }
+
+  fn support_returning(&self) -> bool {
+    match self {
+      Conn::Pool(conn) => conn.support_returning(),
+      Conn::Tx(tx) => tx.support_returning(),
+    }
+  }
+
+  fn is_mock_connection(&self) -> bool {
+    match self {
+      Conn::Pool(conn) => conn.is_mock_connection(),
+      Conn::Tx(tx) => tx.is_mock_connection(),
+    }
+  }
+}
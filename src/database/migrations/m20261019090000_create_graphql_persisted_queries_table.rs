@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Queries registered with Automatic Persisted Queries, by the hex SHA-256 of their text
+    manager
+      .create_table(
+        Table::create()
+          .table(GraphqlPersistedQueries::Table)
+          .if_not_exists()
+          .col(
+            ColumnDef::new(GraphqlPersistedQueries::Hash)
+              .char_len(64)
+              .not_null()
+              .primary_key(),
+          )
+          .col(
+            ColumnDef::new(GraphqlPersistedQueries::Query)
+              .text()
+              .not_null(),
+          )
+          .col(
+            ColumnDef::new(GraphqlPersistedQueries::CreatedAt)
+              .timestamp_with_time_zone()
+              .not_null()
+              .default(Expr::current_timestamp()),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(
+        Table::drop()
+          .table(GraphqlPersistedQueries::Table)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum GraphqlPersistedQueries {
+  Table,
+  Hash,
+  Query,
+  CreatedAt,
+}
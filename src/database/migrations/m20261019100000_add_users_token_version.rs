@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // Bumped when the password changes, invalidating the tokens issued before
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .add_column(
+            ColumnDef::new(Users::TokenVersion)
+              .integer()
+              .not_null()
+              .default(0),
+          )
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Users::Table)
+          .drop_column(Users::TokenVersion)
+          .to_owned(),
+      )
+      .await
+  }
+}
+
+#[derive(Iden)]
+enum Users {
+  Table,
+  TokenVersion,
+}
@@ -1,12 +1,18 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20240126114845_create_users_table;
+mod m20261019090000_create_graphql_persisted_queries_table;
+mod m20261019100000_add_users_token_version;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
   fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-    vec![Box::new(m20240126114845_create_users_table::Migration)]
+    vec![
+      Box::new(m20240126114845_create_users_table::Migration),
+      Box::new(m20261019090000_create_graphql_persisted_queries_table::Migration),
+      Box::new(m20261019100000_add_users_token_version::Migration),
+    ]
   }
 }
@@ -1,24 +1,106 @@
+pub mod conn;
 pub mod migrations;
+mod replicas;
+pub mod seeders;
 
 use anyhow::Result;
-use sea_orm::{ConnectOptions, Database, DatabaseConnection};
-use sea_orm_migration::MigratorTrait;
-use std::time::Duration;
+use sea_orm::{
+  ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DatabaseTransaction, DbBackend,
+  EntityTrait, Statement, TransactionTrait,
+};
+use sea_orm_migration::{seaql_migrations, MigratorTrait};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::info;
 
-use crate::common::cfg::Config;
-use crate::database::migrations::Migrator;
+use crate::common::{
+  cfg::{Config, Dsn},
+  metrics,
+  shutdown::Shutdown,
+  telemetry,
+};
+use crate::database::{migrations::Migrator, replicas::Replicas};
 
+/// Key of the Postgres advisory lock held while migrations run ("migrate!" in ASCII).
+const MIGRATION_LOCK_KEY: i64 = 0x6d69_6772_6174_6521;
+
+/// The `role` label of the metrics of the primary pool.
+pub const PRIMARY: &str = "primary";
+
+/// The `role` label of the metrics of the replica pools.
+pub const REPLICA: &str = "replica";
+
+/// The connection pools of the primary database and its read replicas.
+///
+/// Writes, and reads that must see them, go to `writer`. Read-only queries that tolerate
+/// replication lag go to `reader`.
 #[derive(Clone)]
 pub struct Db {
-  pub conn: DatabaseConnection,
+  conn: DatabaseConnection,
+  replicas: Arc<Replicas>,
+}
+
+/// A snapshot of the connection pool state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+  /// Number of connections currently open, both idle and in use.
+  pub size: u32,
+  /// Number of idle connections.
+  pub idle: usize,
+  /// Maximum number of connections allowed in the pool.
+  pub max_size: u32,
 }
 
 impl Db {
   // We create a single connection pool for Sea-ORM that is shared across the entire application.
   // This prevents the need to open a new connection for every API call, which would be wasteful.
   pub async fn new(cfg: &Config) -> Result<Self, sea_orm::DbErr> {
-    let mut opt = ConnectOptions::new(cfg.db_dsn.to_owned());
+    let conn = Self::connect(cfg, &cfg.db_dsn, PRIMARY, cfg.db_pool_max_size, false).await?;
+
+    // Replicas are connected lazily, so that an unavailable replica does not prevent startup.
+    let mut replicas = Vec::new();
+    for dsn in &cfg.db_replica_dsns {
+      let replica = Self::connect(cfg, dsn, REPLICA, cfg.db_pool_max_size, true).await?;
+      replicas.push((dsn.clone(), replica));
+    }
+    let replicas = Replicas::new(replicas);
+    replicas.check().await;
+
+    Ok(Self {
+      conn,
+      replicas: Arc::new(replicas),
+    })
+  }
+
+  /// Connects with the migration DSN if one is configured, otherwise with the application DSN.
+  ///
+  /// Migrations run in a single transaction, so one connection is enough.
+  pub async fn for_migrations(cfg: &Config) -> Result<Self, sea_orm::DbErr> {
+    let dsn = cfg.db_migration_dsn.as_ref().unwrap_or(&cfg.db_dsn);
+    Ok(Self {
+      conn: Self::connect(cfg, dsn, PRIMARY, 1, false).await?,
+      replicas: Arc::default(),
+    })
+  }
+
+  /// A `Db` that is not connected, for tests that do not reach the database.
+  #[cfg(test)]
+  pub(crate) fn disconnected() -> Self {
+    Self {
+      conn: DatabaseConnection::Disconnected,
+      replicas: Arc::default(),
+    }
+  }
+
+  async fn connect(
+    cfg: &Config,
+    dsn: &Dsn,
+    role: &'static str,
+    max_connections: u32,
+    lazy: bool,
+  ) -> Result<DatabaseConnection, sea_orm::DbErr> {
+    let mut opt = ConnectOptions::new(dsn.expose());
 
     // Set connection timeout from environment variable
     opt
@@ -27,21 +109,209 @@ impl Db {
       .idle_timeout(Duration::from_secs(600))
       // Set max lifetime to 30 minutes
       .max_lifetime(Duration::from_secs(1800))
-      // Set max connections from environment variable
-      .max_connections(cfg.db_pool_max_size)
+      .max_connections(max_connections)
       // Set min connections to 1
-      .min_connections(1);
+      .min_connections(1)
+      .connect_lazy(lazy);
 
-    info!("Database connection options: {:?}", opt);
+    // The options are not logged as is, since they contain the password.
+    info!(
+      dsn = ?dsn,
+      max_connections,
+      connect_timeout = ?opt.get_connect_timeout(),
+      "Database connection options"
+    );
     info!("Connecting to database...");
-    let conn = Database::connect(opt).await?;
-    Ok(Self { conn })
+    let mut conn = Database::connect(opt).await?;
+
+    // Record every statement as a span of the request that issued it, and report it so that the
+    // time spent acquiring its connection is recorded.
+    conn.set_metric_callback(move |info| {
+      telemetry::record_db_query(info);
+      metrics::report_db_statement(role, info.elapsed);
+    });
+
+    Ok(conn)
+  }
+
+  /// Returns the connection to the primary, for writes and reads that must see them.
+  pub fn writer(&self) -> &DatabaseConnection {
+    &self.conn
+  }
+
+  /// Returns a connection for read-only queries: the next healthy replica, or the primary if
+  /// there is none.
+  ///
+  /// Replicas lag behind the primary, so a request that just wrote should read with `writer`.
+  pub fn reader(&self) -> &DatabaseConnection {
+    self.replicas.pick().unwrap_or(&self.conn)
+  }
+
+  /// Whether read replicas are configured.
+  pub fn has_replicas(&self) -> bool {
+    !self.replicas.is_empty()
   }
 
-  pub async fn run_migrations(&self) -> Result<(), sea_orm::DbErr> {
+  /// Checks the health of the replicas every `interval` until shutdown, so that `reader` skips
+  /// the unhealthy ones.
+  pub async fn monitor_replicas(self, interval: Duration, shutdown: Shutdown) {
+    self.replicas.monitor(interval, shutdown).await
+  }
+
+  /// Applies pending migrations, at most `steps` if given.
+  ///
+  /// Every instance may call this on startup: the migration lock makes the others wait, and they
+  /// find nothing left to apply once they get it.
+  pub async fn run_migrations(&self, steps: Option<u32>) -> Result<(), sea_orm::DbErr> {
     // This integrates database migrations into the application binary to ensure the database
     // is properly migrated during startup.
-    Migrator::up(&self.conn, None).await?;
-    Ok(())
+    let txn = self.migration_transaction().await?;
+    let pending = Migrator::get_pending_migrations(&txn).await?;
+    if pending.is_empty() {
+      info!("No pending migrations");
+    }
+
+    let steps = steps.map_or(pending.len(), |steps| steps as usize);
+    for migration in pending.iter().take(steps) {
+      let start = Instant::now();
+      // Each migration is applied in a savepoint of the transaction holding the lock.
+      Migrator::up(&txn, Some(1)).await?;
+      info!(
+        migration = migration.name(),
+        duration = ?start.elapsed(),
+        "Applied migration"
+      );
+    }
+    txn.commit().await
+  }
+
+  /// Begins a transaction holding the migration lock, waiting for other instances to release it.
+  ///
+  /// The lock is released when the transaction ends, including when the connection is lost.
+  pub async fn migration_transaction(&self) -> Result<DatabaseTransaction, sea_orm::DbErr> {
+    let txn = self.conn.begin().await?;
+    info!("Acquiring the migration lock");
+    let start = Instant::now();
+    txn
+      .execute_unprepared(&format!(
+        "SELECT pg_advisory_xact_lock({MIGRATION_LOCK_KEY})"
+      ))
+      .await?;
+    info!(waited = ?start.elapsed(), "Acquired the migration lock");
+    Ok(txn)
+  }
+
+  /// Returns the names of the migrations that are not applied yet.
+  ///
+  /// Unlike the migrator, this does not create the migrations table, so it works with a role that
+  /// cannot change the schema. Every migration is pending on a database without the table.
+  pub async fn pending_migrations(&self) -> Result<Vec<String>, sea_orm::DbErr> {
+    let applied: HashSet<String> = applied_migrations(&self.conn).await?.into_iter().collect();
+
+    Ok(
+      Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .filter(|name| !applied.contains(name))
+        .collect(),
+    )
+  }
+
+  /// Returns the name of the last applied migration, including the ones this binary does not know
+  /// about. Like `pending_migrations`, this does not change the schema, and it reads from a
+  /// reader.
+  pub async fn last_migration(&self) -> Result<Option<String>, sea_orm::DbErr> {
+    // Migration names start with their timestamp, so they sort in the order they were written.
+    Ok(applied_migrations(self.reader()).await?.into_iter().max())
+  }
+
+  /// Closes all connections of the pool, waiting for the ones in use to be released.
+  pub async fn close(&self) -> Result<(), sea_orm::DbErr> {
+    self.replicas.close().await?;
+    self.conn.close_by_ref().await
+  }
+
+  /// Returns the current state of the connection pools by role, the replicas being added up,
+  /// without acquiring a connection.
+  pub fn pool_stats(&self) -> Vec<(&'static str, PoolStats)> {
+    let mut stats = vec![(PRIMARY, PoolStats::of(&self.conn))];
+    if self.has_replicas() {
+      let replicas = self.replicas.connections().map(PoolStats::of).fold(
+        PoolStats::default(),
+        |total, stats| PoolStats {
+          size: total.size + stats.size,
+          idle: total.idle + stats.idle,
+          max_size: total.max_size + stats.max_size,
+        },
+      );
+      stats.push((REPLICA, replicas));
+    }
+    stats
+  }
+
+  /// Begins a transaction on the primary, recording how long the connection took to acquire.
+  pub async fn begin(&self) -> Result<DatabaseTransaction, sea_orm::DbErr> {
+    let start = Instant::now();
+    let txn = self.conn.begin().await?;
+    metrics::record_db_acquire(PRIMARY, start.elapsed());
+    Ok(txn)
+  }
+}
+
+/// Returns the names of the migrations applied to the database of `conn`, none if the migrations
+/// table does not exist.
+async fn applied_migrations(conn: &DatabaseConnection) -> Result<Vec<String>, sea_orm::DbErr> {
+  let table = Migrator::migration_table_name().to_string();
+  let exists = conn
+    .query_one(Statement::from_sql_and_values(
+      DbBackend::Postgres,
+      "SELECT to_regclass($1) IS NOT NULL AS exists",
+      [table.into()],
+    ))
+    .await?
+    .map(|row| row.try_get::<bool>("", "exists"))
+    .transpose()?
+    .unwrap_or(false);
+  if !exists {
+    return Ok(Vec::new());
+  }
+
+  Ok(
+    seaql_migrations::Entity::find()
+      .all(conn)
+      .await?
+      .into_iter()
+      .map(|model| model.version)
+      .collect(),
+  )
+}
+
+impl PoolStats {
+  /// The state of the pool of `conn`, empty if it is not a Postgres pool, e.g. a mock.
+  fn of(conn: &DatabaseConnection) -> Self {
+    match conn {
+      DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+        let pool = conn.get_postgres_connection_pool();
+        Self {
+          size: pool.size(),
+          idle: pool.num_idle(),
+          max_size: pool.options().get_max_connections(),
+        }
+      }
+      _ => Self::default(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pool_stats_of_a_connection_that_is_not_postgres_are_empty() {
+    assert_eq!(
+      Db::disconnected().pool_stats(),
+      vec![(PRIMARY, PoolStats::default())]
+    );
   }
 }
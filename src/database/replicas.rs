@@ -0,0 +1,130 @@
+use std::{
+  sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+  time::Duration,
+};
+
+use sea_orm::DatabaseConnection;
+use tracing::{info, warn};
+
+use crate::common::{cfg::Dsn, shutdown::Shutdown};
+
+/// Read replicas, used in turn while they are healthy.
+#[derive(Default)]
+pub(crate) struct Replicas {
+  members: Vec<Replica>,
+  next: AtomicUsize,
+}
+
+struct Replica {
+  conn: DatabaseConnection,
+  dsn: Dsn,
+  healthy: AtomicBool,
+}
+
+impl Replicas {
+  /// Replicas are assumed healthy until checked, so that the first check reports the failing ones.
+  pub(crate) fn new(members: Vec<(Dsn, DatabaseConnection)>) -> Self {
+    Self {
+      members: members
+        .into_iter()
+        .map(|(dsn, conn)| Replica {
+          conn,
+          dsn,
+          healthy: AtomicBool::new(true),
+        })
+        .collect(),
+      next: AtomicUsize::new(0),
+    }
+  }
+
+  pub(crate) fn is_empty(&self) -> bool {
+    self.members.is_empty()
+  }
+
+  /// Returns the connections of every replica, healthy or not.
+  pub(crate) fn connections(&self) -> impl Iterator<Item = &DatabaseConnection> {
+    self.members.iter().map(|replica| &replica.conn)
+  }
+
+  /// Returns the next healthy replica in round-robin order, if any.
+  pub(crate) fn pick(&self) -> Option<&DatabaseConnection> {
+    self.pick_index().map(|index| &self.members[index].conn)
+  }
+
+  fn pick_index(&self) -> Option<usize> {
+    let len = self.members.len();
+    if len == 0 {
+      return None;
+    }
+    let start = self.next.fetch_add(1, Ordering::Relaxed);
+    (0..len)
+      .map(|offset| (start + offset) % len)
+      .find(|&index| self.members[index].healthy.load(Ordering::Relaxed))
+  }
+
+  /// Pings every replica, logging the ones that changed state.
+  pub(crate) async fn check(&self) {
+    for replica in &self.members {
+      let healthy = replica.conn.ping().await.is_ok();
+      if replica.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+        if healthy {
+          info!(dsn = ?replica.dsn, "Read replica is healthy");
+        } else {
+          warn!(dsn = ?replica.dsn, "Read replica is unhealthy, reading from the primary");
+        }
+      }
+    }
+  }
+
+  /// Checks the replicas every `interval` until shutdown.
+  pub(crate) async fn monitor(&self, interval: Duration, shutdown: Shutdown) {
+    let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+      tokio::select! {
+        _ = shutdown.cancelled() => return,
+        _ = ticker.tick() => self.check().await,
+      }
+    }
+  }
+
+  pub(crate) async fn close(&self) -> Result<(), sea_orm::DbErr> {
+    for replica in &self.members {
+      replica.conn.close_by_ref().await?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn replicas(count: usize) -> Replicas {
+    Replicas::new(
+      (0..count)
+        .map(|_| (Dsn::default(), DatabaseConnection::Disconnected))
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn test_round_robin_over_healthy_replicas() {
+    let replicas = replicas(3);
+    replicas.members[1].healthy.store(false, Ordering::Relaxed);
+
+    let picked: Vec<_> = (0..4).map(|_| replicas.pick_index().unwrap()).collect();
+    assert_eq!(picked, vec![0, 2, 2, 0]);
+  }
+
+  #[test]
+  fn test_no_healthy_replica() {
+    assert!(replicas(0).pick().is_none());
+
+    let replicas = replicas(2);
+    for replica in &replicas.members {
+      replica.healthy.store(false, Ordering::Relaxed);
+    }
+    assert!(replicas.pick().is_none());
+  }
+}
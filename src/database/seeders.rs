@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc, time::Instant};
+
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use sea_orm::DatabaseConnection;
+use serde::de::DeserializeOwned;
+use tracing::info;
+
+/// Inserts data a module needs to be usable, e.g. an initial admin.
+///
+/// Modules implement this trait and register their seeders in the `SeederRegistry`. Seeders must
+/// be idempotent: running them again must not duplicate or overwrite existing records.
+#[async_trait]
+pub trait Seeder: Send + Sync {
+  /// The name used to select the seeder on the command line.
+  fn name(&self) -> &'static str;
+
+  /// Inserts the missing records.
+  async fn seed(&self, db: &DatabaseConnection, fixtures: &Fixtures) -> anyhow::Result<()>;
+}
+
+/// Holds the registered seeders, run in registration order.
+#[derive(Clone, Default)]
+pub struct SeederRegistry {
+  seeders: Vec<Arc<dyn Seeder>>,
+}
+
+impl SeederRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers a seeder.
+  pub fn register(mut self, seeder: impl Seeder + 'static) -> Self {
+    self.seeders.push(Arc::new(seeder));
+    self
+  }
+
+  /// Returns the names of the registered seeders.
+  pub fn names(&self) -> Vec<&'static str> {
+    self.seeders.iter().map(|seeder| seeder.name()).collect()
+  }
+
+  /// Runs the seeders named in `only`, or all of them if it is empty.
+  pub async fn run(
+    &self,
+    db: &DatabaseConnection,
+    fixtures: &Fixtures,
+    only: &[String],
+  ) -> anyhow::Result<()> {
+    let names = self.names();
+    if let Some(unknown) = only.iter().find(|name| !names.contains(&name.as_str())) {
+      bail!(
+        "Unknown seeder \"{}\", expected one of: {}",
+        unknown,
+        names.join(", ")
+      );
+    }
+
+    for seeder in &self.seeders {
+      if !only.is_empty() && !only.iter().any(|name| name == seeder.name()) {
+        continue;
+      }
+
+      let start = Instant::now();
+      seeder
+        .seed(db, fixtures)
+        .await
+        .with_context(|| format!("Seeder \"{}\" failed", seeder.name()))?;
+      info!(
+        seeder = seeder.name(),
+        duration = ?start.elapsed(),
+        "Seeder completed"
+      );
+    }
+    Ok(())
+  }
+}
+
+/// Fixture files keyed by their name without extension, e.g. `users` for `fixtures/users.yaml`.
+///
+/// Each file holds a list of records in YAML (`.yaml`, `.yml`) or JSON (`.json`).
+#[derive(Debug, Default)]
+pub struct Fixtures {
+  files: HashMap<String, serde_json::Value>,
+}
+
+impl Fixtures {
+  /// Loads every fixture file in `dir`. A missing directory holds no fixtures.
+  pub fn load(dir: &Path) -> anyhow::Result<Self> {
+    let mut fixtures = Fixtures::default();
+    if !dir.exists() {
+      return Ok(fixtures);
+    }
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Unable to read {}", dir.display()))? {
+      let path = entry?.path();
+      let (Some(name), Some(extension)) = (
+        path.file_stem().and_then(|name| name.to_str()),
+        path.extension().and_then(|extension| extension.to_str()),
+      ) else {
+        continue;
+      };
+      if !matches!(extension, "yaml" | "yml" | "json") {
+        continue;
+      }
+
+      let content =
+        fs::read_to_string(&path).with_context(|| format!("Unable to read {}", path.display()))?;
+      let value = parse(extension, &content)
+        .with_context(|| format!("Invalid fixture file {}", path.display()))?;
+      fixtures.insert(name, value)?;
+    }
+    Ok(fixtures)
+  }
+
+  /// Adds the records of the fixture `name`.
+  pub fn insert(&mut self, name: &str, value: serde_json::Value) -> anyhow::Result<()> {
+    if self.files.insert(name.to_string(), value).is_some() {
+      bail!("Fixture \"{}\" is defined by more than one file", name);
+    }
+    Ok(())
+  }
+
+  /// Returns the records of the fixture `name`, none if there is no such fixture.
+  pub fn get<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<Vec<T>> {
+    match self.files.get(name) {
+      Some(value) => serde_json::from_value(value.clone())
+        .map_err(|e| anyhow!("Invalid records in fixture \"{}\": {}", name, e)),
+      None => Ok(Vec::new()),
+    }
+  }
+}
+
+fn parse(extension: &str, content: &str) -> anyhow::Result<serde_json::Value> {
+  Ok(match extension {
+    "json" => serde_json::from_str(content)?,
+    _ => serde_yaml::from_str(content)?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(serde::Deserialize, Debug, PartialEq)]
+  struct Record {
+    name: String,
+  }
+
+  #[test]
+  fn test_yaml_and_json_fixtures() {
+    let mut fixtures = Fixtures::default();
+    fixtures
+      .insert(
+        "a",
+        parse("yaml", "- name: first\n- name: second\n").unwrap(),
+      )
+      .unwrap();
+    fixtures
+      .insert("b", parse("json", r#"[{"name": "third"}]"#).unwrap())
+      .unwrap();
+
+    let a: Vec<Record> = fixtures.get("a").unwrap();
+    assert_eq!(a.len(), 2);
+    assert_eq!(a[1].name, "second");
+    let b: Vec<Record> = fixtures.get("b").unwrap();
+    assert_eq!(
+      b,
+      vec![Record {
+        name: "third".to_string()
+      }]
+    );
+    assert!(fixtures.get::<Record>("missing").unwrap().is_empty());
+
+    assert!(fixtures.insert("a", serde_json::json!([])).is_err());
+  }
+
+  struct Noop;
+
+  #[async_trait]
+  impl Seeder for Noop {
+    fn name(&self) -> &'static str {
+      "noop"
+    }
+
+    async fn seed(&self, _: &DatabaseConnection, _: &Fixtures) -> anyhow::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[tokio::test]
+  async fn test_unknown_seeder_is_rejected() {
+    let registry = SeederRegistry::new().register(Noop);
+    let db = DatabaseConnection::Disconnected;
+    let fixtures = Fixtures::default();
+
+    assert!(registry.run(&db, &fixtures, &[]).await.is_ok());
+    let err = registry
+      .run(&db, &fixtures, &["users".to_string()])
+      .await
+      .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Unknown seeder \"users\", expected one of: noop"
+    );
+  }
+}
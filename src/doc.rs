@@ -4,6 +4,9 @@ use utoipa::{
 };
 use utoipauto::utoipauto;
 
+use crate::common::cfg::SessionCookieConfig;
+
+/// The specification of the handlers, see [`openapi`] for the one that is served.
 #[utoipauto]
 #[derive(OpenApi)]
 #[openapi(
@@ -11,6 +14,25 @@ use utoipauto::utoipauto;
 )]
 pub struct ApiDoc;
 
+/// Returns the specification with the session cookie named as `cookies` name it.
+pub fn openapi(cookies: &SessionCookieConfig) -> utoipa::openapi::OpenApi {
+  let mut openapi = ApiDoc::openapi();
+  // We can unwrap safely since there already is components registered.
+  let components = openapi.components.as_mut().unwrap();
+
+  // Add the session cookie of the cookie login to the OpenAPI components.
+  components.add_security_scheme(
+    "sessionCookie",
+    SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(cookies.session_name()))),
+  );
+  openapi
+}
+
+/// Serializes the specification as YAML.
+pub fn to_yaml(openapi: &utoipa::openapi::OpenApi) -> Result<String, serde_yaml::Error> {
+  serde_yaml::to_string(openapi)
+}
+
 struct SecurityAddon;
 
 impl Modify for SecurityAddon {
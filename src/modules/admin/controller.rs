@@ -0,0 +1,76 @@
+use axum::{extract::State, Json};
+
+use crate::{
+  app::AppState,
+  common::api_error::{ApiError, ApiErrorResp},
+  modules::admin::{
+    dto::{LogFilterUpdate, SettingsDto},
+    service,
+  },
+};
+
+#[utoipa::path(
+  get,
+  tag = "Admin",
+  path = "/api/v1/admin/settings",
+  operation_id = "adminSettings",
+  responses(
+      (status = 200, description = "Current runtime settings", body = SettingsDto),
+      (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+      (status = 403, description = "Admin role required", body = ApiErrorResp)
+  ),
+  security(
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
+  )
+)]
+pub async fn settings(State(state): State<AppState>) -> Result<Json<SettingsDto>, ApiError> {
+  let result = service::settings(&state.settings);
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  post,
+  tag = "Admin",
+  path = "/api/v1/admin/settings/reload",
+  operation_id = "adminSettingsReload",
+  responses(
+      (status = 200, description = "Runtime settings reloaded from the configuration sources", body = SettingsDto),
+      (status = 400, description = "The new configuration is invalid, the current settings are kept", body = ApiErrorResp),
+      (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+      (status = 403, description = "Admin role required", body = ApiErrorResp)
+  ),
+  security(
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
+  )
+)]
+pub async fn reload(State(state): State<AppState>) -> Result<Json<SettingsDto>, ApiError> {
+  let result = service::reload(&state.settings)?;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  put,
+  tag = "Admin",
+  path = "/api/v1/admin/log-filter",
+  operation_id = "adminLogFilter",
+  request_body = LogFilterUpdate,
+  responses(
+      (status = 200, description = "Log filter changed until the next reload", body = SettingsDto),
+      (status = 400, description = "Invalid log filter directives", body = ApiErrorResp),
+      (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+      (status = 403, description = "Admin role required", body = ApiErrorResp)
+  ),
+  security(
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
+  )
+)]
+pub async fn set_log_filter(
+  State(state): State<AppState>,
+  Json(req): Json<LogFilterUpdate>,
+) -> Result<Json<SettingsDto>, ApiError> {
+  let result = service::set_log_filter(&state.settings, &req.directives)?;
+  Ok(Json(result))
+}
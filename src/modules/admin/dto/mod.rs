@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::cfg::RuntimeSettings;
+
+/// The settings that can be changed without a restart.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SettingsDto {
+  pub log_filter: String,
+  pub cors_allowed_origins: Vec<String>,
+  pub rate_limit_per_second: u32,
+  pub rate_limit_burst: u32,
+}
+
+impl From<&RuntimeSettings> for SettingsDto {
+  fn from(settings: &RuntimeSettings) -> Self {
+    Self {
+      log_filter: settings.log_filter.clone(),
+      cors_allowed_origins: settings.cors_allowed_origins.clone(),
+      rate_limit_per_second: settings.rate_limit_per_second,
+      rate_limit_burst: settings.rate_limit_burst,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LogFilterUpdate {
+  /// `RUST_LOG`-style directives, e.g. "server=debug,tower_http=info"
+  pub directives: String,
+}
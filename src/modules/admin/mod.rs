@@ -0,0 +1,25 @@
+pub mod controller;
+pub mod dto;
+pub mod service;
+
+use axum::{
+  extract::State,
+  routing::{get, post, put},
+  Router,
+};
+
+use crate::app::AppState;
+use crate::modules::auth::guards::{admin_guard, auth_guard};
+
+pub fn router(State(state): State<AppState>) -> axum::Router<AppState> {
+  Router::new()
+    .nest(
+      "/v1/admin",
+      Router::new()
+        .route("/settings", get(controller::settings))
+        .route("/settings/reload", post(controller::reload))
+        .route("/log-filter", put(controller::set_log_filter)),
+    )
+    .layer(axum::middleware::from_fn(admin_guard))
+    .layer(axum::middleware::from_fn_with_state(state, auth_guard))
+}
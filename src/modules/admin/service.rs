@@ -0,0 +1,45 @@
+use crate::common::{api_error::ApiError, settings::Settings};
+use crate::modules::admin::dto::SettingsDto;
+
+pub fn settings(settings: &Settings) -> SettingsDto {
+  SettingsDto::from(&*settings.current())
+}
+
+pub fn reload(settings: &Settings) -> Result<SettingsDto, ApiError> {
+  settings
+    .reload()
+    .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+  tracing::info!(settings = ?settings.current(), "Runtime settings reloaded on request");
+  Ok(SettingsDto::from(&*settings.current()))
+}
+
+pub fn set_log_filter(settings: &Settings, directives: &str) -> Result<SettingsDto, ApiError> {
+  settings
+    .set_log_filter(directives)
+    .map_err(|e| ApiError::InvalidRequest(format!("Invalid log filter: {}", e)))?;
+  tracing::info!("Log filter changed to \"{}\"", directives);
+  Ok(SettingsDto::from(&*settings.current()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::cfg::RuntimeSettings;
+
+  #[test]
+  fn test_set_log_filter() {
+    let settings = Settings::new(RuntimeSettings {
+      log_filter: "info".to_string(),
+      cors_allowed_origins: vec!["*".to_string()],
+      rate_limit_per_second: 0,
+      rate_limit_burst: 50,
+    });
+
+    let result = set_log_filter(&settings, "server=debug").unwrap();
+    assert_eq!(result.log_filter, "server=debug");
+
+    let err = set_log_filter(&settings, "server=loud").unwrap_err();
+    assert!(matches!(err, ApiError::InvalidRequest(_)));
+    assert_eq!(settings.current().log_filter, "server=debug");
+  }
+}
@@ -1,10 +1,17 @@
-use axum::{extract::State, Json};
-use serde_json::Value;
+use axum::{
+  extract::State,
+  http::{HeaderMap, Method, StatusCode},
+  Json,
+};
+use axum_extra::extract::cookie::CookieJar;
 
 use crate::app::AppState;
-use crate::common::api_error::ApiError;
-use crate::modules::auth::dto::{AuthResponse, LoginRequest, RegisterRequest};
-use crate::modules::auth::service;
+use crate::common::{
+  api_error::{ApiError, ApiErrorResp},
+  transaction::Tx,
+};
+use crate::modules::auth::dto::{AuthResponse, LoginRequest, RegisterRequest, SessionResponse};
+use crate::modules::auth::{service, session};
 
 #[utoipa::path(
   post,
@@ -14,15 +21,19 @@ use crate::modules::auth::service;
   request_body = RegisterRequest,
   responses(
     (status = 200, description = "Register successful", body = AuthResponse),
-    (status = 409, description = "Email already exists"),
-    (status = 500, description = "Internal server error")
+    (status = 400, description = "Invalid payload", body = ApiErrorResp),
+    (status = 409, description = "Email already exists", body = ApiErrorResp),
+    (status = 500, description = "Internal server error", body = ApiErrorResp)
   )
 )]
 pub async fn register(
   State(state): State<AppState>,
+  tx: Tx,
   Json(req): Json<RegisterRequest>,
-) -> Result<Json<Value>, ApiError> {
-  let result = service::register(&state.db.conn, req).await?;
+) -> Result<Json<AuthResponse>, ApiError> {
+  let events = state.user_events.in_transaction(&tx);
+  let users = state.repositories.users(tx.into());
+  let result = service::register(&*users, &events, state.cfg.jwt_secret.expose(), req).await?;
   Ok(Json(result))
 }
 
@@ -34,14 +45,64 @@ pub async fn register(
   request_body = LoginRequest,
   responses(
     (status = 200, description = "Login successful", body = AuthResponse),
-    (status = 401, description = "Invalid credentials"),
-    (status = 500, description = "Internal server error")
+    (status = 400, description = "Invalid payload or credentials", body = ApiErrorResp),
+    (status = 500, description = "Internal server error", body = ApiErrorResp)
   )
 )]
 pub async fn login(
   State(state): State<AppState>,
   Json(req): Json<LoginRequest>,
-) -> Result<Json<Value>, ApiError> {
-  let result = service::login(&state.db.conn, req).await?;
+) -> Result<Json<AuthResponse>, ApiError> {
+  let users = state.repositories.users(state.db.writer().into());
+  let result = service::login(&*users, state.cfg.jwt_secret.expose(), req).await?;
   Ok(Json(result))
 }
+
+#[utoipa::path(
+  post,
+  tag = "Auth",
+  path = "/api/v1/auth/session",
+  operation_id = "authSessionCreate",
+  request_body = LoginRequest,
+  responses(
+    (status = 200, description = "Login successful, the session and CSRF cookies are set", body = SessionResponse),
+    (status = 400, description = "Invalid payload or credentials", body = ApiErrorResp),
+    (status = 500, description = "Internal server error", body = ApiErrorResp)
+  )
+)]
+pub async fn create_session(
+  State(state): State<AppState>,
+  jar: CookieJar,
+  Json(req): Json<LoginRequest>,
+) -> Result<(CookieJar, Json<SessionResponse>), ApiError> {
+  let users = state.repositories.users(state.db.writer().into());
+  let secret = state.cfg.jwt_secret.expose();
+  let AuthResponse { token, user } = service::login(&*users, secret, req).await?;
+  let csrf_token = session::csrf_token(&token, secret);
+  let jar = session::start(jar, &state.cfg.session_cookie, token, csrf_token.clone());
+  Ok((jar, Json(SessionResponse { csrf_token, user })))
+}
+
+#[utoipa::path(
+  delete,
+  tag = "Auth",
+  path = "/api/v1/auth/session",
+  operation_id = "authSessionDestroy",
+  responses(
+    (status = 204, description = "Logout successful, the session and CSRF cookies are removed"),
+    (status = 403, description = "Invalid CSRF token", body = ApiErrorResp)
+  )
+)]
+pub async fn destroy_session(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+  jar: CookieJar,
+) -> Result<(CookieJar, StatusCode), ApiError> {
+  let cookies = &state.cfg.session_cookie;
+  // Other sites could otherwise log the browser out.
+  if session::token(&jar, cookies).is_some() {
+    let secret = state.cfg.jwt_secret.expose();
+    session::verify_csrf(&Method::DELETE, &headers, &jar, cookies, secret)?;
+  }
+  Ok((session::end(jar, cookies), StatusCode::NO_CONTENT))
+}
@@ -16,12 +16,27 @@ pub struct RegisterRequest {
   pub name: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+  pub current_password: String,
+  pub new_password: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthResponse {
   pub token: String,
   pub user: UserDto,
 }
 
+/// The response of the cookie login, whose token is only sent in the session cookie.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionResponse {
+  /// The CSRF token to send in the `x-csrf-token` header of state-changing requests, also set
+  /// in the CSRF cookie.
+  pub csrf_token: String,
+  pub user: UserDto,
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -0,0 +1,76 @@
+use async_graphql::{
+  dynamic::{Field, FieldFuture, FieldValue, InputValue, Object, TypeRef},
+  to_value,
+};
+
+use crate::modules::auth::{
+  dto::{LoginRequest, RegisterRequest},
+  service,
+};
+use crate::modules::users::graphql::{events, json_field, repository, USER};
+use crate::query_root::Services;
+
+/// The type of the result of `login` and `register`, mirroring `AuthResponse`.
+pub const AUTH_PAYLOAD: &str = "AuthPayload";
+
+/// The `AuthPayload` type, resolved from a serialized `AuthResponse`.
+pub fn auth_payload_object() -> Object {
+  Object::new(AUTH_PAYLOAD)
+    .field(json_field(
+      "token",
+      "token",
+      TypeRef::named_nn(TypeRef::STRING),
+    ))
+    .field(json_field("user", "user", TypeRef::named_nn(USER)))
+}
+
+/// The mutations logging users in, open to anonymous requests.
+pub fn mutation_fields() -> Vec<Field> {
+  vec![
+    Field::new("login", TypeRef::named_nn(AUTH_PAYLOAD), |ctx| {
+      FieldFuture::new(async move {
+        let services = ctx.data::<Services>()?;
+        let req = LoginRequest {
+          email: ctx.args.try_get("email")?.string()?.to_string(),
+          password: ctx.args.try_get("password")?.string()?.to_string(),
+        };
+        let response = service::login(
+          repository(&ctx)?.as_ref(),
+          services.jwt_secret.expose(),
+          req,
+        )
+        .await?;
+        Ok(Some(FieldValue::value(to_value(response)?)))
+      })
+    })
+    .argument(InputValue::new("email", TypeRef::named_nn(TypeRef::STRING)))
+    .argument(InputValue::new(
+      "password",
+      TypeRef::named_nn(TypeRef::STRING),
+    )),
+    Field::new("register", TypeRef::named_nn(AUTH_PAYLOAD), |ctx| {
+      FieldFuture::new(async move {
+        let services = ctx.data::<Services>()?;
+        let req = RegisterRequest {
+          email: ctx.args.try_get("email")?.string()?.to_string(),
+          password: ctx.args.try_get("password")?.string()?.to_string(),
+          name: ctx.args.try_get("name")?.string()?.to_string(),
+        };
+        let response = service::register(
+          repository(&ctx)?.as_ref(),
+          &events(&ctx)?,
+          services.jwt_secret.expose(),
+          req,
+        )
+        .await?;
+        Ok(Some(FieldValue::value(to_value(response)?)))
+      })
+    })
+    .argument(InputValue::new("email", TypeRef::named_nn(TypeRef::STRING)))
+    .argument(InputValue::new(
+      "password",
+      TypeRef::named_nn(TypeRef::STRING),
+    ))
+    .argument(InputValue::new("name", TypeRef::named_nn(TypeRef::STRING))),
+  ]
+}
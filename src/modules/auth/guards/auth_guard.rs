@@ -1,10 +1,18 @@
 use axum::extract::State;
-use axum::{extract::Request, middleware::Next, response::Response};
+use axum::{
+  extract::Request,
+  http::{header::AUTHORIZATION, HeaderMap, Method},
+  middleware::Next,
+  response::Response,
+};
+use axum_extra::extract::cookie::CookieJar;
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::app::AppState;
 use crate::common::api_error::ApiError;
+use crate::modules::auth::session;
 use crate::modules::users::dto::UserDto;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -13,30 +21,121 @@ pub struct Claims {
   pub exp: usize,
   pub iat: usize,
   pub user: UserDto,
+  /// The token version of the user when the token was issued.
+  #[serde(default)]
+  pub ver: i32,
 }
 
+/// Authenticates requests with the bearer token of the authorization header or, for browsers,
+/// with the session cookie, in which case state-changing requests must carry the CSRF token.
 pub async fn auth_guard(
-  State(_): State<AppState>,
+  State(state): State<AppState>,
   req: Request,
   next: Next,
 ) -> Result<Response, ApiError> {
-  // Get the authorization header
-  let auth_header = req
-    .headers()
-    .get("authorization")
+  let user = authenticate(&state, req.method(), req.headers()).await?;
+
+  // Add user role to request extensions for GraphQL context
+  let mut req = req;
+  req.extensions_mut().insert(user);
+
+  Ok(next.run(req).await)
+}
+
+/// Returns the user of the bearer token or of the session cookie of a request, as `auth_guard`
+/// authenticates it.
+pub async fn authenticate(
+  state: &AppState,
+  method: &Method,
+  headers: &HeaderMap,
+) -> Result<UserDto, ApiError> {
+  let secret = state.cfg.jwt_secret.expose();
+  let claims = match headers.get(AUTHORIZATION) {
+    Some(auth_header) => {
+      let auth_header = auth_header
+        .to_str()
+        .map_err(|_| ApiError::Unauthorized("Invalid authorization header".to_string()))?;
+      decode_token(bearer_token(auth_header)?, secret)?
+    }
+    None => {
+      let cookies = &state.cfg.session_cookie;
+      let jar = CookieJar::from_headers(headers);
+      let token = session::token(&jar, cookies).ok_or_else(|| {
+        ApiError::Unauthorized("Missing authorization header or session cookie".to_string())
+      })?;
+      let claims = decode_token(token, secret)?;
+      // Browsers send the cookie with requests made by other sites.
+      session::verify_csrf(method, headers, &jar, cookies, secret)?;
+      claims
+    }
+  };
+  current_user(state, &claims).await
+}
+
+/// Like `auth_guard`, but lets requests without an authorization header or a session cookie
+/// through anonymously, for endpoints that check the user themselves such as GraphQL. Invalid
+/// tokens are still rejected.
+pub async fn optional_auth_guard(
+  state: State<AppState>,
+  req: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let has_session = session::token(
+    &CookieJar::from_headers(req.headers()),
+    &state.cfg.session_cookie,
+  )
+  .is_some();
+  if !req.headers().contains_key(AUTHORIZATION) && !has_session {
+    return Ok(next.run(req).await);
+  }
+  auth_guard(state, req, next).await
+}
+
+/// Authenticates a GraphQL WebSocket connection from the payload of its `connection_init`
+/// message, since browsers cannot set the authorization header of a WebSocket.
+///
+/// The payload holds the header as `{"Authorization": "Bearer <token>"}`. The claims are returned
+/// whole, since the connection must not outlive the token.
+pub fn authenticate_connection(
+  payload: &serde_json::Value,
+  secret: &str,
+) -> Result<Claims, ApiError> {
+  let auth_header = ["Authorization", "authorization"]
+    .iter()
+    .find_map(|key| payload.get(key))
     .ok_or_else(|| ApiError::Unauthorized("Missing authorization header".to_string()))?
-    .to_str()
-    .map_err(|_| ApiError::Unauthorized("Invalid authorization header".to_string()))?;
+    .as_str()
+    .ok_or_else(|| ApiError::Unauthorized("Invalid authorization header".to_string()))?;
 
-  // Check if it's a Bearer token
-  let token = auth_header
-    .strip_prefix("Bearer ")
-    .ok_or_else(|| ApiError::Unauthorized("Invalid authorization format".to_string()))?;
+  decode_token(bearer_token(auth_header)?, secret)
+}
 
-  // Get JWT secret from environment
-  let secret = std::env::var("JWT_SECRET")
-    .unwrap_or_else(|_| "a-string-secret-at-least-256-bits-long".to_string());
+/// Returns the user of a token, whose role may have changed since the token was issued.
+///
+/// Rejects the tokens issued before the last password change of their user, or whose user no
+/// longer exists. The user is read from the primary, so that a change is seen at once.
+pub async fn current_user(state: &AppState, claims: &Claims) -> Result<UserDto, ApiError> {
+  let revoked = || ApiError::Unauthorized("Token has been revoked".to_string());
+  let id = Uuid::parse_str(&claims.sub).map_err(|_| revoked())?;
+  let user = state
+    .repositories
+    .users(state.db.writer().into())
+    .find_by_id(id)
+    .await?;
+  match user {
+    Some(user) if user.token_version == claims.ver => Ok(user.into()),
+    _ => Err(revoked()),
+  }
+}
+
+fn bearer_token(auth_header: &str) -> Result<&str, ApiError> {
+  auth_header
+    .strip_prefix("Bearer ")
+    .ok_or_else(|| ApiError::Unauthorized("Invalid authorization format".to_string()))
+}
 
+/// Decodes and validates a JWT issued by the auth service.
+pub fn decode_token(token: &str, secret: &str) -> Result<Claims, ApiError> {
   // Decode and validate the token
   let token_data = decode::<Claims>(
     token,
@@ -51,13 +150,7 @@ pub async fn auth_guard(
     return Err(ApiError::Unauthorized("Token has expired".to_string()));
   }
 
-  // Add user role to request extensions for GraphQL context
-  let mut req = req;
-  req.extensions_mut().insert(UserDto {
-    ..token_data.claims.user
-  });
-
-  Ok(next.run(req).await)
+  Ok(token_data.claims)
 }
 
 #[cfg(test)]
@@ -79,12 +172,14 @@ mod tests {
       exp: 1234567890,
       iat: 1234567800,
       user: UserDto::default(),
+      ver: 1,
     };
 
     let json = serde_json::to_string(&claims).unwrap();
     assert!(json.contains("\"sub\":\"user-123\""));
     assert!(json.contains("\"exp\":1234567890"));
     assert!(json.contains("\"iat\":1234567800"));
+    assert!(json.contains("\"ver\":1"));
   }
 
   #[test]
@@ -95,4 +190,35 @@ mod tests {
     assert_eq!(claims.exp, 9999999999);
     assert_eq!(claims.iat, 9999999900);
   }
+
+  #[test]
+  fn test_authenticate_connection() {
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    let claims = Claims {
+      sub: "user-123".to_string(),
+      exp: (chrono::Utc::now().timestamp() + 60) as usize,
+      iat: 0,
+      user: UserDto {
+        email: "jane@example.com".to_string(),
+        ..Default::default()
+      },
+      ver: 0,
+    };
+    let token = encode(
+      &Header::default(),
+      &claims,
+      &EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+
+    let payload = serde_json::json!({ "Authorization": format!("Bearer {token}") });
+    let claims = authenticate_connection(&payload, "secret").unwrap();
+    assert_eq!(claims.user.email, "jane@example.com");
+
+    assert!(authenticate_connection(&payload, "other-secret").is_err());
+    assert!(authenticate_connection(&serde_json::json!({}), "secret").is_err());
+    let payload = serde_json::json!({ "authorization": token });
+    assert!(authenticate_connection(&payload, "secret").is_err());
+  }
 }
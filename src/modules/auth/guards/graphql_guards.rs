@@ -1,15 +1,28 @@
 use async_graphql::dynamic::ResolverContext;
+use sea_orm::ActiveEnum;
 use seaography::GuardsConfig;
 
-use crate::modules::users::enums::UserRole;
+use crate::common::api_error::ApiError;
+use crate::modules::users::{dto::UserDto, enums::UserRole};
+
+/// The GraphQL type of the users, which the guards are keyed by.
+const USERS: &str = "Users";
+
+/// Returns the user authenticated by the request or the WebSocket connection.
+pub fn current_user<'a>(ctx: &ResolverContext<'a>) -> Result<&'a UserDto, ApiError> {
+  ctx
+    .data_opt::<UserDto>()
+    .ok_or_else(|| ApiError::Unauthorized("Authentication required".to_string()))
+}
+
+/// Whether the request or the WebSocket connection is authenticated as an admin.
+pub fn is_admin(ctx: &ResolverContext) -> bool {
+  current_user(ctx).is_ok_and(|user| user.role == UserRole::Admin.into_value())
+}
 
 pub fn admin_guard(ctx: &ResolverContext) -> seaography::GuardAction {
-  // Get the user role from the context
-  tracing::info!("Context data: {:?}", ctx.data::<UserRole>());
-  if let Some(role) = ctx.data_opt::<UserRole>() {
-    if *role == UserRole::Admin {
-      return seaography::GuardAction::Allow;
-    }
+  if is_admin(ctx) {
+    return seaography::GuardAction::Allow;
   }
   seaography::GuardAction::Block(Some("Admin role required".to_string()))
 }
@@ -21,17 +34,17 @@ pub fn setup_guards() -> GuardsConfig {
   // Add entity guards
   config
     .entity_guards
-    .insert("users".to_string(), Box::new(admin_guard));
-  tracing::info!("Added entity guard for 'users'");
+    .insert(USERS.to_string(), Box::new(admin_guard));
+  tracing::info!("Added entity guard for '{USERS}'");
 
   // Add field guards for specific fields that require admin access
   config
     .field_guards
-    .insert("users.role".to_string(), Box::new(admin_guard));
+    .insert(format!("{USERS}.role"), Box::new(admin_guard));
   config
     .field_guards
-    .insert("users.status".to_string(), Box::new(admin_guard));
-  tracing::info!("Added field guards for 'users.role' and 'users.status'");
+    .insert(format!("{USERS}.status"), Box::new(admin_guard));
+  tracing::info!("Added field guards for '{USERS}.role' and '{USERS}.status'");
 
   config
 }
@@ -3,4 +3,4 @@ pub mod auth_guard;
 pub mod graphql_guards;
 
 pub use admin_guard::admin_guard;
-pub use auth_guard::auth_guard;
+pub use auth_guard::{auth_guard, optional_auth_guard};
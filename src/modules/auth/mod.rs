@@ -1,17 +1,26 @@
 pub mod controller;
 pub mod dto;
+pub mod graphql;
 pub mod guards;
 pub mod service;
+pub mod session;
 
-use axum::Router;
+use axum::{extract::State, Router};
 
 use crate::app::AppState;
+use crate::common::transaction::transaction;
 
-pub fn router() -> Router<AppState> {
+pub fn router(State(state): State<AppState>) -> Router<AppState> {
   Router::new()
     .route(
       "/v1/auth/register",
-      axum::routing::post(controller::register),
+      // The email check and the insert run in one transaction.
+      axum::routing::post(controller::register)
+        .route_layer(axum::middleware::from_fn_with_state(state, transaction)),
     )
     .route("/v1/auth/login", axum::routing::post(controller::login))
+    .route(
+      "/v1/auth/session",
+      axum::routing::post(controller::create_session).delete(controller::destroy_session),
+    )
 }
@@ -1,17 +1,36 @@
 use anyhow::anyhow;
 use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{encode, EncodingKey, Header};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
-use serde_json::Value;
+use sea_orm::Set;
 use uuid::Uuid;
 
-use crate::common::api_error::ApiError;
-use crate::modules::auth::dto::{AuthResponse, LoginRequest, RegisterRequest};
+use crate::common::{api_error::ApiError, events::EventBus, metrics};
+use crate::modules::auth::dto::{
+  AuthResponse, ChangePasswordRequest, LoginRequest, RegisterRequest,
+};
 use crate::modules::auth::guards::auth_guard::Claims;
 use crate::modules::users::dto::UserDto;
 use crate::modules::users::entities::{self as UserEntities};
+use crate::modules::users::events::UserEvent;
+use crate::modules::users::repository::UserRepository;
+
+/// Days during which the issued tokens, and the session cookies holding them, are valid.
+pub const TOKEN_LIFETIME_DAYS: i64 = 7;
+
+pub async fn register(
+  users: &dyn UserRepository,
+  events: &EventBus<UserEvent>,
+  jwt_secret: &str,
+  req: RegisterRequest,
+) -> Result<AuthResponse, ApiError> {
+  // The check saves hashing the password of a taken email. It is no guarantee, since under READ
+  // COMMITTED a concurrent registration is not seen even in a transaction: the unique index on
+  // the email is, and its violation is reported the same way.
+  let existing = users.find_by_email(&req.email).await?;
+  if existing.is_some() {
+    return Err(ApiError::Conflict("Email already exists".to_string()));
+  }
 
-pub async fn register(conn: &DatabaseConnection, req: RegisterRequest) -> Result<Value, ApiError> {
   // Hash password
   let password_hash = hash(req.password.as_bytes(), DEFAULT_COST)
     .map_err(|e| ApiError::InternalError(anyhow!("Failed to hash password: {}", e)))?;
@@ -25,56 +44,84 @@ pub async fn register(conn: &DatabaseConnection, req: RegisterRequest) -> Result
     ..Default::default()
   };
 
-  let user = user.insert(conn).await.map_err(|e| {
-    if e.to_string().contains("duplicate key") {
-      ApiError::InvalidRequest("Email already exists".to_string())
-    } else {
-      ApiError::InternalError(anyhow!(e))
-    }
-  })?;
+  let user = users
+    .insert(user)
+    .await
+    .map_err(|e| ApiError::unique_violation(e, "Email already exists"))?;
+
+  metrics::record_registration();
+  events.publish(UserEvent::Created(user.clone()));
 
   // Generate JWT token
-  let token = generate_token(&user)?;
+  let token = generate_token(&user, jwt_secret)?;
 
-  let response = AuthResponse {
+  Ok(AuthResponse {
     token,
-    user: UserDto { ..user.into() },
-  };
-
-  Ok(serde_json::to_value(response).map_err(|e| ApiError::InternalError(anyhow!(e)))?)
+    user: UserDto::from(user),
+  })
 }
 
-pub async fn login(conn: &DatabaseConnection, req: LoginRequest) -> Result<Value, ApiError> {
+pub async fn login(
+  users: &dyn UserRepository,
+  jwt_secret: &str,
+  req: LoginRequest,
+) -> Result<AuthResponse, ApiError> {
   // Find user by email
-  let user = UserEntities::Entity::find()
-    .filter(UserEntities::Column::Email.eq(req.email))
-    .one(conn)
-    .await?
-    .ok_or_else(|| ApiError::InvalidRequest("Invalid credentials".to_string()))?;
+  let user = users.find_by_email(&req.email).await?.ok_or_else(|| {
+    metrics::record_failed_login();
+    ApiError::InvalidRequest("Invalid credentials".to_string())
+  })?;
 
   // Verify password
   if !verify(req.password, &user.password)
     .map_err(|e| ApiError::InternalError(anyhow!("Failed to verify password: {}", e)))?
   {
+    metrics::record_failed_login();
     return Err(ApiError::InvalidRequest("Invalid credentials".to_string()));
   }
 
+  metrics::record_login();
+
   // Generate JWT token
-  let token = generate_token(&user)?;
+  let token = generate_token(&user, jwt_secret)?;
 
-  let response = AuthResponse {
+  Ok(AuthResponse {
     token,
-    user: UserDto { ..user.into() },
-  };
+    user: UserDto::from(user),
+  })
+}
+
+/// Changes the password of a user, who must confirm their current one.
+pub async fn change_password(
+  users: &dyn UserRepository,
+  id: Uuid,
+  req: ChangePasswordRequest,
+) -> Result<(), ApiError> {
+  let user = users
+    .find_by_id(id)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+  if !verify(req.current_password, &user.password)
+    .map_err(|e| ApiError::InternalError(anyhow!("Failed to verify password: {}", e)))?
+  {
+    return Err(ApiError::InvalidRequest("Invalid credentials".to_string()));
+  }
 
-  Ok(serde_json::to_value(response).map_err(|e| ApiError::InternalError(anyhow!(e)))?)
+  let password_hash = hash(req.new_password.as_bytes(), DEFAULT_COST)
+    .map_err(|e| ApiError::InternalError(anyhow!("Failed to hash password: {}", e)))?;
+  // The tokens issued before are revoked.
+  let token_version = user.token_version + 1;
+  let mut user: UserEntities::ActiveModel = user.into();
+  user.password = Set(password_hash);
+  user.token_version = Set(token_version);
+  users.update(user).await?;
+  Ok(())
 }
 
-fn generate_token(user: &UserEntities::Model) -> Result<String, ApiError> {
-  let secret = std::env::var("JWT_SECRET")
-    .unwrap_or_else(|_| "a-string-secret-at-least-256-bits-long".to_string());
+fn generate_token(user: &UserEntities::Model, secret: &str) -> Result<String, ApiError> {
   let expiration = chrono::Utc::now()
-    .checked_add_signed(chrono::Duration::days(7))
+    .checked_add_signed(chrono::Duration::days(TOKEN_LIFETIME_DAYS))
     .expect("valid timestamp")
     .timestamp();
 
@@ -82,6 +129,7 @@ fn generate_token(user: &UserEntities::Model) -> Result<String, ApiError> {
     sub: user.id.to_string(),
     exp: expiration as usize,
     user: user.clone().into(),
+    ver: user.token_version,
     ..Default::default()
   };
 
@@ -92,3 +140,97 @@ fn generate_token(user: &UserEntities::Model) -> Result<String, ApiError> {
   )
   .map_err(|e| ApiError::InternalError(anyhow!("Failed to generate token: {}", e)))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::modules::users::{
+    enums::{UserRole, UserStatus},
+    repository::MockUserRepository,
+  };
+
+  const SECRET: &str = "a-test-secret";
+
+  fn user(password: &str) -> UserEntities::Model {
+    UserEntities::Model {
+      id: Uuid::new_v4(),
+      email: "jane@example.com".to_string(),
+      name: "Jane".to_string(),
+      password: hash(password.as_bytes(), 4).unwrap(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: None,
+      updated_at: None,
+      token_version: 0,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_register_existing_email() {
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_email()
+      .returning(|_| Ok(Some(user("secret"))));
+    users.expect_insert().never();
+
+    let req = RegisterRequest {
+      email: "jane@example.com".to_string(),
+      password: "secret".to_string(),
+      name: "Jane".to_string(),
+    };
+    let err = register(&users, &EventBus::new(), SECRET, req)
+      .await
+      .unwrap_err();
+    assert_eq!(err.to_string(), "Conflict: Email already exists");
+  }
+
+  #[tokio::test]
+  async fn test_login_checks_the_password() {
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_email()
+      .returning(|_| Ok(Some(user("secret"))));
+
+    let req = |password: &str| LoginRequest {
+      email: "jane@example.com".to_string(),
+      password: password.to_string(),
+    };
+    let err = login(&users, SECRET, req("wrong")).await.unwrap_err();
+    assert!(matches!(err, ApiError::InvalidRequest(_)));
+
+    let result = login(&users, SECRET, req("secret")).await.unwrap();
+    assert_eq!(result.user.email, "jane@example.com");
+    assert!(!result.token.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_change_password_checks_the_current_one() {
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_id()
+      .returning(|_| Ok(Some(user("secret"))));
+    users
+      .expect_update()
+      .withf(|user| {
+        let sea_orm::ActiveValue::Set(password) = &user.password else {
+          return false;
+        };
+        verify("changed", password).unwrap() && user.token_version.as_ref() == &1
+      })
+      .times(1)
+      .returning(|_| Ok(user("changed")));
+
+    let req = |current: &str| ChangePasswordRequest {
+      current_password: current.to_string(),
+      new_password: "changed".to_string(),
+    };
+    let err = change_password(&users, Uuid::new_v4(), req("wrong"))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, ApiError::InvalidRequest(_)));
+
+    change_password(&users, Uuid::new_v4(), req("secret"))
+      .await
+      .unwrap();
+  }
+}
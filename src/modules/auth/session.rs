@@ -0,0 +1,177 @@
+use axum::http::{HeaderMap, Method};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::common::{
+  api_error::ApiError,
+  cfg::{session_cookie, SessionCookieConfig},
+};
+use crate::modules::auth::service::TOKEN_LIFETIME_DAYS;
+
+/// The header in which browsers send back the value of the CSRF cookie.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// The CSRF token of the session holding `session_token`, an HMAC of it keyed with `secret`.
+///
+/// It is tied to the session cookie, so a cookie planted by a sibling domain cannot forge it.
+pub fn csrf_token(session_token: &str, secret: &str) -> String {
+  let mut mac =
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+  mac.update(session_token.as_bytes());
+  format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Adds the session cookie holding `token`, out of reach of scripts, and the CSRF cookie, which
+/// scripts read to send it back in the `x-csrf-token` header.
+pub fn start(
+  jar: CookieJar,
+  cfg: &SessionCookieConfig,
+  token: String,
+  csrf_token: String,
+) -> CookieJar {
+  let max_age = time::Duration::days(TOKEN_LIFETIME_DAYS);
+  let mut session = cookie(cfg, cfg.session_name(), token);
+  session.set_http_only(true);
+  session.set_max_age(max_age);
+  let mut csrf = cookie(cfg, cfg.csrf_name(), csrf_token);
+  csrf.set_max_age(max_age);
+  jar.add(session).add(csrf)
+}
+
+/// Removes the session and CSRF cookies, whether or not the request sent them.
+pub fn end(jar: CookieJar, cfg: &SessionCookieConfig) -> CookieJar {
+  [cfg.session_name(), cfg.csrf_name()]
+    .into_iter()
+    .fold(jar, |jar, name| {
+      let mut cookie = cookie(cfg, name, String::new());
+      cookie.make_removal();
+      jar.add(cookie)
+    })
+}
+
+/// The token of the session cookie, if any.
+pub fn token<'a>(jar: &'a CookieJar, cfg: &SessionCookieConfig) -> Option<&'a str> {
+  jar
+    .get(cfg.session_name())
+    .map(Cookie::value)
+    .filter(|token| !token.is_empty())
+}
+
+/// Checks that a state-changing request authenticated by the session cookie sends the CSRF
+/// token of its session in the `x-csrf-token` header, which other sites cannot read.
+pub fn verify_csrf(
+  method: &Method,
+  headers: &HeaderMap,
+  jar: &CookieJar,
+  cfg: &SessionCookieConfig,
+  secret: &str,
+) -> Result<(), ApiError> {
+  if method.is_safe() {
+    return Ok(());
+  }
+
+  let expected = token(jar, cfg)
+    .map(|token| csrf_token(token, secret))
+    .unwrap_or_default();
+  let actual = headers
+    .get(CSRF_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or("");
+  if expected.is_empty() || !bool::from(expected.as_bytes().ct_eq(actual.as_bytes())) {
+    return Err(ApiError::Forbidden("Invalid CSRF token".to_string()));
+  }
+  Ok(())
+}
+
+fn cookie(cfg: &SessionCookieConfig, name: &'static str, value: String) -> Cookie<'static> {
+  let mut cookie = Cookie::build((name, value))
+    .path("/")
+    .secure(cfg.secure)
+    .same_site(match cfg.same_site {
+      session_cookie::SameSite::Strict => SameSite::Strict,
+      session_cookie::SameSite::Lax => SameSite::Lax,
+      session_cookie::SameSite::None => SameSite::None,
+    });
+  if let Some(domain) = &cfg.domain {
+    cookie = cookie.domain(domain.clone());
+  }
+  cookie.build()
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::{http::header, response::IntoResponse};
+
+  use super::*;
+  use crate::common::cfg::testing;
+
+  const SECRET: &str = "secret";
+
+  fn config(secure_cookies: bool) -> SessionCookieConfig {
+    testing::section(&[], |values| {
+      SessionCookieConfig::from_values(values, secure_cookies)
+    })
+  }
+
+  fn set_cookies(jar: CookieJar) -> Vec<String> {
+    let response = jar.into_response();
+    let mut cookies: Vec<String> = response
+      .headers()
+      .get_all(header::SET_COOKIE)
+      .iter()
+      .map(|value| value.to_str().unwrap().to_string())
+      .collect();
+    cookies.sort();
+    cookies
+  }
+
+  #[test]
+  fn test_session_cookies() {
+    let jar = start(
+      CookieJar::new(),
+      &config(true),
+      "jwt".to_string(),
+      "csrf".to_string(),
+    );
+    assert_eq!(
+      set_cookies(jar),
+      [
+        "__Host-csrf=csrf; SameSite=Lax; Secure; Path=/; Max-Age=604800",
+        "__Host-session=jwt; HttpOnly; SameSite=Lax; Secure; Path=/; Max-Age=604800",
+      ]
+    );
+
+    let cleared = set_cookies(end(CookieJar::new(), &config(false)));
+    assert!(cleared[0].starts_with("csrf=; SameSite=Lax; Path=/; Max-Age=0"));
+    assert!(cleared[1].starts_with("session=; SameSite=Lax; Path=/; Max-Age=0"));
+  }
+
+  #[test]
+  fn test_csrf_token_is_required_on_state_changing_requests() {
+    let cfg = config(false);
+    let jar = CookieJar::new().add(Cookie::new("session", "jwt"));
+    let mut headers = HeaderMap::new();
+
+    assert!(verify_csrf(&Method::GET, &headers, &jar, &cfg, SECRET).is_ok());
+    assert!(matches!(
+      verify_csrf(&Method::POST, &headers, &jar, &cfg, SECRET),
+      Err(ApiError::Forbidden(_))
+    ));
+
+    headers.insert(CSRF_HEADER, "other".parse().unwrap());
+    assert!(verify_csrf(&Method::DELETE, &headers, &jar, &cfg, SECRET).is_err());
+
+    headers.insert(CSRF_HEADER, csrf_token("jwt", SECRET).parse().unwrap());
+    assert!(verify_csrf(&Method::DELETE, &headers, &jar, &cfg, SECRET).is_ok());
+    assert!(verify_csrf(&Method::DELETE, &headers, &jar, &cfg, "other").is_err());
+    assert!(verify_csrf(&Method::DELETE, &headers, &CookieJar::new(), &cfg, SECRET).is_err());
+
+    // A CSRF cookie planted next to another session does not match it.
+    let planted = CookieJar::new()
+      .add(Cookie::new("session", "other"))
+      .add(Cookie::new("csrf", csrf_token("jwt", SECRET)));
+    assert!(verify_csrf(&Method::DELETE, &headers, &planted, &cfg, SECRET).is_err());
+  }
+}
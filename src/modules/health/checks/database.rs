@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+
+use crate::database::Db;
+use crate::modules::health::checks::HealthCheck;
+
+/// Checks that the database accepts connections.
+pub struct DatabaseCheck {
+  db: Db,
+}
+
+impl DatabaseCheck {
+  pub fn new(db: Db) -> Self {
+    Self { db }
+  }
+}
+
+#[async_trait]
+impl HealthCheck for DatabaseCheck {
+  fn name(&self) -> &'static str {
+    "database"
+  }
+
+  async fn check(&self) -> Result<(), String> {
+    self.db.writer().ping().await.map_err(|e| e.to_string())
+  }
+}
+
+/// Checks that every migration embedded in the binary has been applied.
+pub struct MigrationsCheck {
+  db: Db,
+}
+
+impl MigrationsCheck {
+  pub fn new(db: Db) -> Self {
+    Self { db }
+  }
+}
+
+#[async_trait]
+impl HealthCheck for MigrationsCheck {
+  fn name(&self) -> &'static str {
+    "migrations"
+  }
+
+  async fn check(&self) -> Result<(), String> {
+    let pending = self
+      .db
+      .pending_migrations()
+      .await
+      .map_err(|e| e.to_string())?;
+
+    if pending.is_empty() {
+      Ok(())
+    } else {
+      Err(format!("{} pending migration(s)", pending.len()))
+    }
+  }
+}
@@ -0,0 +1,209 @@
+pub mod database;
+
+use std::{
+  collections::BTreeMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::task::JoinSet;
+
+use crate::modules::health::dto::{CheckStatus, Healthy};
+
+pub use database::{DatabaseCheck, MigrationsCheck};
+
+/// A dependency the application needs in order to serve traffic.
+///
+/// Modules implement this trait for the services they rely on and register them in the
+/// `HealthRegistry`, which runs them for the readiness probe.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+  /// The name under which the result is reported.
+  fn name(&self) -> &'static str;
+
+  /// Returns an error message if the dependency is unhealthy.
+  async fn check(&self) -> Result<(), String>;
+}
+
+/// Holds the registered health checks and the readiness state of the application.
+#[derive(Clone)]
+pub struct HealthRegistry {
+  checks: Vec<Arc<dyn HealthCheck>>,
+  timeout: Duration,
+  shutting_down: Arc<AtomicBool>,
+}
+
+impl HealthRegistry {
+  /// Creates an empty registry. Each check fails if it does not complete within `timeout`.
+  pub fn new(timeout: Duration) -> Self {
+    Self {
+      checks: Vec::new(),
+      timeout,
+      shutting_down: Arc::new(AtomicBool::new(false)),
+    }
+  }
+
+  /// Registers a health check.
+  pub fn register(mut self, check: impl HealthCheck + 'static) -> Self {
+    self.checks.push(Arc::new(check));
+    self
+  }
+
+  /// Marks the application as shutting down, so the readiness probe fails and load balancers
+  /// stop routing new requests to this instance.
+  pub fn mark_shutting_down(&self) {
+    self.shutting_down.store(true, Ordering::SeqCst);
+  }
+
+  /// Whether the application is shutting down.
+  pub fn is_shutting_down(&self) -> bool {
+    self.shutting_down.load(Ordering::SeqCst)
+  }
+
+  /// Runs all registered checks concurrently and returns the report.
+  pub async fn run(&self) -> Healthy {
+    if self.is_shutting_down() {
+      return Healthy {
+        status: "shutting_down".to_string(),
+        checks: None,
+      };
+    }
+
+    let mut tasks = JoinSet::new();
+    for check in &self.checks {
+      let check = check.clone();
+      let timeout = self.timeout;
+      tasks.spawn(async move {
+        let start = Instant::now();
+        let result = match tokio::time::timeout(timeout, check.check()).await {
+          Ok(result) => result,
+          Err(_) => Err(format!("Timed out after {}ms", timeout.as_millis())),
+        };
+        let latency = start.elapsed();
+        let status = match result {
+          Ok(()) => CheckStatus::up(latency),
+          Err(error) => {
+            tracing::warn!(
+              check = check.name(),
+              latency_ms = latency.as_millis() as u64,
+              error,
+              "Health check failed"
+            );
+            CheckStatus::down(latency)
+          }
+        };
+        (check.name(), status)
+      });
+    }
+
+    let mut checks = BTreeMap::new();
+    while let Some(joined) = tasks.join_next().await {
+      match joined {
+        Ok((name, status)) => {
+          checks.insert(name.to_string(), status);
+        }
+        Err(err) => tracing::error!("Health check panicked: {}", err),
+      }
+    }
+
+    let healthy = checks.len() == self.checks.len() && checks.values().all(CheckStatus::is_ok);
+    Healthy {
+      status: if healthy { "ok" } else { "error" }.to_string(),
+      checks: Some(checks),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct StaticCheck(&'static str, Result<(), String>);
+
+  #[async_trait]
+  impl HealthCheck for StaticCheck {
+    fn name(&self) -> &'static str {
+      self.0
+    }
+
+    async fn check(&self) -> Result<(), String> {
+      self.1.clone()
+    }
+  }
+
+  struct SlowCheck(Duration);
+
+  #[async_trait]
+  impl HealthCheck for SlowCheck {
+    fn name(&self) -> &'static str {
+      "slow"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+      tokio::time::sleep(self.0).await;
+      Ok(())
+    }
+  }
+
+  #[tokio::test]
+  async fn test_registry_without_checks_is_ok() {
+    let report = HealthRegistry::new(Duration::from_secs(1)).run().await;
+    assert_eq!(report.status, "ok");
+    assert!(report.checks.unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_registry_reports_each_check() {
+    let report = HealthRegistry::new(Duration::from_secs(1))
+      .register(StaticCheck("first", Ok(())))
+      .register(StaticCheck("second", Err("unreachable".to_string())))
+      .run()
+      .await;
+
+    assert_eq!(report.status, "error");
+    let checks = report.checks.unwrap();
+    assert_eq!(checks["first"].status, "up");
+    assert_eq!(checks["second"].status, "down");
+  }
+
+  #[tokio::test]
+  async fn test_registry_times_out_slow_checks() {
+    let report = HealthRegistry::new(Duration::from_millis(10))
+      .register(SlowCheck(Duration::from_secs(5)))
+      .run()
+      .await;
+
+    assert_eq!(report.status, "error");
+    let checks = report.checks.unwrap();
+    assert_eq!(checks["slow"].status, "down");
+    assert!((10..5000).contains(&checks["slow"].latency_ms));
+  }
+
+  #[tokio::test]
+  async fn test_registry_reports_the_latency_of_each_check() {
+    let report = HealthRegistry::new(Duration::from_secs(1))
+      .register(SlowCheck(Duration::from_millis(20)))
+      .run()
+      .await;
+
+    assert_eq!(report.status, "ok");
+    let checks = report.checks.unwrap();
+    assert_eq!(checks["slow"].status, "up");
+    assert!(checks["slow"].latency_ms >= 20);
+  }
+
+  #[tokio::test]
+  async fn test_registry_is_not_ready_when_shutting_down() {
+    let registry =
+      HealthRegistry::new(Duration::from_secs(1)).register(StaticCheck("first", Ok(())));
+    registry.mark_shutting_down();
+
+    let report = registry.run().await;
+    assert_eq!(report.status, "shutting_down");
+    assert!(report.checks.is_none());
+  }
+}
@@ -1,9 +1,21 @@
-use axum::Json;
-use serde_json::Value;
+use axum::{
+  extract::State,
+  http::{header::AUTHORIZATION, HeaderMap, Method, StatusCode},
+  Json,
+};
+use sea_orm::ActiveEnum;
 
 use crate::{
+  app::AppState,
   common::api_error::ApiError,
-  modules::health::{dto::Healthy, service},
+  modules::{
+    auth::guards::auth_guard,
+    health::{
+      dto::{BuildInfo, Healthy},
+      service,
+    },
+    users::enums::UserRole,
+  },
 };
 
 #[utoipa::path(
@@ -12,10 +24,83 @@ use crate::{
   path = "/api/v1/health",
   operation_id = "healthIndex",
   responses(
-      (status = 200, description = "Heathy check", body = [Healthy])
+      (status = 200, description = "Heathy check", body = Healthy)
   )
 )]
-pub async fn index() -> Result<Json<Value>, ApiError> {
+pub async fn index() -> Result<Json<Healthy>, ApiError> {
   let result = service::index().await;
   Ok(Json(result))
 }
+
+#[utoipa::path(
+  get,
+  tag = "Health",
+  path = "/api/v1/health/live",
+  operation_id = "healthLive",
+  responses(
+      (status = 200, description = "The process is alive", body = Healthy)
+  )
+)]
+pub async fn live() -> Result<Json<Healthy>, ApiError> {
+  let result = service::live().await;
+  Ok(Json(result))
+}
+
+#[utoipa::path(
+  get,
+  tag = "Health",
+  path = "/api/v1/health/ready",
+  operation_id = "healthReady",
+  responses(
+      (status = 200, description = "All dependencies are healthy", body = Healthy),
+      (status = 503, description = "A dependency is unhealthy or the server is shutting down", body = Healthy)
+  )
+)]
+pub async fn ready(State(state): State<AppState>) -> Result<(StatusCode, Json<Healthy>), ApiError> {
+  let result = service::ready(&state.health).await;
+  let status = if result.status == "ok" {
+    StatusCode::OK
+  } else {
+    StatusCode::SERVICE_UNAVAILABLE
+  };
+  Ok((status, Json(result)))
+}
+
+#[utoipa::path(
+  get,
+  tag = "Health",
+  path = "/api/v1/health/info",
+  operation_id = "healthInfo",
+  responses(
+      (status = 200, description = "Build and runtime information. The effective configuration is only included for admins and the info basic auth user.", body = BuildInfo)
+  ),
+  security(
+    (),
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
+  )
+)]
+pub async fn info(
+  State(state): State<AppState>,
+  headers: HeaderMap,
+) -> Result<Json<BuildInfo>, ApiError> {
+  let include_config = can_see_config(&state, &headers).await;
+  let result = service::info(&state, include_config).await?;
+  Ok(Json(result))
+}
+
+/// Whether the request comes from an admin or from the info basic auth user.
+///
+/// Admins are authenticated as by `auth_guard`, so revoked tokens and demoted users are refused.
+async fn can_see_config(state: &AppState, headers: &HeaderMap) -> bool {
+  let authorization = headers
+    .get(AUTHORIZATION)
+    .and_then(|value| value.to_str().ok());
+  if let Some(auth_str) = authorization.filter(|value| !value.starts_with("Bearer ")) {
+    return state.info_basic_auth.authenticate(auth_str).await;
+  }
+
+  auth_guard::authenticate(state, &Method::GET, headers)
+    .await
+    .is_ok_and(|user| user.role == UserRole::Admin.to_value())
+}
@@ -1,7 +1,85 @@
+use std::{collections::BTreeMap, time::Duration};
+
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Healthy {
   pub status: String,
+  /// Per-dependency results, only reported by the readiness probe.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub checks: Option<BTreeMap<String, CheckStatus>>,
+}
+
+/// The result of a health check. The readiness probe is public, so the reason of a failure is
+/// only logged.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CheckStatus {
+  /// Either "up" or "down"
+  pub status: String,
+  /// How long the check took, up to the timeout of the failed ones
+  pub latency_ms: u64,
+}
+
+impl CheckStatus {
+  pub fn up(latency: Duration) -> Self {
+    Self {
+      status: "up".to_string(),
+      latency_ms: latency.as_millis() as u64,
+    }
+  }
+
+  pub fn down(latency: Duration) -> Self {
+    Self {
+      status: "down".to_string(),
+      latency_ms: latency.as_millis() as u64,
+    }
+  }
+
+  pub fn is_ok(&self) -> bool {
+    self.status == "up"
+  }
+}
+
+/// Build and runtime information about the running instance.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+  pub version: String,
+  pub git_commit: String,
+  #[schema(format = "date-time")]
+  pub build_timestamp: Option<String>,
+  pub rustc_version: String,
+  pub features: Vec<String>,
+  pub uptime_seconds: u64,
+  /// The name of the last applied migration.
+  pub migration: Option<String>,
+  /// The redacted effective configuration, only reported to admins.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schema(value_type = Option<Object>)]
+  pub config: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_healthy_without_checks_serialization() {
+    let healthy = Healthy {
+      status: "ok".to_string(),
+      checks: None,
+    };
+
+    let json = serde_json::to_string(&healthy).unwrap();
+    assert_eq!(json, r#"{"status":"ok"}"#);
+  }
+
+  #[test]
+  fn test_check_status_serialization() {
+    assert!(CheckStatus::up(Duration::ZERO).is_ok());
+    assert!(!CheckStatus::down(Duration::ZERO).is_ok());
+
+    let json = serde_json::to_string(&CheckStatus::down(Duration::from_millis(12))).unwrap();
+    assert_eq!(json, r#"{"status":"down","latency_ms":12}"#);
+  }
 }
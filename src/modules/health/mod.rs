@@ -1,8 +1,9 @@
+pub mod checks;
 pub mod controller;
 pub mod dto;
 pub mod service;
 
-use axum::Router;
+use axum::{routing::get, Router};
 use axum_extra::routing::Resource;
 
 use crate::app::AppState;
@@ -10,5 +11,12 @@ use crate::app::AppState;
 pub fn router() -> axum::Router<AppState> {
   let resources_v1 = Resource::named("health").index(controller::index);
 
-  Router::new().nest("/v1", Router::new().merge(resources_v1))
+  Router::new().nest(
+    "/v1",
+    Router::new()
+      .merge(resources_v1)
+      .route("/health/live", get(controller::live))
+      .route("/health/ready", get(controller::ready))
+      .route("/health/info", get(controller::info)),
+  )
 }
@@ -1,23 +1,97 @@
-use serde_json::{json, Value};
+use chrono::{DateTime, SecondsFormat};
 
-pub async fn index() -> Value {
-  json!({ "status": "ok" })
+use crate::app::AppState;
+use crate::common::api_error::ApiError;
+use crate::modules::health::{
+  checks::HealthRegistry,
+  dto::{BuildInfo, Healthy},
+};
+
+pub async fn index() -> Healthy {
+  Healthy {
+    status: "ok".to_string(),
+    checks: None,
+  }
+}
+
+pub async fn live() -> Healthy {
+  Healthy {
+    status: "ok".to_string(),
+    checks: None,
+  }
+}
+
+pub async fn ready(registry: &HealthRegistry) -> Healthy {
+  registry.run().await
+}
+
+pub async fn info(state: &AppState, include_config: bool) -> Result<BuildInfo, ApiError> {
+  let migration = state.db.last_migration().await?;
+
+  let config = if include_config {
+    let mut config =
+      serde_json::to_value(&*state.cfg).map_err(|e| ApiError::InternalError(e.into()))?;
+    // Report the current runtime settings rather than the ones loaded at startup.
+    config["runtime"] = serde_json::to_value(&*state.settings.current())
+      .map_err(|e| ApiError::InternalError(e.into()))?;
+    Some(config)
+  } else {
+    None
+  };
+
+  Ok(BuildInfo {
+    version: env!("CARGO_PKG_VERSION").to_string(),
+    git_commit: env!("GIT_COMMIT").to_string(),
+    build_timestamp: build_timestamp(),
+    rustc_version: env!("RUSTC_VERSION").to_string(),
+    features: features(),
+    uptime_seconds: state.started_at.elapsed().as_secs(),
+    migration,
+    config,
+  })
+}
+
+fn build_timestamp() -> Option<String> {
+  let seconds = env!("BUILD_TIMESTAMP").parse::<i64>().ok()?;
+  DateTime::from_timestamp(seconds, 0).map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn features() -> Vec<String> {
+  env!("CARGO_FEATURES")
+    .split(',')
+    .filter(|feature| !feature.is_empty())
+    .map(str::to_string)
+    .collect()
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  #[tokio::test]
+  async fn test_health_index_has_status_field() {
+    let result = serde_json::to_value(index().await).unwrap();
+    assert!(result.get("status").is_some());
+    assert_eq!(result.get("status").unwrap(), "ok");
+  }
+
   #[tokio::test]
   async fn test_health_index_returns_ok() {
     let result = index().await;
-    assert_eq!(result, json!({ "status": "ok" }));
+    assert_eq!(result.status, "ok");
+    assert!(result.checks.is_none());
+  }
+
+  #[test]
+  fn test_build_timestamp_is_rfc3339() {
+    let timestamp = build_timestamp().unwrap();
+    assert!(DateTime::parse_from_rfc3339(&timestamp).is_ok());
   }
 
   #[tokio::test]
-  async fn test_health_index_has_status_field() {
-    let result = index().await;
-    assert!(result.get("status").is_some());
-    assert_eq!(result.get("status").unwrap(), "ok");
+  async fn test_health_live_returns_ok() {
+    let result = live().await;
+    assert_eq!(result.status, "ok");
+    assert!(result.checks.is_none());
   }
 }
@@ -1,20 +1,50 @@
+pub mod admin;
 pub mod auth;
 pub mod health;
+pub mod repositories;
 pub mod users;
 
+use std::time::Duration;
+
 use axum::{extract::State, Router};
 
 use crate::app::AppState;
+use crate::common::cfg::Config;
+use crate::database::{seeders::SeederRegistry, Db};
+use crate::modules::health::checks::{DatabaseCheck, HealthRegistry, MigrationsCheck};
+use crate::modules::users::seeders::{AdminSeeder, FakeUsersSeeder, FixtureUsersSeeder};
 
 pub fn router(State(state): State<AppState>) -> Router<AppState> {
-  let router_auth: Router<AppState> = auth::router();
+  let router_admin: Router<AppState> = admin::router(axum::extract::State(state.clone()));
+  let router_auth: Router<AppState> = auth::router(axum::extract::State(state.clone()));
   let router_health: Router<AppState> = health::router();
   let router_users: Router<AppState> = users::router(axum::extract::State(state));
 
   let routers: Router<AppState> = Router::new()
+    .merge(router_admin)
     .merge(router_auth)
     .merge(router_health)
     .merge(router_users);
 
   Router::new().nest("/api", routers)
 }
+
+/// Registers the health checks of the dependencies the modules rely on.
+pub fn health_checks(cfg: &Config, db: &Db) -> HealthRegistry {
+  HealthRegistry::new(Duration::from_secs(cfg.health_check_timeout))
+    .register(DatabaseCheck::new(db.clone()))
+    .register(MigrationsCheck::new(db.clone()))
+}
+
+/// Registers the seeders of the modules. Fake users are only created if `fake_users` is not 0.
+pub fn seeders(cfg: &Config, fake_users: u32) -> SeederRegistry {
+  let registry = SeederRegistry::new()
+    .register(AdminSeeder::new(cfg))
+    .register(FixtureUsersSeeder);
+
+  if fake_users > 0 {
+    registry.register(FakeUsersSeeder::new(fake_users))
+  } else {
+    registry
+  }
+}
@@ -0,0 +1,24 @@
+#[cfg(test)]
+use mockall::automock;
+
+use crate::database::conn::Conn;
+use crate::modules::users::repository::{SeaOrmUserRepository, UserRepository};
+
+/// Creates the repositories of the modules on the connection a request uses: a replica for
+/// reads, the primary for writes, or the request transaction.
+///
+/// The application holds the sea-orm implementation, tests replace it with
+/// `MockRepositories` returning mock repositories.
+#[cfg_attr(test, automock)]
+pub trait Repositories: Send + Sync {
+  fn users(&self, conn: Conn) -> Box<dyn UserRepository>;
+}
+
+/// The sea-orm implementation of `Repositories`.
+pub struct SeaOrmRepositories;
+
+impl Repositories for SeaOrmRepositories {
+  fn users(&self, conn: Conn) -> Box<dyn UserRepository> {
+    Box::new(SeaOrmUserRepository::new(conn))
+  }
+}
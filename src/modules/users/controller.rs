@@ -1,13 +1,17 @@
 use axum::{
   extract::{Path, State},
+  http::StatusCode,
   Json,
 };
-use serde_json::Value;
 use uuid::Uuid;
 
+use crate::common::{read_your_writes::Reader, transaction::Tx};
 use crate::modules::users::dto::UserCreate;
 use crate::{app::AppState, modules::users::dto::UserDto};
-use crate::{common::api_error::ApiError, modules::users::service};
+use crate::{
+  common::api_error::{ApiError, ApiErrorResp},
+  modules::users::service,
+};
 
 #[utoipa::path(
   get,
@@ -15,14 +19,21 @@ use crate::{common::api_error::ApiError, modules::users::service};
   path = "/api/v1/users",
   operation_id = "usersIndex",
   responses(
-      (status = 200, description = "List users", body = [UserDto])
+    (status = 200, description = "List users", body = [UserDto]),
+    (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+    (status = 403, description = "Admin role required", body = ApiErrorResp)
   ),
   security(
-    ("bearerAuth" = [])
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
   )
 )]
-pub async fn index(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
-  let result = service::index(&state.db.conn).await?;
+pub async fn index(
+  State(state): State<AppState>,
+  Reader(db): Reader,
+) -> Result<Json<Vec<UserDto>>, ApiError> {
+  let users = state.repositories.users(db.into());
+  let result = service::index(&*users).await?;
   Ok(Json(result))
 }
 
@@ -33,17 +44,30 @@ pub async fn index(State(state): State<AppState>) -> Result<Json<Value>, ApiErro
   operation_id = "usersCreate",
   request_body = UserCreate,
   responses(
-      (status = 200, description = "Create a user", body = UserDto)
+    (status = 200, description = "Create a user", body = UserDto),
+    (status = 400, description = "Invalid payload", body = ApiErrorResp),
+    (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+    (status = 403, description = "Admin role required", body = ApiErrorResp),
+    (status = 409, description = "Email already exists", body = ApiErrorResp)
   ),
   security(
-    ("bearerAuth" = [])
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
   )
 )]
 pub async fn create(
   State(state): State<AppState>,
   Json(user): Json<UserCreate>,
-) -> Result<Json<Value>, ApiError> {
-  let result = service::create(&state.db.conn, user.email, user.password, user.name).await?;
+) -> Result<Json<UserDto>, ApiError> {
+  let users = state.repositories.users(state.db.writer().into());
+  let result = service::create(
+    &*users,
+    &state.user_events,
+    user.email,
+    user.password,
+    user.name,
+  )
+  .await?;
   Ok(Json(result))
 }
 
@@ -57,19 +81,25 @@ pub async fn create(
   ),
   responses(
     (status = 200, description = "Get user details", body = UserDto),
-    (status = 404, description = "User not found")
+    (status = 400, description = "Invalid user ID", body = ApiErrorResp),
+    (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+    (status = 403, description = "Admin role required", body = ApiErrorResp),
+    (status = 404, description = "User not found", body = ApiErrorResp)
   ),
   security(
-    ("bearerAuth" = [])
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
   )
 )]
 pub async fn show(
   State(state): State<AppState>,
+  Reader(db): Reader,
   Path(user_id): Path<String>,
-) -> Result<Json<Value>, ApiError> {
+) -> Result<Json<UserDto>, ApiError> {
   let id = Uuid::parse_str(&user_id)
     .map_err(|_| ApiError::InvalidRequest("Invalid user ID".to_string()))?;
-  let result = service::show(&state.db.conn, id).await?;
+  let users = state.repositories.users(db.into());
+  let result = service::show(&*users, id).await?;
   Ok(Json(result))
 }
 
@@ -84,20 +114,27 @@ pub async fn show(
   request_body = UserCreate,
   responses(
     (status = 200, description = "Update user", body = UserDto),
-    (status = 404, description = "User not found")
+    (status = 400, description = "Invalid user ID or payload", body = ApiErrorResp),
+    (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+    (status = 403, description = "Admin role required", body = ApiErrorResp),
+    (status = 404, description = "User not found", body = ApiErrorResp)
   ),
   security(
-    ("bearerAuth" = [])
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
   )
 )]
 pub async fn update(
   State(state): State<AppState>,
+  tx: Tx,
   Path(user_id): Path<String>,
   Json(user): Json<UserCreate>,
-) -> Result<Json<Value>, ApiError> {
+) -> Result<Json<UserDto>, ApiError> {
   let id = Uuid::parse_str(&user_id)
     .map_err(|_| ApiError::InvalidRequest("Invalid user ID".to_string()))?;
-  let result = service::update(&state.db.conn, id, user.name).await?;
+  let events = state.user_events.in_transaction(&tx);
+  let users = state.repositories.users(tx.into());
+  let result = service::update(&*users, &events, id, user.name).await?;
   Ok(Json(result))
 }
 
@@ -111,17 +148,90 @@ pub async fn update(
   ),
   responses(
     (status = 204, description = "User deleted successfully"),
-    (status = 404, description = "User not found")
+    (status = 400, description = "Invalid user ID", body = ApiErrorResp),
+    (status = 401, description = "Missing or invalid token", body = ApiErrorResp),
+    (status = 403, description = "Admin role required", body = ApiErrorResp),
+    (status = 404, description = "User not found", body = ApiErrorResp)
   ),
   security(
-    ("bearerAuth" = [])
+    ("bearerAuth" = []),
+    ("sessionCookie" = [])
   )
 )]
 pub async fn destroy(
   State(state): State<AppState>,
   Path(user_id): Path<String>,
-) -> Result<(), ApiError> {
+) -> Result<StatusCode, ApiError> {
   let id = Uuid::parse_str(&user_id)
     .map_err(|_| ApiError::InvalidRequest("Invalid user ID".to_string()))?;
-  service::destroy(&state.db.conn, id).await
+  let users = state.repositories.users(state.db.writer().into());
+  service::destroy(&*users, id).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sea_orm::DatabaseConnection;
+
+  use super::*;
+  use crate::common::cfg;
+  use crate::database::Db;
+  use crate::modules::repositories::MockRepositories;
+  use crate::modules::users::{
+    entities,
+    enums::{UserRole, UserStatus},
+    repository::MockUserRepository,
+  };
+
+  fn state(repositories: MockRepositories) -> AppState {
+    let cfg = cfg::testing::configuration(&[]);
+    AppState::new(Arc::new(cfg), Db::disconnected()).with_repositories(Arc::new(repositories))
+  }
+
+  #[tokio::test]
+  async fn test_index_lists_users() {
+    let mut repositories = MockRepositories::new();
+    repositories.expect_users().returning(|_| {
+      let mut users = MockUserRepository::new();
+      users.expect_find_all().returning(|| {
+        Ok(vec![entities::Model {
+          id: Uuid::nil(),
+          email: "jane@example.com".to_string(),
+          name: "Jane".to_string(),
+          password: "hash".to_string(),
+          status: UserStatus::Active,
+          role: UserRole::User,
+          created_at: None,
+          updated_at: None,
+          token_version: 0,
+        }])
+      });
+      Box::new(users)
+    });
+
+    let Json(result) = index(
+      State(state(repositories)),
+      Reader(DatabaseConnection::Disconnected),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result[0].email, "jane@example.com");
+  }
+
+  #[tokio::test]
+  async fn test_show_rejects_invalid_id() {
+    let mut repositories = MockRepositories::new();
+    repositories.expect_users().never();
+
+    let err = show(
+      State(state(repositories)),
+      Reader(DatabaseConnection::Disconnected),
+      Path("not-a-uuid".to_string()),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, ApiError::InvalidRequest(_)));
+  }
 }
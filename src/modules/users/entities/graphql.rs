@@ -0,0 +1,43 @@
+//! The users as exposed by the generated GraphQL schema.
+//!
+//! The password hash is left out, so that it is neither returned nor usable in filters and
+//! ordering. Users are only written through the service layer, see `users::graphql`.
+
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+
+use crate::modules::users::enums::{UserRole, UserStatus};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "users")]
+pub struct Model {
+  #[sea_orm(primary_key, auto_increment = false)]
+  pub id: Uuid,
+  pub email: String,
+  pub name: String,
+  pub status: UserStatus,
+  pub role: UserRole,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub created_at: Option<DateTime<Utc>>,
+  #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl From<super::Model> for Model {
+  fn from(user: super::Model) -> Self {
+    Self {
+      id: user.id,
+      email: user.email,
+      name: user.name,
+      status: user.status,
+      role: user.role,
+      created_at: user.created_at,
+      updated_at: user.updated_at,
+    }
+  }
+}
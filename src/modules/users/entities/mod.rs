@@ -1,3 +1,5 @@
+pub mod graphql;
+
 use chrono::{DateTime, Utc};
 use sea_orm::{entity::prelude::*, ActiveValue::Set};
 use serde::{Deserialize, Serialize};
@@ -18,6 +20,8 @@ pub struct Model {
   pub created_at: Option<DateTime<Utc>>,
   #[sea_orm(column_type = "TimestampWithTimeZone", nullable)]
   pub updated_at: Option<DateTime<Utc>>,
+  /// Bumped when the password changes, the tokens carrying an older version are rejected.
+  pub token_version: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
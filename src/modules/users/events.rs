@@ -0,0 +1,8 @@
+use crate::modules::users::entities;
+
+/// Changes of users, published by the users service.
+#[derive(Clone, Debug)]
+pub enum UserEvent {
+  Created(entities::Model),
+  Updated(entities::Model),
+}
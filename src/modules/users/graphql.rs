@@ -0,0 +1,167 @@
+use async_graphql::{
+  dynamic::{Field, FieldFuture, FieldValue, InputValue, Object, ResolverContext, TypeRef},
+  to_value, Value,
+};
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use crate::common::{api_error::ApiError, events::EventBus, transaction::Tx};
+use crate::database::conn::Conn;
+use crate::modules::auth::{dto::ChangePasswordRequest, guards::graphql_guards, service as auth};
+use crate::modules::users::{events::UserEvent, repository::UserRepository, service};
+use crate::query_root::Services;
+
+/// The type of the users returned by the hand-written fields, mirroring `UserDto`.
+pub const USER: &str = "User";
+
+/// The `User` type, resolved from a serialized `UserDto`.
+pub fn user_object() -> Object {
+  [
+    ("id", "id", TypeRef::named_nn(TypeRef::ID)),
+    ("email", "email", TypeRef::named_nn(TypeRef::STRING)),
+    ("name", "name", TypeRef::named_nn(TypeRef::STRING)),
+    ("status", "status", TypeRef::named_nn(TypeRef::STRING)),
+    ("role", "role", TypeRef::named_nn(TypeRef::STRING)),
+    ("createdAt", "created_at", TypeRef::named(TypeRef::STRING)),
+    ("updatedAt", "updated_at", TypeRef::named(TypeRef::STRING)),
+  ]
+  .into_iter()
+  .fold(Object::new(USER), |object, (name, key, ty)| {
+    object.field(json_field(name, key, ty))
+  })
+}
+
+/// A field resolved from a key of its parent, a JSON object returned by a service.
+pub fn json_field(name: &str, key: &'static str, ty: TypeRef) -> Field {
+  Field::new(name, ty, move |ctx| {
+    FieldFuture::new(async move {
+      let value = match ctx.parent_value.as_value() {
+        Some(Value::Object(object)) => object.get(key).cloned(),
+        _ => None,
+      };
+      Ok(
+        value
+          .filter(|value| *value != Value::Null)
+          .map(FieldValue::value),
+      )
+    })
+  })
+}
+
+/// The users repository on the connection of the request: the transaction of mutations, or the
+/// reader of queries.
+pub fn repository(ctx: &ResolverContext) -> async_graphql::Result<Box<dyn UserRepository>> {
+  let conn: Conn = match ctx.data_opt::<Tx>() {
+    Some(tx) => tx.clone().into(),
+    None => ctx.data::<DatabaseConnection>()?.into(),
+  };
+  Ok(ctx.data::<Services>()?.repositories.users(conn))
+}
+
+/// The user events, published once the transaction of the request is committed.
+pub fn events(ctx: &ResolverContext) -> async_graphql::Result<EventBus<UserEvent>> {
+  let events = &ctx.data::<Services>()?.user_events;
+  Ok(match ctx.data_opt::<Tx>() {
+    Some(tx) => events.in_transaction(tx),
+    None => events.clone(),
+  })
+}
+
+/// The self-service queries.
+pub fn query_fields() -> Vec<Field> {
+  vec![Field::new("me", TypeRef::named(USER), |ctx| {
+    FieldFuture::new(async move {
+      let Ok(user) = graphql_guards::current_user(&ctx) else {
+        return Ok(None);
+      };
+      let user = service::show(repository(&ctx)?.as_ref(), parse_id(&user.id)?).await?;
+      Ok(Some(FieldValue::value(to_value(user)?)))
+    })
+  })]
+}
+
+/// The mutations of the users, going through the service layer so that passwords are hashed and
+/// events published.
+pub fn mutation_fields() -> Vec<Field> {
+  vec![
+    Field::new("createUser", TypeRef::named_nn(USER), |ctx| {
+      FieldFuture::new(async move {
+        require_admin(&ctx)?;
+        let user = service::create(
+          repository(&ctx)?.as_ref(),
+          &events(&ctx)?,
+          ctx.args.try_get("email")?.string()?.to_string(),
+          ctx.args.try_get("password")?.string()?.to_string(),
+          ctx.args.try_get("name")?.string()?.to_string(),
+        )
+        .await?;
+        Ok(Some(FieldValue::value(to_value(user)?)))
+      })
+    })
+    .argument(InputValue::new("email", TypeRef::named_nn(TypeRef::STRING)))
+    .argument(InputValue::new(
+      "password",
+      TypeRef::named_nn(TypeRef::STRING),
+    ))
+    .argument(InputValue::new("name", TypeRef::named_nn(TypeRef::STRING))),
+    Field::new("updateUser", TypeRef::named_nn(USER), |ctx| {
+      FieldFuture::new(async move {
+        require_admin(&ctx)?;
+        let user = service::update(
+          repository(&ctx)?.as_ref(),
+          &events(&ctx)?,
+          parse_id(ctx.args.try_get("id")?.string()?)?,
+          ctx.args.try_get("name")?.string()?.to_string(),
+        )
+        .await?;
+        Ok(Some(FieldValue::value(to_value(user)?)))
+      })
+    })
+    .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+    .argument(InputValue::new("name", TypeRef::named_nn(TypeRef::STRING))),
+    Field::new("deleteUser", TypeRef::named_nn(TypeRef::BOOLEAN), |ctx| {
+      FieldFuture::new(async move {
+        require_admin(&ctx)?;
+        let id = parse_id(ctx.args.try_get("id")?.string()?)?;
+        service::destroy(repository(&ctx)?.as_ref(), id).await?;
+        Ok(Some(FieldValue::value(true)))
+      })
+    })
+    .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
+    Field::new(
+      "changePassword",
+      TypeRef::named_nn(TypeRef::BOOLEAN),
+      |ctx| {
+        FieldFuture::new(async move {
+          let id = parse_id(&graphql_guards::current_user(&ctx)?.id)?;
+          let req = ChangePasswordRequest {
+            current_password: ctx.args.try_get("currentPassword")?.string()?.to_string(),
+            new_password: ctx.args.try_get("newPassword")?.string()?.to_string(),
+          };
+          auth::change_password(repository(&ctx)?.as_ref(), id, req).await?;
+          Ok(Some(FieldValue::value(true)))
+        })
+      },
+    )
+    .argument(InputValue::new(
+      "currentPassword",
+      TypeRef::named_nn(TypeRef::STRING),
+    ))
+    .argument(InputValue::new(
+      "newPassword",
+      TypeRef::named_nn(TypeRef::STRING),
+    )),
+  ]
+}
+
+fn require_admin(ctx: &ResolverContext) -> Result<(), ApiError> {
+  graphql_guards::current_user(ctx)?;
+  if !graphql_guards::is_admin(ctx) {
+    return Err(ApiError::Forbidden("Admin role required".to_string()));
+  }
+  Ok(())
+}
+
+fn parse_id(id: &str) -> Result<Uuid, ApiError> {
+  Uuid::parse_str(id).map_err(|_| ApiError::InvalidRequest("Invalid user id".to_string()))
+}
@@ -2,15 +2,25 @@ pub mod controller;
 pub mod dto;
 pub mod entities;
 pub mod enums;
+pub mod events;
+pub mod graphql;
+pub mod repository;
+pub mod seeders;
 pub mod service;
+pub mod subscriptions;
 
-use axum::{extract::State, Router};
+use axum::{extract::State, handler::Handler, Router};
 use axum_extra::routing::Resource;
 
 use crate::app::AppState;
+use crate::common::transaction::transaction;
 use crate::modules::auth::guards::{admin_guard, auth_guard};
 
 pub fn router(State(state): State<AppState>) -> axum::Router<AppState> {
+  // Updates lock the user they look up until they write it back, which only lasts as long as the
+  // transaction. Deletes are a single statement and need none.
+  let transaction = axum::middleware::from_fn_with_state(state.clone(), transaction);
+
   let resources = Resource::named("users")
     // Define a route for `GET /users`
     .index(controller::index)
@@ -19,7 +29,7 @@ pub fn router(State(state): State<AppState>) -> axum::Router<AppState> {
     // `GET /users/{user_id}`
     .show(controller::show)
     // `PUT or PATCH /users/{user_id}`
-    .update(controller::update)
+    .update(controller::update.layer(transaction))
     // `DELETE /users/{user_id}`
     .destroy(controller::destroy);
 
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect};
+use uuid::Uuid;
+
+use crate::database::conn::Conn;
+use crate::modules::users::entities::{self, Entity as UserEntity};
+
+/// Persistence of users.
+///
+/// Services depend on this trait rather than on sea-orm, so that they can be tested with
+/// `MockUserRepository`.
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+  async fn find_all(&self) -> Result<Vec<entities::Model>, DbErr>;
+
+  async fn find_by_id(&self, id: Uuid) -> Result<Option<entities::Model>, DbErr>;
+
+  /// Finds the user and locks it until the end of the transaction, so that it cannot be changed
+  /// or deleted before being written back.
+  async fn find_by_id_for_update(&self, id: Uuid) -> Result<Option<entities::Model>, DbErr>;
+
+  async fn find_by_email(&self, email: &str) -> Result<Option<entities::Model>, DbErr>;
+
+  async fn insert(&self, user: entities::ActiveModel) -> Result<entities::Model, DbErr>;
+
+  async fn update(&self, user: entities::ActiveModel) -> Result<entities::Model, DbErr>;
+
+  /// Deletes the user, returning the number of deleted rows.
+  async fn delete(&self, id: Uuid) -> Result<u64, DbErr>;
+}
+
+/// The sea-orm implementation of `UserRepository`.
+pub struct SeaOrmUserRepository {
+  conn: Conn,
+}
+
+impl SeaOrmUserRepository {
+  pub fn new(conn: impl Into<Conn>) -> Self {
+    Self { conn: conn.into() }
+  }
+}
+
+#[async_trait]
+impl UserRepository for SeaOrmUserRepository {
+  async fn find_all(&self) -> Result<Vec<entities::Model>, DbErr> {
+    UserEntity::find().all(&self.conn).await
+  }
+
+  async fn find_by_id(&self, id: Uuid) -> Result<Option<entities::Model>, DbErr> {
+    UserEntity::find_by_id(id).one(&self.conn).await
+  }
+
+  async fn find_by_id_for_update(&self, id: Uuid) -> Result<Option<entities::Model>, DbErr> {
+    UserEntity::find_by_id(id)
+      .lock_exclusive()
+      .one(&self.conn)
+      .await
+  }
+
+  async fn find_by_email(&self, email: &str) -> Result<Option<entities::Model>, DbErr> {
+    UserEntity::find()
+      .filter(entities::Column::Email.eq(email))
+      .one(&self.conn)
+      .await
+  }
+
+  async fn insert(&self, user: entities::ActiveModel) -> Result<entities::Model, DbErr> {
+    user.insert(&self.conn).await
+  }
+
+  async fn update(&self, user: entities::ActiveModel) -> Result<entities::Model, DbErr> {
+    user.update(&self.conn).await
+  }
+
+  async fn delete(&self, id: Uuid) -> Result<u64, DbErr> {
+    let result = UserEntity::delete_by_id(id).exec(&self.conn).await?;
+    Ok(result.rows_affected)
+  }
+}
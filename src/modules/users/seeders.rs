@@ -0,0 +1,281 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use async_trait::async_trait;
+use bcrypt::{hash, DEFAULT_COST};
+use fake::{
+  faker::name::en::{FirstName, LastName},
+  rand::{rngs::StdRng, SeedableRng},
+  Fake,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, Set};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::common::cfg::{Config, Secret};
+use crate::database::seeders::{Fixtures, Seeder};
+use crate::modules::users::entities::{self, Entity as UserEntity};
+use crate::modules::users::enums::{UserRole, UserStatus};
+use crate::modules::users::repository::SeaOrmUserRepository;
+use crate::modules::users::service;
+
+/// The password of every fake user, so that load tests can log in as any of them.
+pub const FAKE_USER_PASSWORD: &str = "password";
+
+/// Number of users inserted per statement.
+const BATCH_SIZE: usize = 1_000;
+
+/// Creates the admin configured with `ADMIN_EMAIL` and `ADMIN_PASSWORD`.
+///
+/// An existing user with that email is promoted to admin, keeping their password.
+pub struct AdminSeeder {
+  email: Option<String>,
+  name: String,
+  password: Option<Secret>,
+}
+
+impl AdminSeeder {
+  pub fn new(cfg: &Config) -> Self {
+    Self {
+      email: cfg.admin_email.clone(),
+      name: cfg.admin_name.clone(),
+      password: cfg.admin_password.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl Seeder for AdminSeeder {
+  fn name(&self) -> &'static str {
+    "admin"
+  }
+
+  async fn seed(&self, db: &DatabaseConnection, _: &Fixtures) -> anyhow::Result<()> {
+    let Some(email) = &self.email else {
+      tracing::debug!("ADMIN_EMAIL is not set, no admin to create");
+      return Ok(());
+    };
+
+    let exists = !existing_emails(db, vec![email.clone()]).await?.is_empty();
+    let password = match exists {
+      true => None,
+      false => self.password.as_ref().map(|p| p.expose().to_string()),
+    };
+    let users = SeaOrmUserRepository::new(db);
+    let user = service::upsert_admin(&users, email.clone(), self.name.clone(), password).await?;
+    info!(email = %user.email, created = !exists, "Admin ready");
+    Ok(())
+  }
+}
+
+/// A user of the `users` fixture.
+#[derive(Deserialize, Debug)]
+pub struct UserFixture {
+  pub email: String,
+  pub name: String,
+  pub password: String,
+  #[serde(default)]
+  pub role: UserRole,
+  #[serde(default = "active")]
+  pub status: UserStatus,
+}
+
+fn active() -> UserStatus {
+  UserStatus::Active
+}
+
+/// Creates the users of the `users` fixture that do not exist yet.
+pub struct FixtureUsersSeeder;
+
+#[async_trait]
+impl Seeder for FixtureUsersSeeder {
+  fn name(&self) -> &'static str {
+    "users"
+  }
+
+  async fn seed(&self, db: &DatabaseConnection, fixtures: &Fixtures) -> anyhow::Result<()> {
+    let users: Vec<UserFixture> = fixtures.get("users")?;
+    let existing = existing_emails(db, users.iter().map(|u| u.email.clone()).collect()).await?;
+
+    let users: Vec<UserFixture> = users
+      .into_iter()
+      .filter(|u| !existing.contains(&u.email))
+      .collect();
+    let hashes = hash_passwords(users.iter().map(|u| u.password.clone()).collect()).await?;
+
+    let missing = users
+      .into_iter()
+      .map(|user| entities::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        password: Set(hashes[&user.password].clone()),
+        email: Set(user.email),
+        name: Set(user.name),
+        status: Set(user.status),
+        role: Set(user.role),
+        ..Default::default()
+      })
+      .collect();
+
+    let created = insert(db, missing).await?;
+    info!(created, existing = existing.len(), "Fixture users seeded");
+    Ok(())
+  }
+}
+
+/// Creates `count` fake users with realistic names for load tests.
+///
+/// Users are generated from their index, so running the seeder again creates the same users and
+/// skips the ones that already exist.
+pub struct FakeUsersSeeder {
+  count: u32,
+}
+
+impl FakeUsersSeeder {
+  pub fn new(count: u32) -> Self {
+    Self { count }
+  }
+}
+
+#[async_trait]
+impl Seeder for FakeUsersSeeder {
+  fn name(&self) -> &'static str {
+    "fake-users"
+  }
+
+  async fn seed(&self, db: &DatabaseConnection, _: &Fixtures) -> anyhow::Result<()> {
+    // Hashing is slow by design, every fake user shares the same hash.
+    let password =
+      tokio::task::spawn_blocking(|| hash(FAKE_USER_PASSWORD.as_bytes(), DEFAULT_COST)).await??;
+
+    let mut created = 0;
+    let indexes: Vec<u32> = (0..self.count).collect();
+    for batch in indexes.chunks(BATCH_SIZE) {
+      let users: Vec<entities::ActiveModel> = batch
+        .iter()
+        .map(|&index| fake_user(index, &password))
+        .collect();
+      let emails = users.iter().map(|u| u.email.as_ref().clone()).collect();
+      let existing = existing_emails(db, emails).await?;
+
+      let missing = users
+        .into_iter()
+        .filter(|u| !existing.contains(u.email.as_ref()))
+        .collect();
+      created += insert(db, missing).await?;
+    }
+
+    info!(created, total = self.count, "Fake users seeded");
+    Ok(())
+  }
+}
+
+/// Hashes each distinct password once, on a blocking thread since hashing is slow by design.
+async fn hash_passwords(passwords: Vec<String>) -> anyhow::Result<HashMap<String, String>> {
+  tokio::task::spawn_blocking(move || {
+    let mut hashes = HashMap::new();
+    for password in passwords {
+      if let Entry::Vacant(entry) = hashes.entry(password) {
+        let hashed = hash(entry.key().as_bytes(), DEFAULT_COST)?;
+        entry.insert(hashed);
+      }
+    }
+    Ok(hashes)
+  })
+  .await?
+}
+
+/// Generates the fake user number `index`.
+fn fake_user(index: u32, password_hash: &str) -> entities::ActiveModel {
+  let mut rng = StdRng::seed_from_u64(u64::from(index));
+  let first_name: String = FirstName().fake_with_rng(&mut rng);
+  let last_name: String = LastName().fake_with_rng(&mut rng);
+
+  let local_part: String = format!("{first_name}.{last_name}")
+    .chars()
+    .filter(|c| c.is_ascii_alphanumeric() || *c == '.')
+    .collect();
+
+  entities::ActiveModel {
+    id: Set(Uuid::new_v4()),
+    email: Set(format!(
+      "{}.{}@example.com",
+      local_part.to_lowercase(),
+      index
+    )),
+    name: Set(format!("{first_name} {last_name}")),
+    password: Set(password_hash.to_string()),
+    status: Set(UserStatus::Active),
+    role: Set(UserRole::User),
+    ..Default::default()
+  }
+}
+
+/// Returns the emails among `emails` that belong to existing users.
+async fn existing_emails(
+  db: &DatabaseConnection,
+  emails: Vec<String>,
+) -> Result<HashSet<String>, sea_orm::DbErr> {
+  let existing: Vec<String> = UserEntity::find()
+    .select_only()
+    .column(entities::Column::Email)
+    .filter(entities::Column::Email.is_in(emails))
+    .into_tuple()
+    .all(db)
+    .await?;
+  Ok(existing.into_iter().collect())
+}
+
+async fn insert(
+  db: &DatabaseConnection,
+  users: Vec<entities::ActiveModel>,
+) -> Result<u64, sea_orm::DbErr> {
+  if users.is_empty() {
+    return Ok(0);
+  }
+  UserEntity::insert_many(users)
+    .exec_without_returning(db)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fake_users_are_deterministic() {
+    let first = fake_user(42, "hash");
+    let again = fake_user(42, "hash");
+    let other = fake_user(43, "hash");
+
+    assert_eq!(first.email, again.email);
+    assert_eq!(first.name, again.name);
+    assert_ne!(first.email, other.email);
+    assert!(first.email.as_ref().ends_with(".42@example.com"));
+  }
+
+  #[test]
+  fn test_user_fixture_defaults() {
+    let user: UserFixture = serde_json::from_value(serde_json::json!({
+      "email": "jane@example.com",
+      "name": "Jane",
+      "password": "secret"
+    }))
+    .unwrap();
+    assert_eq!(user.role, UserRole::User);
+    assert_eq!(user.status, UserStatus::Active);
+  }
+
+  #[tokio::test]
+  async fn test_passwords_are_hashed_once_each() {
+    let passwords = vec![
+      "secret".to_string(),
+      "other".to_string(),
+      "secret".to_string(),
+    ];
+    let hashes = hash_passwords(passwords).await.unwrap();
+
+    assert_eq!(hashes.len(), 2);
+    assert!(bcrypt::verify("secret", &hashes["secret"]).unwrap());
+    assert!(bcrypt::verify("other", &hashes["other"]).unwrap());
+  }
+}
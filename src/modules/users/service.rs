@@ -1,24 +1,26 @@
-use bcrypt::{hash, DEFAULT_COST};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use sea_orm::Set;
 use uuid::Uuid;
 
-use crate::common::api_error::ApiError;
+use crate::common::{api_error::ApiError, events::EventBus};
 use crate::modules::users::dto::UserDto;
-use crate::modules::users::entities::{self, Entity as UserEntity};
-use crate::modules::users::enums::UserStatus;
+use crate::modules::users::entities;
+use crate::modules::users::enums::{UserRole, UserStatus};
+use crate::modules::users::events::UserEvent;
+use crate::modules::users::repository::UserRepository;
 
-pub async fn index(db: &DatabaseConnection) -> Result<serde_json::Value, ApiError> {
-  let users = UserEntity::find().all(db).await?;
-  let responses: Vec<UserDto> = users.into_iter().map(UserDto::from).collect();
-  Ok(serde_json::json!(responses))
+pub async fn index(users: &dyn UserRepository) -> Result<Vec<UserDto>, ApiError> {
+  let users = users.find_all().await?;
+  Ok(users.into_iter().map(UserDto::from).collect())
 }
 
 pub async fn create(
-  db: &DatabaseConnection,
+  users: &dyn UserRepository,
+  events: &EventBus<UserEvent>,
   email: String,
   password: String,
   name: String,
-) -> Result<serde_json::Value, ApiError> {
+) -> Result<UserDto, ApiError> {
   // Hash password
   let password_hash = hash(password.as_bytes(), DEFAULT_COST)
     .map_err(|e| ApiError::InternalError(anyhow::anyhow!("Failed to hash password: {}", e)))?;
@@ -32,56 +34,210 @@ pub async fn create(
     ..Default::default()
   };
 
-  let user = user.insert(db).await.map_err(|e| {
-    if e.to_string().contains("duplicate key") {
-      ApiError::InvalidRequest("Email already exists".to_string())
-    } else {
-      ApiError::InternalError(anyhow::anyhow!(e))
-    }
-  })?;
+  let user = users
+    .insert(user)
+    .await
+    .map_err(|e| ApiError::unique_violation(e, "Email already exists"))?;
+  events.publish(UserEvent::Created(user.clone()));
 
-  let response = UserDto::from(user);
-  Ok(serde_json::json!(response))
+  Ok(UserDto::from(user))
 }
 
-pub async fn show(db: &DatabaseConnection, id: Uuid) -> Result<serde_json::Value, ApiError> {
-  let user = UserEntity::find()
-    .filter(entities::Column::Id.eq(id))
-    .one(db)
+pub async fn show(users: &dyn UserRepository, id: Uuid) -> Result<UserDto, ApiError> {
+  let user = users
+    .find_by_id(id)
     .await?
     .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
-  let response = UserDto::from(user);
-  Ok(serde_json::json!(response))
+  Ok(UserDto::from(user))
 }
 
 pub async fn update(
-  db: &DatabaseConnection,
+  users: &dyn UserRepository,
+  events: &EventBus<UserEvent>,
   id: Uuid,
   name: String,
-) -> Result<serde_json::Value, ApiError> {
-  let user = UserEntity::find()
-    .filter(entities::Column::Id.eq(id))
-    .one(db)
+) -> Result<UserDto, ApiError> {
+  let user = users
+    .find_by_id_for_update(id)
     .await?
     .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
   let mut user: entities::ActiveModel = user.into();
   user.name = Set(name);
 
-  let user = user.update(db).await?;
-  let response = UserDto::from(user);
-  Ok(serde_json::json!(response))
-}
+  let user = users.update(user).await?;
+  events.publish(UserEvent::Updated(user.clone()));
 
-pub async fn destroy(db: &DatabaseConnection, id: Uuid) -> Result<(), ApiError> {
-  let user = UserEntity::find()
-    .filter(entities::Column::Id.eq(id))
-    .one(db)
-    .await?
-    .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+  Ok(UserDto::from(user))
+}
 
-  let user: entities::ActiveModel = user.into();
-  user.delete(db).await?;
+pub async fn destroy(users: &dyn UserRepository, id: Uuid) -> Result<(), ApiError> {
+  if users.delete(id).await? == 0 {
+    return Err(ApiError::NotFound("User not found".to_string()));
+  }
   Ok(())
 }
+
+/// Creates an admin user, or promotes the user with the given email to admin.
+///
+/// The password of an existing user is only changed if a different one is given, which revokes
+/// the tokens of the user.
+pub async fn upsert_admin(
+  users: &dyn UserRepository,
+  email: String,
+  name: String,
+  password: Option<String>,
+) -> Result<UserDto, ApiError> {
+  let existing = users.find_by_email(&email).await?;
+
+  // Seeding again with the same password must not log the admin out.
+  let changed = match (&existing, &password) {
+    (Some(user), Some(password)) => !verify(password, &user.password).unwrap_or(false),
+    _ => password.is_some(),
+  };
+  let password_hash = password
+    .filter(|_| changed)
+    .map(|password| hash(password.as_bytes(), DEFAULT_COST))
+    .transpose()
+    .map_err(|e| ApiError::InternalError(anyhow::anyhow!("Failed to hash password: {}", e)))?;
+
+  let user = match existing {
+    Some(user) => {
+      let token_version = user.token_version + 1;
+      let mut user: entities::ActiveModel = user.into();
+      user.role = Set(UserRole::Admin);
+      if let Some(password_hash) = password_hash {
+        user.password = Set(password_hash);
+        user.token_version = Set(token_version);
+      }
+      users.update(user).await?
+    }
+    None => {
+      let password_hash = password_hash.ok_or_else(|| {
+        ApiError::InvalidRequest("A password is required to create a user".to_string())
+      })?;
+      users
+        .insert(entities::ActiveModel {
+          id: Set(Uuid::new_v4()),
+          email: Set(email),
+          password: Set(password_hash),
+          name: Set(name),
+          status: Set(UserStatus::Active),
+          role: Set(UserRole::Admin),
+          ..Default::default()
+        })
+        .await?
+    }
+  };
+
+  Ok(UserDto::from(user))
+}
+
+#[cfg(test)]
+mod tests {
+  use mockall::predicate::eq;
+
+  use super::*;
+  use crate::modules::users::repository::MockUserRepository;
+
+  fn user(id: Uuid, email: &str) -> entities::Model {
+    entities::Model {
+      id,
+      email: email.to_string(),
+      name: "Jane".to_string(),
+      password: "hash".to_string(),
+      status: UserStatus::Active,
+      role: UserRole::User,
+      created_at: None,
+      updated_at: None,
+      token_version: 0,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_show_missing_user() {
+    let id = Uuid::new_v4();
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_id()
+      .with(eq(id))
+      .returning(|_| Ok(None));
+
+    let err = show(&users, id).await.unwrap_err();
+    assert!(matches!(err, ApiError::NotFound(_)));
+  }
+
+  #[tokio::test]
+  async fn test_update_changes_the_name() {
+    let id = Uuid::new_v4();
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_id_for_update()
+      .returning(move |_| Ok(Some(user(id, "jane@example.com"))));
+    users
+      .expect_update()
+      .withf(|user| user.name.as_ref() == "Janet")
+      .returning(move |_| {
+        Ok(entities::Model {
+          name: "Janet".to_string(),
+          ..user(id, "jane@example.com")
+        })
+      });
+
+    let events = EventBus::new();
+    let mut subscriber = events.subscribe();
+
+    let result = update(&users, &events, id, "Janet".to_string())
+      .await
+      .unwrap();
+    assert_eq!(result.name, "Janet");
+    assert!(matches!(
+      subscriber.try_recv(),
+      Ok(UserEvent::Updated(user)) if user.name == "Janet"
+    ));
+  }
+
+  #[tokio::test]
+  async fn test_destroy_missing_user() {
+    let mut users = MockUserRepository::new();
+    users.expect_delete().returning(|_| Ok(0));
+
+    let err = destroy(&users, Uuid::new_v4()).await.unwrap_err();
+    assert!(matches!(err, ApiError::NotFound(_)));
+  }
+
+  #[tokio::test]
+  async fn test_upsert_admin_promotes_existing_user() {
+    let id = Uuid::new_v4();
+    let mut users = MockUserRepository::new();
+    users
+      .expect_find_by_email()
+      .with(eq("jane@example.com"))
+      .returning(move |email| Ok(Some(user(id, email))));
+    users
+      .expect_update()
+      .withf(|user| {
+        user.role.as_ref() == &UserRole::Admin
+          && user.password.is_unchanged()
+          && user.token_version.is_unchanged()
+      })
+      .returning(move |_| {
+        Ok(entities::Model {
+          role: UserRole::Admin,
+          ..user(id, "jane@example.com")
+        })
+      });
+    users.expect_insert().never();
+
+    let admin = upsert_admin(
+      &users,
+      "jane@example.com".to_string(),
+      "Jane".to_string(),
+      None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(admin.role, "Admin");
+  }
+}
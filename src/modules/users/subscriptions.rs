@@ -0,0 +1,56 @@
+use async_graphql::dynamic::{FieldValue, SubscriptionField, SubscriptionFieldFuture, TypeRef};
+use seaography::{BuilderContext, EntityObjectBuilder};
+use tokio_stream::{
+  wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+  StreamExt,
+};
+
+use crate::common::events::EventBus;
+use crate::modules::auth::guards::graphql_guards;
+use crate::modules::users::{entities, events::UserEvent};
+
+/// The GraphQL subscriptions to changes of users, restricted to admins.
+pub fn fields(context: &'static BuilderContext) -> Vec<SubscriptionField> {
+  let type_name = EntityObjectBuilder { context }.type_name::<entities::graphql::Entity>();
+  vec![
+    field("userCreated", &type_name, |event| match event {
+      UserEvent::Created(user) => Some(user),
+      _ => None,
+    }),
+    field("userUpdated", &type_name, |event| match event {
+      UserEvent::Updated(user) => Some(user),
+      _ => None,
+    }),
+  ]
+}
+
+fn field(
+  name: &str,
+  type_name: &str,
+  select: fn(UserEvent) -> Option<entities::Model>,
+) -> SubscriptionField {
+  SubscriptionField::new(name, TypeRef::named_nn(type_name), move |ctx| {
+    SubscriptionFieldFuture::new(async move {
+      // The user is authenticated when the WebSocket connection is initialized, which is closed
+      // when their token expires.
+      if !graphql_guards::is_admin(&ctx) {
+        return Err("Admin role required".into());
+      }
+
+      let events = ctx.data::<EventBus<UserEvent>>()?.subscribe();
+      Ok(
+        BroadcastStream::new(events).filter_map(move |event| match event {
+          Ok(event) => select(event)
+            .map(|user| Ok(FieldValue::owned_any(entities::graphql::Model::from(user)))),
+          Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+            tracing::warn!(
+              skipped,
+              "GraphQL subscriber fell behind, events were dropped"
+            );
+            None
+          }
+        }),
+      )
+    })
+  })
+}
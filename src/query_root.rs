@@ -1,9 +1,17 @@
+use std::sync::Arc;
+
 use async_graphql::dynamic::*;
 use sea_orm::DatabaseConnection;
 use seaography::{async_graphql, lazy_static, Builder, BuilderContext};
 
-use crate::modules::auth::guards::graphql_guards;
-use crate::modules::users::{self, entities as usersEntities};
+use crate::common::{
+  cfg::Secret,
+  events::EventBus,
+  graphql::{limits::Limits, persisted::PersistedQueries, telemetry::Telemetry},
+};
+use crate::modules::auth::{self, guards::graphql_guards};
+use crate::modules::repositories::Repositories;
+use crate::modules::users::{self, entities::graphql as usersEntities, events::UserEvent};
 
 lazy_static::lazy_static! {
   static ref CONTEXT: BuilderContext = {
@@ -17,26 +25,63 @@ lazy_static::lazy_static! {
   };
 }
 
+/// What the hand-written fields use, besides the database connection of the request.
+#[derive(Clone)]
+pub struct Services {
+  pub repositories: Arc<dyn Repositories>,
+  pub user_events: EventBus<UserEvent>,
+  pub jwt_secret: Secret,
+}
+
 pub fn schema(
   database: DatabaseConnection,
-  depth: Option<usize>,
-  complexity: Option<usize>,
+  services: Services,
+  limits: Limits,
+  persisted: PersistedQueries,
+  telemetry: Telemetry,
 ) -> Result<Schema, SchemaError> {
   // Create a new schema builder with the provided database connection
   let mut builder = Builder::new(&CONTEXT, database.clone());
 
-  // Register the entities
-  seaography::register_entities!(builder, [usersEntities]);
+  // The builder has no subscriptions, they are served over WebSocket.
+  let subscription = users::subscriptions::fields(&CONTEXT)
+    .into_iter()
+    .fold(Subscription::new("Subscription"), |subscription, field| {
+      subscription.field(field)
+    });
+  builder.schema = Schema::build(
+    builder.query.type_name(),
+    Some(builder.mutation.type_name()),
+    Some(subscription.type_name()),
+  );
+
+  // Register the entities. Their mutations are hand-written, so that writes go through the
+  // service layer.
+  seaography::register_entities_without_relation!(builder, [usersEntities]);
+  builder.queries.extend(users::graphql::query_fields());
+  builder.mutations.extend(auth::graphql::mutation_fields());
+  builder.mutations.extend(users::graphql::mutation_fields());
+  builder.outputs.extend([
+    users::graphql::user_object(),
+    auth::graphql::auth_payload_object(),
+  ]);
 
   // Register the active enums
   builder.register_enumeration::<users::enums::UserStatus>();
   builder.register_enumeration::<users::enums::UserRole>();
 
   // Register the custom scalars
-  builder
-    .set_depth_limit(depth)
-    .set_complexity_limit(complexity)
+  let mut schema = builder
     .schema_builder()
+    .register(subscription)
+    .extension(telemetry)
+    .extension(persisted)
+    .extension(limits.clone())
     .data(database)
-    .finish()
+    .data(services.user_events.clone())
+    .data(services);
+  if !limits.introspection {
+    schema = schema.disable_introspection();
+  }
+  schema.finish()
 }
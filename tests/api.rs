@@ -0,0 +1,858 @@
+mod common;
+
+use axum::{
+  body::Body,
+  http::{header, Method, Request, StatusCode},
+};
+use base64::{engine::general_purpose, Engine};
+use serde_json::json;
+
+use common::{contract, TestApp, PASSWORD};
+use sea_orm::ConnectionTrait;
+use sea_orm_migration::MigratorTrait;
+use server::common::graphql::persisted::sha256;
+use server::database::migrations::Migrator;
+use server::modules::users::dto::UserDto;
+
+#[tokio::test]
+async fn test_register_and_login() {
+  let app = TestApp::spawn().await;
+
+  let registered = app.register("jane@example.com").await;
+  assert_eq!(registered.user.email, "jane@example.com");
+  assert_eq!(registered.user.role, "User");
+
+  let duplicate = app
+    .post(
+      "/api/v1/auth/register",
+      json!({ "email": "jane@example.com", "password": PASSWORD, "name": "Jane" }),
+      None,
+    )
+    .await;
+  assert_eq!(duplicate.status, StatusCode::CONFLICT);
+
+  let logged_in = app.login("jane@example.com", PASSWORD).await;
+  assert_eq!(logged_in.user.id, registered.user.id);
+
+  let wrong_password = app
+    .post(
+      "/api/v1/auth/login",
+      json!({ "email": "jane@example.com", "password": "wrong" }),
+      None,
+    )
+    .await;
+  assert_eq!(wrong_password.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_users_require_an_admin() {
+  let app = TestApp::spawn().await;
+
+  let anonymous = app.get("/api/v1/users", None).await;
+  assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+
+  let user = app.register("jane@example.com").await;
+  let forbidden = app.get("/api/v1/users", Some(&user.token)).await;
+  assert_eq!(forbidden.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_users_crud() {
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let token = Some(admin.token.as_str());
+
+  let created = app
+    .post(
+      "/api/v1/users",
+      json!({ "email": "john@example.com", "password": PASSWORD, "name": "John" }),
+      token,
+    )
+    .await;
+  assert_eq!(created.status, StatusCode::OK, "{}", created.body);
+  let created: UserDto = created.json();
+  let path = format!("/api/v1/users/{}", created.id);
+
+  let users: Vec<UserDto> = app.get("/api/v1/users", token).await.json();
+  assert_eq!(users.len(), 2);
+
+  let updated = app
+    .put(
+      &path,
+      json!({ "email": "john@example.com", "password": PASSWORD, "name": "Johnny" }),
+      token,
+    )
+    .await;
+  assert_eq!(updated.json::<UserDto>().name, "Johnny");
+
+  assert_eq!(
+    app.delete(&path, token).await.status,
+    StatusCode::NO_CONTENT
+  );
+  assert_eq!(app.get(&path, token).await.status, StatusCode::NOT_FOUND);
+  assert_eq!(app.delete(&path, token).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cookie_session_requires_a_csrf_token() {
+  let app = TestApp::spawn().await;
+  app.admin("admin@example.com").await;
+
+  let login = app
+    .post(
+      "/api/v1/auth/session",
+      json!({ "email": "admin@example.com", "password": PASSWORD }),
+      None,
+    )
+    .await;
+  assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+  let set_cookies: Vec<&str> = login
+    .headers
+    .get_all(header::SET_COOKIE)
+    .iter()
+    .map(|value| value.to_str().unwrap())
+    .collect();
+  assert_eq!(set_cookies.len(), 2);
+  assert!(set_cookies
+    .iter()
+    .any(|cookie| cookie.starts_with("session=") && cookie.contains("HttpOnly")));
+  let csrf_token = login.body["csrf_token"].as_str().unwrap();
+  let cookies = set_cookies
+    .iter()
+    .map(|cookie| cookie.split(';').next().unwrap())
+    .collect::<Vec<_>>()
+    .join("; ");
+
+  let request = |method: Method, path: &str, csrf_token: Option<&str>| {
+    let mut req = Request::builder()
+      .method(method)
+      .uri(path)
+      .header(header::COOKIE, &cookies);
+    if let Some(csrf_token) = csrf_token {
+      req = req.header("x-csrf-token", csrf_token);
+    }
+    app.send(req.body(Body::empty()).unwrap())
+  };
+
+  // Reads only need the cookie, writes also need the CSRF token.
+  assert_eq!(
+    request(Method::GET, "/api/v1/users", None).await.status,
+    StatusCode::OK
+  );
+  let user = app.register("jane@example.com").await;
+  let path = format!("/api/v1/users/{}", user.user.id);
+  assert_eq!(
+    request(Method::DELETE, &path, None).await.status,
+    StatusCode::FORBIDDEN
+  );
+  assert_eq!(
+    request(Method::DELETE, &path, Some("forged")).await.status,
+    StatusCode::FORBIDDEN
+  );
+  assert_eq!(
+    request(Method::DELETE, &path, Some(csrf_token))
+      .await
+      .status,
+    StatusCode::NO_CONTENT
+  );
+
+  // Other sites cannot log the browser out either.
+  assert_eq!(
+    request(Method::DELETE, "/api/v1/auth/session", None)
+      .await
+      .status,
+    StatusCode::FORBIDDEN
+  );
+  let logout = request(Method::DELETE, "/api/v1/auth/session", Some(csrf_token)).await;
+  assert_eq!(logout.status, StatusCode::NO_CONTENT);
+  assert!(logout
+    .headers
+    .get_all(header::SET_COOKIE)
+    .iter()
+    .all(|cookie| cookie.to_str().unwrap().contains("Max-Age=0")));
+}
+
+#[tokio::test]
+async fn test_databases_are_isolated() {
+  let first = TestApp::spawn().await;
+  let second = TestApp::spawn().await;
+
+  first.register("jane@example.com").await;
+  second.register("jane@example.com").await;
+}
+
+#[tokio::test]
+async fn test_pending_migrations_without_the_migrations_table() {
+  let app = TestApp::spawn().await;
+  assert!(app.db.pending_migrations().await.unwrap().is_empty());
+
+  app
+    .db
+    .writer()
+    .execute_unprepared("DROP TABLE seaql_migrations")
+    .await
+    .unwrap();
+  let pending = app.db.pending_migrations().await.unwrap();
+  assert_eq!(pending.len(), Migrator::migrations().len());
+}
+
+#[tokio::test]
+async fn test_health_info_reports_the_configuration_to_current_admins() {
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let user = app.register("jane@example.com").await;
+  let info = |token: Option<String>| {
+    let app = &app;
+    async move { app.get("/api/v1/health/info", token.as_deref()).await }
+  };
+
+  let anonymous = info(None).await;
+  assert_eq!(anonymous.status, StatusCode::OK);
+  assert_eq!(
+    anonymous.body["migration"].as_str(),
+    Migrator::migrations()
+      .last()
+      .map(|migration| migration.name())
+  );
+  assert!(anonymous.body.get("config").is_none());
+  assert!(info(Some(user.token)).await.body.get("config").is_none());
+  assert!(info(Some(admin.token.clone())).await.body["config"].is_object());
+
+  // Admins logged in with the session cookie see it too.
+  let login = app
+    .post(
+      "/api/v1/auth/session",
+      json!({ "email": "admin@example.com", "password": PASSWORD }),
+      None,
+    )
+    .await;
+  let session = login
+    .headers
+    .get_all(header::SET_COOKIE)
+    .iter()
+    .map(|cookie| cookie.to_str().unwrap().split(';').next().unwrap())
+    .find(|cookie| cookie.starts_with("session="))
+    .unwrap()
+    .to_string();
+  let with_session = app
+    .send(
+      Request::get("/api/v1/health/info")
+        .header(header::COOKIE, session)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+  assert!(with_session.body["config"].is_object());
+
+  // Demoted admins and revoked tokens no longer see it.
+  let writer = app.db.writer();
+  writer
+    .execute_unprepared("UPDATE users SET role = 'User'")
+    .await
+    .unwrap();
+  assert!(info(Some(admin.token.clone()))
+    .await
+    .body
+    .get("config")
+    .is_none());
+  writer
+    .execute_unprepared("UPDATE users SET role = 'Admin', token_version = token_version + 1")
+    .await
+    .unwrap();
+  assert!(info(Some(admin.token)).await.body.get("config").is_none());
+}
+
+#[tokio::test]
+async fn test_last_migration_includes_unknown_migrations() {
+  let app = TestApp::spawn().await;
+  app
+    .db
+    .writer()
+    .execute_unprepared(
+      "INSERT INTO seaql_migrations (version, applied_at) VALUES ('m99991231_000000_from_the_future', 0)",
+    )
+    .await
+    .unwrap();
+  assert_eq!(
+    app.db.last_migration().await.unwrap().as_deref(),
+    Some("m99991231_000000_from_the_future")
+  );
+
+  app
+    .db
+    .writer()
+    .execute_unprepared("DROP TABLE seaql_migrations")
+    .await
+    .unwrap();
+  assert_eq!(app.db.last_migration().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_openapi_is_served_as_yaml() {
+  let app = TestApp::spawn().await;
+  let response = app
+    .get(
+      &format!("{}/api-doc/openapi.yaml", app.cfg.swagger_endpoint),
+      None,
+    )
+    .await;
+  assert_eq!(response.status, StatusCode::OK);
+  let openapi: serde_json::Value = serde_yaml::from_str(response.body.as_str().unwrap()).unwrap();
+  assert_eq!(openapi["openapi"], "3.1.0");
+  assert!(openapi["paths"]["/api/v1/users"].is_object());
+}
+
+#[test]
+#[should_panic(expected = "not matching its schema")]
+fn test_contract_rejects_bodies_not_matching_the_schema() {
+  let body = json!({ "id": "1", "email": 1 });
+  contract::validate(&Method::GET, "/api/v1/users/1", StatusCode::OK, &body);
+}
+
+#[test]
+#[should_panic(expected = "undocumented status")]
+fn test_contract_rejects_undocumented_statuses() {
+  contract::validate(
+    &Method::GET,
+    "/api/v1/users",
+    StatusCode::IM_A_TEAPOT,
+    &json!({}),
+  );
+}
+
+#[tokio::test]
+async fn test_graphiql_basic_auth() {
+  let users = format!("ops:{}", bcrypt::hash("ops-password", 4).unwrap());
+  let app = TestApp::spawn_with(&[("GRAPHQL_BASIC_AUTH", &users)]).await;
+  let graphiql = |credentials: Option<&str>| {
+    let mut req = Request::get(&app.cfg.graphql_endpoint);
+    if let Some(credentials) = credentials {
+      let encoded = general_purpose::STANDARD.encode(credentials);
+      req = req.header(header::AUTHORIZATION, format!("Basic {encoded}"));
+    }
+    app.send(req.body(Body::empty()).unwrap())
+  };
+
+  let anonymous = graphiql(None).await;
+  assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+  assert_eq!(
+    anonymous.headers[header::WWW_AUTHENTICATE],
+    "Basic realm=\"GraphQL\""
+  );
+  assert_eq!(
+    graphiql(Some("ops:wrong")).await.status,
+    StatusCode::UNAUTHORIZED
+  );
+  assert_eq!(
+    graphiql(Some("ops:ops-password")).await.status,
+    StatusCode::OK
+  );
+
+  // Clients outside the allowed networks are rejected whatever their credentials.
+  let app = TestApp::spawn_with(&[
+    ("GRAPHQL_BASIC_AUTH", &users),
+    ("GRAPHQL_BASIC_AUTH_ALLOWED_IPS", "10.0.0.0/8"),
+  ])
+  .await;
+  let encoded = general_purpose::STANDARD.encode("ops:ops-password");
+  let req = Request::get(&app.cfg.graphql_endpoint)
+    .header(header::AUTHORIZATION, format!("Basic {encoded}"))
+    .body(Body::empty())
+    .unwrap();
+  assert_eq!(app.send(req).await.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_security_headers() {
+  let app = TestApp::spawn_with(&[("SECURITY_HSTS_MAX_AGE", "600")]).await;
+
+  let health = app.get("/api/v1/health", None).await;
+  assert_eq!(health.headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+  assert_eq!(health.headers[header::X_FRAME_OPTIONS], "DENY");
+  assert_eq!(health.headers[header::REFERRER_POLICY], "no-referrer");
+  assert_eq!(
+    health.headers[header::STRICT_TRANSPORT_SECURITY],
+    "max-age=600; includeSubDomains"
+  );
+  assert!(health.headers.contains_key("permissions-policy"));
+  assert_eq!(
+    health.headers[header::CONTENT_SECURITY_POLICY],
+    "default-src 'none'; base-uri 'none'; form-action 'none'; frame-ancestors 'none'"
+  );
+
+  // GraphiQL runs its inline script with the nonce of the response.
+  let req = Request::get(&app.cfg.graphql_endpoint)
+    .body(Body::empty())
+    .unwrap();
+  let graphiql = app.send(req).await;
+  let policy = graphiql.headers[header::CONTENT_SECURITY_POLICY]
+    .to_str()
+    .unwrap();
+  let nonce = policy
+    .split("'nonce-")
+    .nth(1)
+    .and_then(|rest| rest.split('\'').next())
+    .expect("GraphiQL policy without a nonce");
+  assert!(policy.contains("https://unpkg.com"));
+  assert!(policy.ends_with("frame-ancestors 'none'"));
+  let html = graphiql.body.as_str().unwrap();
+  assert!(html.contains(&format!("<script nonce=\"{nonce}\">")));
+
+  let again = app
+    .send(
+      Request::get(&app.cfg.graphql_endpoint)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await;
+  assert!(!again.headers[header::CONTENT_SECURITY_POLICY]
+    .to_str()
+    .unwrap()
+    .contains(nonce));
+
+  let swagger = app
+    .get(&format!("{}/", app.cfg.swagger_endpoint), None)
+    .await;
+  assert!(swagger.headers[header::CONTENT_SECURITY_POLICY]
+    .to_str()
+    .unwrap()
+    .contains("style-src 'self' 'unsafe-inline'"));
+}
+
+#[tokio::test]
+async fn test_graphql_users_require_an_admin() {
+  let app = TestApp::spawn().await;
+  let query = "query Users($email: String!) { users(filters: { email: { eq: $email } }) { nodes { email } } }";
+  let variables = json!({ "email": "admin@example.com" });
+
+  let invalid = app.graphql(query, variables.clone(), Some("invalid")).await;
+  assert_eq!(invalid.status, StatusCode::UNAUTHORIZED);
+
+  let anonymous = app.graphql(query, variables.clone(), None).await;
+  assert_eq!(
+    anonymous.body["errors"][0]["message"],
+    "Admin role required"
+  );
+
+  let user = app.register("jane@example.com").await;
+  let forbidden = app
+    .graphql(query, variables.clone(), Some(&user.token))
+    .await;
+  assert_eq!(
+    forbidden.body["errors"][0]["message"],
+    "Admin role required"
+  );
+
+  let admin = app.admin("admin@example.com").await;
+  let response = app.graphql(query, variables, Some(&admin.token)).await;
+  assert_eq!(response.status, StatusCode::OK);
+  assert_eq!(
+    response.body["data"]["users"]["nodes"],
+    json!([{ "email": "admin@example.com" }]),
+    "{}",
+    response.body
+  );
+}
+
+#[tokio::test]
+async fn test_graphql_auth_and_self_service() {
+  let app = TestApp::spawn().await;
+
+  let registered = app
+    .graphql(
+      "mutation($email: String!, $password: String!) { register(email: $email, password: $password, name: \"Jane\") { token user { email } } }",
+      json!({ "email": "jane@example.com", "password": PASSWORD }),
+      None,
+    )
+    .await;
+  assert_eq!(
+    registered.body["data"]["register"]["user"]["email"], "jane@example.com",
+    "{}",
+    registered.body
+  );
+
+  let login = "mutation($password: String!) { login(email: \"jane@example.com\", password: $password) { token } }";
+  let logged_in = app
+    .graphql(login, json!({ "password": PASSWORD }), None)
+    .await;
+  let token = logged_in.body["data"]["login"]["token"].as_str().unwrap();
+
+  let me = app
+    .graphql("{ me { email role } }", json!({}), Some(token))
+    .await;
+  assert_eq!(
+    me.body["data"]["me"],
+    json!({ "email": "jane@example.com", "role": "User" })
+  );
+  let anonymous = app.graphql("{ me { email } }", json!({}), None).await;
+  assert_eq!(anonymous.body["data"]["me"], json!(null));
+
+  let changed = app
+    .graphql(
+      "mutation($password: String!) { changePassword(currentPassword: $password, newPassword: \"changed123\") }",
+      json!({ "password": PASSWORD }),
+      Some(token),
+    )
+    .await;
+  assert_eq!(
+    changed.body["data"]["changePassword"], true,
+    "{}",
+    changed.body
+  );
+  // The tokens issued before the change are revoked.
+  let revoked = app
+    .graphql("{ me { email } }", json!({}), Some(token))
+    .await;
+  assert_eq!(revoked.status, StatusCode::UNAUTHORIZED);
+  let old_password = app
+    .graphql(login, json!({ "password": PASSWORD }), None)
+    .await;
+  assert_eq!(
+    old_password.body["errors"][0]["message"],
+    "Invalid request: Invalid credentials"
+  );
+  app.login("jane@example.com", "changed123").await;
+}
+
+#[tokio::test]
+async fn test_graphql_mutations_are_rolled_back_together() {
+  let app = TestApp::spawn().await;
+  let register = |alias: &str| {
+    format!("{alias}: register(email: \"jane@example.com\", password: $password, name: \"Jane\") {{ token }}")
+  };
+  let query = format!(
+    "mutation($password: String!) {{ {} {} }}",
+    register("first"),
+    register("second")
+  );
+
+  let conflict = app
+    .graphql(&query, json!({ "password": PASSWORD }), None)
+    .await;
+  assert_eq!(
+    conflict.body["errors"][0]["message"],
+    "Conflict: Email already exists"
+  );
+  // The first registration is rolled back with the failed one.
+  app.register("jane@example.com").await;
+}
+
+#[tokio::test]
+async fn test_graphql_hides_passwords() {
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let token = Some(admin.token.as_str());
+
+  let types = app
+    .graphql(
+      "{ users: __type(name: \"Users\") { fields { name } } filters: __type(name: \"UsersFilterInput\") { inputFields { name } } }",
+      json!({}),
+      token,
+    )
+    .await;
+  let names = |list: &serde_json::Value| -> Vec<String> {
+    list
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|field| field["name"].as_str().unwrap().to_string())
+      .collect()
+  };
+  let fields = names(&types.body["data"]["users"]["fields"]);
+  let filters = names(&types.body["data"]["filters"]["inputFields"]);
+  assert!(fields.contains(&"email".to_string()), "{fields:?}");
+  assert!(!fields.contains(&"password".to_string()), "{fields:?}");
+  assert!(filters.contains(&"email".to_string()), "{filters:?}");
+  assert!(!filters.contains(&"password".to_string()), "{filters:?}");
+
+  // Users created over GraphQL get a hashed password.
+  let created = app
+    .graphql(
+      "mutation($password: String!) { createUser(email: \"john@example.com\", password: $password, name: \"John\") { id } }",
+      json!({ "password": PASSWORD }),
+      token,
+    )
+    .await;
+  assert!(created.body["errors"].is_null(), "{}", created.body);
+  app.login("john@example.com", PASSWORD).await;
+}
+
+#[tokio::test]
+async fn test_graphql_errors_carry_a_code_and_the_request_id() {
+  let app = TestApp::spawn().await;
+  let user = app.register("jane@example.com").await;
+
+  let response = app
+    .graphql(
+      "mutation { deleteUser(id: \"00000000-0000-0000-0000-000000000000\") }",
+      json!({}),
+      Some(&user.token),
+    )
+    .await;
+  let error = &response.body["errors"][0];
+  assert_eq!(
+    error["extensions"]["code"], "FORBIDDEN",
+    "{}",
+    response.body
+  );
+  let request_id = response.headers["x-request-id"].to_str().unwrap();
+  assert_eq!(error["extensions"]["requestId"], request_id);
+}
+
+#[tokio::test]
+async fn test_graphql_schema_is_served_as_sdl() {
+  let app = TestApp::spawn().await;
+  let response = app
+    .get(
+      &format!("{}/schema.graphql", app.cfg.graphql_endpoint),
+      None,
+    )
+    .await;
+  assert_eq!(response.status, StatusCode::OK);
+  let sdl = response.body.as_str().unwrap();
+  assert!(sdl.contains("type Query {"), "{sdl}");
+}
+
+#[tokio::test]
+async fn test_graphql_subscription_over_websocket() {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let address = app.serve().await;
+
+  let mut request = format!("ws://{address}{}/ws", app.cfg.graphql_endpoint)
+    .into_client_request()
+    .unwrap();
+  request.headers_mut().insert(
+    "sec-websocket-protocol",
+    "graphql-transport-ws".parse().unwrap(),
+  );
+  let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+  let mut send = async |message: serde_json::Value| {
+    socket
+      .send(Message::text(message.to_string()))
+      .await
+      .unwrap();
+  };
+  send(json!({
+    "type": "connection_init",
+    "payload": { "Authorization": format!("Bearer {}", admin.token) }
+  }))
+  .await;
+  send(json!({
+    "id": "1",
+    "type": "subscribe",
+    "payload": { "query": "subscription { userCreated { email } }" }
+  }))
+  .await;
+
+  let mut next = async || loop {
+    let message = tokio::time::timeout(std::time::Duration::from_secs(10), socket.next())
+      .await
+      .expect("No message within 10 seconds")
+      .unwrap()
+      .unwrap();
+    if let Message::Text(text) = message {
+      return serde_json::from_str::<serde_json::Value>(&text).unwrap();
+    }
+  };
+  assert_eq!(next().await["type"], "connection_ack");
+
+  // The subscription is registered asynchronously, registering until it is seen.
+  let mut index = 0;
+  let event = loop {
+    index += 1;
+    let email = format!("jane.{index}@example.com");
+    app.register(&email).await;
+    if let Ok(message) = tokio::time::timeout(std::time::Duration::from_millis(500), next()).await {
+      break message;
+    }
+  };
+  assert_eq!(event["type"], "next");
+  assert!(event["payload"]["data"]["userCreated"]["email"]
+    .as_str()
+    .unwrap()
+    .ends_with("@example.com"));
+}
+
+#[tokio::test]
+async fn test_graphql_websocket_is_closed_when_the_token_expires() {
+  use futures_util::{SinkExt, StreamExt};
+  use jsonwebtoken::{encode, EncodingKey, Header};
+  use server::modules::auth::guards::auth_guard::Claims;
+  use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let address = app.serve().await;
+  let claims = Claims {
+    sub: admin.user.id.clone(),
+    exp: (chrono::Utc::now().timestamp() + 2) as usize,
+    iat: 0,
+    user: admin.user,
+    ver: 0,
+  };
+  let token = encode(
+    &Header::default(),
+    &claims,
+    &EncodingKey::from_secret(app.cfg.jwt_secret.expose().as_bytes()),
+  )
+  .unwrap();
+
+  let mut request = format!("ws://{address}{}/ws", app.cfg.graphql_endpoint)
+    .into_client_request()
+    .unwrap();
+  request.headers_mut().insert(
+    "sec-websocket-protocol",
+    "graphql-transport-ws".parse().unwrap(),
+  );
+  let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+  let init = json!({
+    "type": "connection_init",
+    "payload": { "Authorization": format!("Bearer {token}") }
+  });
+  socket.send(Message::text(init.to_string())).await.unwrap();
+
+  let mut messages = Vec::new();
+  let close = loop {
+    let message = tokio::time::timeout(std::time::Duration::from_secs(10), socket.next())
+      .await
+      .expect("The connection outlived its token")
+      .unwrap()
+      .unwrap();
+    match message {
+      Message::Close(frame) => break frame.unwrap(),
+      Message::Text(text) => {
+        messages.push(serde_json::from_str::<serde_json::Value>(&text).unwrap())
+      }
+      _ => {}
+    }
+  };
+  assert_eq!(messages[0]["type"], "connection_ack");
+  assert_eq!(u16::from(close.code), 4401);
+  assert_eq!(close.reason.as_str(), "Token has expired");
+}
+
+#[tokio::test]
+async fn test_graphql_websocket_is_closed_on_shutdown() {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let address = app.serve().await;
+
+  let mut request = format!("ws://{address}{}/ws", app.cfg.graphql_endpoint)
+    .into_client_request()
+    .unwrap();
+  request.headers_mut().insert(
+    "sec-websocket-protocol",
+    "graphql-transport-ws".parse().unwrap(),
+  );
+  let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+  let init = json!({
+    "type": "connection_init",
+    "payload": { "Authorization": format!("Bearer {}", admin.token) }
+  });
+  socket.send(Message::text(init.to_string())).await.unwrap();
+  let ack = socket.next().await.unwrap().unwrap();
+  assert!(ack.to_text().unwrap().contains("connection_ack"), "{ack}");
+  assert_eq!(app.shutdown.running_tasks(), 1);
+
+  app.shutdown.trigger();
+  let close = loop {
+    let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+      .await
+      .expect("The connection outlived the server")
+      .unwrap()
+      .unwrap();
+    if let Message::Close(frame) = message {
+      break frame.unwrap();
+    }
+  };
+  assert_eq!(u16::from(close.code), 1001);
+  assert_eq!(close.reason.as_str(), "Server is shutting down");
+  tokio::time::timeout(
+    std::time::Duration::from_secs(5),
+    app.shutdown.wait_for_tasks(),
+  )
+  .await
+  .expect("The connection is not waited for");
+}
+
+#[tokio::test]
+async fn test_graphql_page_size_limit() {
+  let app = TestApp::spawn().await;
+  let admin = app.admin("admin@example.com").await;
+  let query = "query Users($limit: Int!) { users(pagination: { page: { limit: $limit, page: 0 } }) { nodes { email } } }";
+
+  let response = app
+    .graphql(query, json!({ "limit": 10 }), Some(&admin.token))
+    .await;
+  assert_eq!(
+    response.body["data"]["users"]["nodes"],
+    json!([{ "email": "admin@example.com" }])
+  );
+
+  let limit = app.cfg.graphql_max_page_size + 1;
+  let response = app
+    .graphql(query, json!({ "limit": limit }), Some(&admin.token))
+    .await;
+  assert_eq!(
+    response.body["errors"][0]["extensions"]["code"], "PAGE_SIZE_LIMIT_EXCEEDED",
+    "{}",
+    response.body
+  );
+}
+
+#[tokio::test]
+async fn test_graphql_automatic_persisted_queries() {
+  let app = TestApp::spawn_with(&[("GRAPHQL_PERSISTED_QUERIES", "postgres")]).await;
+  let admin = app.admin("admin@example.com").await;
+  let token = Some(admin.token.as_str());
+  let query = "{ users { nodes { email } } }";
+  let extensions = json!({ "persistedQuery": { "version": 1, "sha256Hash": sha256(query) } });
+
+  let missing = app
+    .post(
+      &app.cfg.graphql_endpoint,
+      json!({ "extensions": extensions }),
+      token,
+    )
+    .await;
+  assert_eq!(
+    missing.body["errors"][0]["message"],
+    "PersistedQueryNotFound"
+  );
+
+  let registered = app
+    .post(
+      &app.cfg.graphql_endpoint,
+      json!({ "query": query, "extensions": extensions }),
+      token,
+    )
+    .await;
+  assert!(registered.body["errors"].is_null(), "{}", registered.body);
+
+  // The query is read back from the database by a fresh instance.
+  let mut app = app;
+  app.router = server::app::router(server::app::AppState::new(app.cfg.clone(), app.db.clone()));
+  let persisted = app
+    .post(
+      &app.cfg.graphql_endpoint,
+      json!({ "extensions": extensions }),
+      token,
+    )
+    .await;
+  assert_eq!(
+    persisted.body["data"]["users"]["nodes"],
+    json!([{ "email": "admin@example.com" }]),
+    "{}",
+    persisted.body
+  );
+  // Queries sent by hash are not mistaken for writes.
+  assert!(persisted.headers.get("set-cookie").is_none());
+}
@@ -0,0 +1,88 @@
+//! Validation of the responses against the OpenAPI specification of `ApiDoc`.
+//!
+//! Every response of a documented operation must have a documented status, and its body must
+//! match the schema of that response, so that the specification cannot drift from the handlers.
+
+use std::sync::LazyLock;
+
+use axum::http::{Method, StatusCode};
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use server::doc::ApiDoc;
+
+static SPEC: LazyLock<Value> =
+  LazyLock::new(|| serde_json::to_value(ApiDoc::openapi()).expect("Invalid OpenAPI specification"));
+
+/// Panics if the response to `method` on `path` does not match the specification.
+///
+/// Paths without a documented operation, such as the GraphQL endpoint, are not checked.
+pub fn validate(method: &Method, path: &str, status: StatusCode, body: &Value) {
+  let Some((template, operation)) = operation(method, path) else {
+    return;
+  };
+  let request = format!("{method} {template}");
+
+  let response = operation["responses"]
+    .get(status.as_str())
+    .or_else(|| operation["responses"].get("default"))
+    .unwrap_or_else(|| panic!("{request} responded with undocumented status {status}: {body}"));
+
+  let Some(schema) = response.pointer("/content/application~1json/schema") else {
+    assert!(
+      body.is_null(),
+      "{request} responded {status} with an undocumented body: {body}"
+    );
+    return;
+  };
+
+  // The schemas refer to the components of the specification.
+  let mut schema = schema.clone();
+  schema["components"] = SPEC["components"].clone();
+  let validator = jsonschema::validator_for(&schema)
+    .unwrap_or_else(|e| panic!("Invalid schema of {request} {status}: {e}"));
+  let errors: Vec<String> = validator
+    .iter_errors(body)
+    .map(|e| format!("{e} at `{}`", e.instance_path))
+    .collect();
+  assert!(
+    errors.is_empty(),
+    "{request} responded {status} with a body not matching its schema:\n{}\n{body}",
+    errors.join("\n")
+  );
+}
+
+/// Returns the path template and the operation documented for a request, preferring literal
+/// segments over parameters.
+fn operation<'a>(method: &Method, path: &str) -> Option<(&'a str, &'a Value)> {
+  let path = path.split('?').next().unwrap_or(path);
+  let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+
+  SPEC["paths"]
+    .as_object()?
+    .iter()
+    .filter_map(|(template, item)| {
+      let parameters = parameters(template, &segments)?;
+      let operation = item.get(method.as_str().to_ascii_lowercase())?;
+      Some((parameters, template.as_str(), operation))
+    })
+    .min_by_key(|(parameters, ..)| *parameters)
+    .map(|(_, template, operation)| (template, operation))
+}
+
+/// The number of parameters through which a template matches the segments of a path.
+fn parameters(template: &str, segments: &[&str]) -> Option<usize> {
+  let template: Vec<&str> = template.split('/').collect();
+  if template.len() != segments.len() {
+    return None;
+  }
+  let mut parameters = 0;
+  for (expected, actual) in template.iter().zip(segments) {
+    if expected.starts_with('{') && expected.ends_with('}') {
+      parameters += 1;
+    } else if expected != actual {
+      return None;
+    }
+  }
+  Some(parameters)
+}